@@ -57,6 +57,18 @@ pub fn public_negate(public: &mut Public) -> Result<(), Error> {
 	Ok(())
 }
 
+/// Parse public key from compressed (33 bytes), uncompressed (65 bytes) or raw (64 bytes) encoding,
+/// checking that it is a valid curve point
+pub fn public_from_slice(data: &[u8]) -> Result<Public, Error> {
+	let key_public = match data.len() {
+		64 => to_secp256k1_public(&Public::from_slice(data))?,
+		_ => key::PublicKey::from_slice(&SECP256K1, data)?,
+	};
+	let mut public = Public::default();
+	set_public(&mut public, &key_public);
+	Ok(public)
+}
+
 /// Return base point of secp256k1
 pub fn generation_point() -> Public {
 	let mut public_sec_raw = [0u8; 65];
@@ -0,0 +1,122 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! benchmarking for ACL checks
+//! should be started with:
+//! ```bash
+//! multirust run nightly cargo bench
+//! ```
+
+#![feature(test)]
+
+extern crate test;
+extern crate ethkey;
+extern crate ethcore_util;
+extern crate ethcore_secretstore;
+
+use std::sync::{Arc, Barrier};
+use std::thread;
+use std::time::Duration;
+use test::{Bencher, black_box};
+use ethkey::{Random, Generator, public_to_address};
+use ethcore_util::H256;
+use ethcore_secretstore::{AclStorage, CachedAclStorage, CachedAclStorageConfig, SystemClock, Error, DocumentAddress, Requester};
+
+/// Number of documents, checked by single requester in every iteration.
+const DOCUMENTS_COUNT: u64 = 1000;
+/// Number of threads, concurrently checking documents in contention benchmarks.
+const THREADS_COUNT: usize = 8;
+
+/// ACL storage, which grants access to everyone.
+struct AllowAllAclStorage;
+
+impl AclStorage for AllowAllAclStorage {
+	fn check(&self, _requester: &Requester, _document: &DocumentAddress) -> Result<bool, Error> {
+		Ok(true)
+	}
+}
+
+fn cached_storage() -> CachedAclStorage {
+	CachedAclStorage::new(Arc::new(AllowAllAclStorage), Duration::from_secs(3600))
+}
+
+#[bench]
+fn many_documents_by_public(b: &mut Bencher) {
+	let acl_storage = cached_storage();
+	let requester = Requester::Public(Random.generate().unwrap().public().clone());
+	b.iter(|| {
+		for document in 0..DOCUMENTS_COUNT {
+			black_box(acl_storage.check(&requester, &H256::from(document)).unwrap());
+		}
+	});
+}
+
+#[bench]
+fn many_documents_by_address(b: &mut Bencher) {
+	let acl_storage = cached_storage();
+	let address = public_to_address(Random.generate().unwrap().public());
+	b.iter(|| {
+		for document in 0..DOCUMENTS_COUNT {
+			black_box(acl_storage.check_by_address(&address, &H256::from(document)).unwrap());
+		}
+	});
+}
+
+/// Check documents from many threads at once, every thread using its own requester.
+fn concurrent_checks(b: &mut Bencher, shards: usize) {
+	let acl_storage = Arc::new(CachedAclStorage::with_config(Arc::new(AllowAllAclStorage), CachedAclStorageConfig {
+		ttl: Duration::from_secs(3600),
+		shards: shards,
+		..Default::default()
+	}, Arc::new(SystemClock)));
+	let requesters: Vec<_> = (0..THREADS_COUNT).map(|_| Requester::Public(Random.generate().unwrap().public().clone())).collect();
+	b.iter(|| {
+		let barrier = Arc::new(Barrier::new(THREADS_COUNT));
+		let threads: Vec<_> = requesters.iter().cloned().map(|requester| {
+			let acl_storage = acl_storage.clone();
+			let barrier = barrier.clone();
+			thread::spawn(move || {
+				barrier.wait();
+				for document in 0..DOCUMENTS_COUNT {
+					black_box(acl_storage.check(&requester, &H256::from(document)).unwrap());
+				}
+			})
+		}).collect();
+		for thread in threads {
+			thread.join().unwrap();
+		}
+	});
+}
+
+#[bench]
+fn concurrent_checks_single_shard(b: &mut Bencher) {
+	concurrent_checks(b, 1);
+}
+
+#[bench]
+fn concurrent_checks_4_shards(b: &mut Bencher) {
+	concurrent_checks(b, 4);
+}
+
+#[bench]
+fn concurrent_checks_16_shards(b: &mut Bencher) {
+	concurrent_checks(b, 16);
+}
+
+#[bench]
+fn concurrent_checks_64_shards(b: &mut Bencher) {
+	concurrent_checks(b, 64);
+}
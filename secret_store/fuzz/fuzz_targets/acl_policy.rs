@@ -0,0 +1,33 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Feeds arbitrary input to ACL policy parsers, which must fail with an error instead of panicking.
+//!
+//! Run with `cargo fuzz run acl_policy` from the `secret_store` directory (requires nightly and `cargo install cargo-fuzz`).
+
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate ethcore_secretstore;
+
+use std::str;
+use ethcore_secretstore::{AttributeExpression, IpNetwork};
+
+fuzz_target!(|data: &[u8]| {
+	if let Ok(policy) = str::from_utf8(data) {
+		let _ = policy.parse::<AttributeExpression>();
+		let _ = policy.parse::<IpNetwork>();
+	}
+});
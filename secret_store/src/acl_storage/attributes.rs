@@ -0,0 +1,737 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+use std::str::FromStr;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+use parking_lot::{Mutex, Condvar};
+use types::all::{Error, DocumentAddress, Address, Requester};
+use super::{AclStorage, Clock, SystemClock};
+use super::background::BackgroundCalls;
+
+/// Default time, after which attributes resolution is treated as failed.
+const DEFAULT_RESOLUTION_TIMEOUT_MS: u64 = 1000;
+/// Default time, during which resolved attributes are reused.
+const DEFAULT_ATTRIBUTES_TTL_SECS: u64 = 10;
+/// Default max number of outstanding attributes resolutions.
+const DEFAULT_MAX_PENDING_RESOLUTIONS: usize = 16;
+/// Default max number of cached requesters (and, separately, documents) attributes.
+const DEFAULT_MAX_CACHED_ATTRIBUTES: usize = 4096;
+/// Max number of tokens in attribute expression. Limits size of compiled expression, which is evaluated recursively.
+const MAX_EXPRESSION_TOKENS: usize = 1024;
+/// Max nesting level of parentheses and negations in attribute expression.
+const MAX_EXPRESSION_DEPTH: usize = 32;
+
+/// Attributes of requester or document.
+pub type Attributes = HashMap<String, String>;
+
+/// Source of requesters and documents attributes.
+pub trait AttributeResolver: Send + Sync {
+	/// Get attributes of the requester.
+	fn requester_attributes(&self, requester: &Address) -> Result<Attributes, Error>;
+	/// Get attributes of the document.
+	fn document_attributes(&self, document: &DocumentAddress) -> Result<Attributes, Error>;
+}
+
+#[derive(Debug, Clone)]
+/// Attribute-based ACL storage configuration.
+pub struct AttributeAclStorageConfig {
+	/// Time, after which attributes resolution is treated as failed.
+	pub resolution_timeout: Duration,
+	/// Time, during which resolved attributes are reused.
+	pub attributes_ttl: Duration,
+	/// Max number of outstanding resolutions, including resolutions, which have timed out, but are still running.
+	/// When the limit is reached, checks are failing with `AclError::Overloaded`.
+	pub max_pending_resolutions: usize,
+	/// Max number of cached requesters (and, separately, documents) attributes. When the limit is reached,
+	/// expired attributes are dropped and then the oldest attributes are evicted.
+	pub max_cached_attributes: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Compiled boolean expression over requester and document attributes. Expressions are made of:
+/// - comparisons `operand == operand` and `operand != operand`, where operand is either an attribute
+///   (`requester.<name>` or `document.<name>`) or a double-quoted string;
+/// - `true` and `false` literals;
+/// - `!`, `&&` and `||` operators (in order of precedence) and parentheses.
+/// Comparison with an attribute, which is missing, fails the evaluation.
+pub enum AttributeExpression {
+	/// Constant.
+	Literal(bool),
+	/// Operands are equal.
+	Equals(Operand, Operand),
+	/// Operands are not equal.
+	NotEquals(Operand, Operand),
+	/// Negation.
+	Not(Box<AttributeExpression>),
+	/// Conjunction.
+	And(Box<AttributeExpression>, Box<AttributeExpression>),
+	/// Disjunction.
+	Or(Box<AttributeExpression>, Box<AttributeExpression>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Operand of the attribute expression comparison.
+pub enum Operand {
+	/// Requester attribute with given name.
+	Requester(String),
+	/// Document attribute with given name.
+	Document(String),
+	/// String constant.
+	Value(String),
+}
+
+/// ACL storage, which grants access when the expression over requester and document attributes evaluates to true.
+/// Expression does not depend on the check scope, so scoped checks are not supported and fail.
+pub struct AttributeAclStorage {
+	/// Attributes resolver.
+	resolver: Arc<AttributeResolver>,
+	/// Access policy.
+	expression: AttributeExpression,
+	/// Storage configuration.
+	config: AttributeAclStorageConfig,
+	/// Time source.
+	clock: Arc<Clock>,
+	/// Outstanding resolutions.
+	resolutions: BackgroundCalls,
+	/// Requesters attributes.
+	requesters: AttributesCache<Address>,
+	/// Documents attributes.
+	documents: AttributesCache<DocumentAddress>,
+}
+
+/// Resolved attributes and resolutions in progress.
+struct AttributesCache<K> {
+	/// Resolved attributes => time of resolution.
+	resolved: Mutex<HashMap<K, (Instant, Arc<Attributes>)>>,
+	/// Resolutions in progress. Concurrent checks wait for the same resolution instead of starting their own.
+	resolving: Mutex<HashMap<K, Arc<Resolution>>>,
+}
+
+/// Single resolution of attributes.
+struct Resolution {
+	/// Result of the resolution. None while resolution is in progress.
+	result: Mutex<Option<Result<Arc<Attributes>, Error>>>,
+	/// Signalled when resolution is completed.
+	completed: Condvar,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Token of the attribute expression.
+enum Token {
+	/// Attribute name or literal.
+	Ident(String),
+	/// Quoted string.
+	Value(String),
+	/// `==`.
+	Equals,
+	/// `!=`.
+	NotEquals,
+	/// `!`.
+	Not,
+	/// `&&`.
+	And,
+	/// `||`.
+	Or,
+	/// `(`.
+	LeftParen,
+	/// `)`.
+	RightParen,
+}
+
+/// Attribute expression parser.
+struct Parser {
+	/// Expression tokens.
+	tokens: Vec<Token>,
+	/// Index of the next token.
+	position: usize,
+	/// Current nesting level.
+	depth: usize,
+}
+
+impl Default for AttributeAclStorageConfig {
+	fn default() -> Self {
+		AttributeAclStorageConfig {
+			resolution_timeout: Duration::from_millis(DEFAULT_RESOLUTION_TIMEOUT_MS),
+			attributes_ttl: Duration::from_secs(DEFAULT_ATTRIBUTES_TTL_SECS),
+			max_pending_resolutions: DEFAULT_MAX_PENDING_RESOLUTIONS,
+			max_cached_attributes: DEFAULT_MAX_CACHED_ATTRIBUTES,
+		}
+	}
+}
+
+impl AttributeAclStorage {
+	/// Create new attribute-based ACL storage with given policy expression.
+	pub fn new(resolver: Arc<AttributeResolver>, expression: &str) -> Result<Self, Error> {
+		AttributeAclStorage::with_config(resolver, expression, Default::default())
+	}
+
+	/// Create new attribute-based ACL storage with given configuration.
+	pub fn with_config(resolver: Arc<AttributeResolver>, expression: &str, config: AttributeAclStorageConfig) -> Result<Self, Error> {
+		AttributeAclStorage::with_clock(resolver, expression, config, Arc::new(SystemClock))
+	}
+
+	/// Create new attribute-based ACL storage, which uses given clock to expire resolved attributes.
+	pub fn with_clock(resolver: Arc<AttributeResolver>, expression: &str, config: AttributeAclStorageConfig, clock: Arc<Clock>) -> Result<Self, Error> {
+		Ok(AttributeAclStorage {
+			resolver: resolver,
+			expression: expression.parse()?,
+			resolutions: BackgroundCalls::new(config.max_pending_resolutions),
+			config: config,
+			clock: clock,
+			requesters: AttributesCache::new(),
+			documents: AttributesCache::new(),
+		})
+	}
+
+	/// Get requester attributes, resolving them if required.
+	fn requester_attributes(&self, requester: &Address) -> Result<Arc<Attributes>, Error> {
+		let resolver = self.resolver.clone();
+		let key = requester.clone();
+		self.attributes(&self.requesters, requester, move || resolver.requester_attributes(&key))
+	}
+
+	/// Get document attributes, resolving them if required.
+	fn document_attributes(&self, document: &DocumentAddress) -> Result<Arc<Attributes>, Error> {
+		let resolver = self.resolver.clone();
+		let key = document.clone();
+		self.attributes(&self.documents, document, move || resolver.document_attributes(&key))
+	}
+
+	/// Get cached attributes or resolve them within configured timeout.
+	fn attributes<K, F>(&self, cache: &AttributesCache<K>, key: &K, resolve: F) -> Result<Arc<Attributes>, Error>
+		where K: Hash + Eq + Clone, F: FnOnce() -> Result<Attributes, Error> + Send + 'static {
+		let now = self.clock.now();
+		if let Some(attributes) = cache.fresh(key, now, self.config.attributes_ttl) {
+			return Ok(attributes);
+		}
+
+		let (resolution, is_leader) = {
+			let mut resolving = cache.resolving.lock();
+			// resolution could have been completed after the cache has been checked
+			if let Some(attributes) = cache.fresh(key, now, self.config.attributes_ttl) {
+				return Ok(attributes);
+			}
+
+			match resolving.get(key) {
+				Some(resolution) => (resolution.clone(), false),
+				None => {
+					let resolution = Arc::new(Resolution {
+						result: Mutex::new(None),
+						completed: Condvar::new(),
+					});
+					resolving.insert(key.clone(), resolution.clone());
+					(resolution, true)
+				},
+			}
+		};
+
+		if !is_leader {
+			return resolution.wait(self.config.resolution_timeout);
+		}
+
+		let result = match self.resolutions.call(self.config.resolution_timeout, resolve) {
+			Ok(Some(Ok(attributes))) => Ok(Arc::new(attributes)),
+			Ok(Some(Err(error))) | Err(error) => Err(error),
+			Ok(None) => Err(Error::Internal("attributes resolution has timed out".to_owned())),
+		};
+		if let Ok(ref attributes) = result {
+			cache.insert(key.clone(), now, attributes.clone(), self.config.attributes_ttl, self.config.max_cached_attributes);
+		}
+
+		*resolution.result.lock() = Some(result.clone());
+		resolution.completed.notify_all();
+		cache.resolving.lock().remove(key);
+		result
+	}
+}
+
+impl<K> AttributesCache<K> where K: Hash + Eq + Clone {
+	fn new() -> Self {
+		AttributesCache {
+			resolved: Mutex::new(HashMap::new()),
+			resolving: Mutex::new(HashMap::new()),
+		}
+	}
+
+	/// Get cached attributes, which are not yet expired.
+	fn fresh(&self, key: &K, now: Instant, ttl: Duration) -> Option<Arc<Attributes>> {
+		self.resolved.lock().get(key)
+			.and_then(|&(resolved_at, ref attributes)| if resolved_at + ttl > now { Some(attributes.clone()) } else { None })
+	}
+
+	/// Cache resolved attributes. When cache is full, expired attributes are dropped first, then the oldest ones.
+	fn insert(&self, key: K, now: Instant, attributes: Arc<Attributes>, ttl: Duration, max_entries: usize) {
+		let mut resolved = self.resolved.lock();
+		if resolved.len() >= max_entries && !resolved.contains_key(&key) {
+			let expired: Vec<_> = resolved.iter()
+				.filter(|&(_, &(resolved_at, _))| resolved_at + ttl <= now)
+				.map(|(key, _)| key.clone())
+				.collect();
+			for key in expired {
+				resolved.remove(&key);
+			}
+
+			if resolved.len() >= max_entries {
+				let oldest = resolved.iter()
+					.min_by_key(|&(_, &(resolved_at, _))| resolved_at)
+					.map(|(key, _)| key.clone());
+				if let Some(oldest) = oldest {
+					resolved.remove(&oldest);
+				}
+			}
+		}
+
+		resolved.insert(key, (now, attributes));
+	}
+
+	/// Get number of cached attributes.
+	fn len(&self) -> usize {
+		self.resolved.lock().len()
+	}
+}
+
+impl Resolution {
+	/// Wait for the result of resolution, started by other check.
+	fn wait(&self, timeout: Duration) -> Result<Arc<Attributes>, Error> {
+		let deadline = Instant::now() + timeout;
+		let mut result = self.result.lock();
+		while result.is_none() {
+			if self.completed.wait_until(&mut result, deadline).timed_out() {
+				break;
+			}
+		}
+
+		match *result {
+			Some(ref result) => result.clone(),
+			None => Err(Error::Internal("attributes resolution has timed out".to_owned())),
+		}
+	}
+}
+
+impl AclStorage for AttributeAclStorage {
+	fn check(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		let requester = requester.address(document)?;
+		let requester_attributes = self.requester_attributes(&requester)?;
+		let document_attributes = self.document_attributes(document)?;
+		self.expression.evaluate(&requester_attributes, &document_attributes)
+	}
+}
+
+impl AttributeExpression {
+	/// Evaluate expression over given attributes.
+	pub fn evaluate(&self, requester: &Attributes, document: &Attributes) -> Result<bool, Error> {
+		match *self {
+			AttributeExpression::Literal(value) => Ok(value),
+			AttributeExpression::Equals(ref left, ref right) => Ok(left.value(requester, document)? == right.value(requester, document)?),
+			AttributeExpression::NotEquals(ref left, ref right) => Ok(left.value(requester, document)? != right.value(requester, document)?),
+			AttributeExpression::Not(ref expression) => expression.evaluate(requester, document).map(|value| !value),
+			AttributeExpression::And(ref left, ref right) => Ok(left.evaluate(requester, document)? && right.evaluate(requester, document)?),
+			AttributeExpression::Or(ref left, ref right) => Ok(left.evaluate(requester, document)? || right.evaluate(requester, document)?),
+		}
+	}
+}
+
+impl FromStr for AttributeExpression {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Error> {
+		let mut parser = Parser {
+			tokens: tokenize(s)?,
+			position: 0,
+			depth: 0,
+		};
+		let expression = parser.parse_or()?;
+		match parser.next() {
+			None => Ok(expression),
+			Some(token) => Err(Error::Internal(format!("unexpected {:?} in attribute expression", token))),
+		}
+	}
+}
+
+impl Operand {
+	/// Get value of the operand.
+	fn value<'a>(&'a self, requester: &'a Attributes, document: &'a Attributes) -> Result<&'a str, Error> {
+		let (attributes, name, kind) = match *self {
+			Operand::Value(ref value) => return Ok(value.as_str()),
+			Operand::Requester(ref name) => (requester, name, "requester"),
+			Operand::Document(ref name) => (document, name, "document"),
+		};
+
+		attributes.get(name)
+			.map(|value| value.as_str())
+			.ok_or_else(|| Error::Internal(format!("{} attribute {} is missing", kind, name)))
+	}
+}
+
+impl Parser {
+	/// Get next token.
+	fn next(&mut self) -> Option<Token> {
+		let token = self.tokens.get(self.position).cloned();
+		self.position += 1;
+		token
+	}
+
+	/// Check if next token is `token` and skip it.
+	fn skip(&mut self, token: &Token) -> bool {
+		if self.tokens.get(self.position) == Some(token) {
+			self.position += 1;
+			return true;
+		}
+
+		false
+	}
+
+	fn parse_or(&mut self) -> Result<AttributeExpression, Error> {
+		let mut expression = self.parse_and()?;
+		while self.skip(&Token::Or) {
+			expression = AttributeExpression::Or(Box::new(expression), Box::new(self.parse_and()?));
+		}
+		Ok(expression)
+	}
+
+	fn parse_and(&mut self) -> Result<AttributeExpression, Error> {
+		let mut expression = self.parse_not()?;
+		while self.skip(&Token::And) {
+			expression = AttributeExpression::And(Box::new(expression), Box::new(self.parse_not()?));
+		}
+		Ok(expression)
+	}
+
+	/// Parse nested expression, failing if max nesting level is reached.
+	fn parse_nested<F>(&mut self, parse: F) -> Result<AttributeExpression, Error> where F: FnOnce(&mut Self) -> Result<AttributeExpression, Error> {
+		if self.depth >= MAX_EXPRESSION_DEPTH {
+			return Err(Error::Internal(format!("attribute expression is nested deeper than {} levels", MAX_EXPRESSION_DEPTH)));
+		}
+
+		self.depth += 1;
+		let expression = parse(self);
+		self.depth -= 1;
+		expression
+	}
+
+	fn parse_not(&mut self) -> Result<AttributeExpression, Error> {
+		if self.skip(&Token::Not) {
+			let expression = self.parse_nested(|parser| parser.parse_not())?;
+			return Ok(AttributeExpression::Not(Box::new(expression)));
+		}
+
+		self.parse_primary()
+	}
+
+	fn parse_primary(&mut self) -> Result<AttributeExpression, Error> {
+		if self.skip(&Token::LeftParen) {
+			let expression = self.parse_nested(|parser| parser.parse_or())?;
+			if !self.skip(&Token::RightParen) {
+				return Err(Error::Internal("missing closing parenthesis in attribute expression".to_owned()));
+			}
+			return Ok(expression);
+		}
+
+		if self.skip(&Token::Ident("true".to_owned())) {
+			return Ok(AttributeExpression::Literal(true));
+		}
+		if self.skip(&Token::Ident("false".to_owned())) {
+			return Ok(AttributeExpression::Literal(false));
+		}
+
+		let left = self.parse_operand()?;
+		let equals = match self.next() {
+			Some(Token::Equals) => true,
+			Some(Token::NotEquals) => false,
+			token => return Err(Error::Internal(format!("expected comparison in attribute expression, found {:?}", token))),
+		};
+		let right = self.parse_operand()?;
+		Ok(if equals { AttributeExpression::Equals(left, right) } else { AttributeExpression::NotEquals(left, right) })
+	}
+
+	fn parse_operand(&mut self) -> Result<Operand, Error> {
+		match self.next() {
+			Some(Token::Value(value)) => Ok(Operand::Value(value)),
+			Some(Token::Ident(ident)) => {
+				let mut parts = ident.splitn(2, '.');
+				let root = parts.next().unwrap_or("");
+				let name = parts.next().unwrap_or("");
+				if name.is_empty() {
+					return Err(Error::Internal(format!("invalid attribute {} in attribute expression", ident)));
+				}
+
+				match root {
+					"requester" => Ok(Operand::Requester(name.to_owned())),
+					"document" => Ok(Operand::Document(name.to_owned())),
+					_ => Err(Error::Internal(format!("unknown attribute {} in attribute expression", ident))),
+				}
+			},
+			token => Err(Error::Internal(format!("expected operand in attribute expression, found {:?}", token))),
+		}
+	}
+}
+
+/// Split attribute expression into tokens.
+fn tokenize(s: &str) -> Result<Vec<Token>, Error> {
+	let mut tokens = Vec::new();
+	let mut chars = s.chars().peekable();
+	while let Some(c) = chars.next() {
+		let token = match c {
+			' ' | '\t' | '\n' | '\r' => continue,
+			'(' => Token::LeftParen,
+			')' => Token::RightParen,
+			'!' if chars.peek() == Some(&'=') => {
+				chars.next();
+				Token::NotEquals
+			},
+			'!' => Token::Not,
+			'=' if chars.peek() == Some(&'=') => {
+				chars.next();
+				Token::Equals
+			},
+			'&' if chars.peek() == Some(&'&') => {
+				chars.next();
+				Token::And
+			},
+			'|' if chars.peek() == Some(&'|') => {
+				chars.next();
+				Token::Or
+			},
+			'"' => {
+				let mut value = String::new();
+				loop {
+					match chars.next() {
+						Some('"') => break,
+						Some(c) => value.push(c),
+						None => return Err(Error::Internal("unterminated string in attribute expression".to_owned())),
+					}
+				}
+				Token::Value(value)
+			},
+			c if c.is_alphanumeric() || c == '_' => {
+				let mut ident = c.to_string();
+				while let Some(&c) = chars.peek() {
+					if !c.is_alphanumeric() && c != '_' && c != '.' {
+						break;
+					}
+					ident.push(c);
+					chars.next();
+				}
+				Token::Ident(ident)
+			},
+			c => return Err(Error::Internal(format!("unexpected character {} in attribute expression", c))),
+		};
+		if tokens.len() == MAX_EXPRESSION_TOKENS {
+			return Err(Error::Internal(format!("attribute expression has more than {} tokens", MAX_EXPRESSION_TOKENS)));
+		}
+		tokens.push(token);
+	}
+
+	Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::sync::atomic::{AtomicUsize, Ordering};
+	use std::thread;
+	use std::time::Duration;
+	use util::Address;
+	use types::all::{Error, AclError, DocumentAddress, Requester};
+	use acl_storage::AclStorage;
+	use acl_storage::clock::tests::MockClock;
+	use super::{AttributeAclStorage, AttributeAclStorageConfig, AttributeExpression, AttributeResolver, Attributes};
+
+	#[derive(Default)]
+	struct DummyAttributeResolver {
+		delay: Option<Duration>,
+		resolutions: AtomicUsize,
+	}
+
+	fn attributes(values: &[(&str, &str)]) -> Attributes {
+		values.iter().map(|&(name, value)| (name.to_owned(), value.to_owned())).collect()
+	}
+
+	impl AttributeResolver for DummyAttributeResolver {
+		fn requester_attributes(&self, requester: &Address) -> Result<Attributes, Error> {
+			self.resolutions.fetch_add(1, Ordering::SeqCst);
+			if let Some(delay) = self.delay {
+				thread::sleep(delay);
+			}
+
+			Ok(if *requester == Address::from(1) {
+				attributes(&[("department", "finance"), ("role", "auditor")])
+			} else {
+				attributes(&[("department", "sales"), ("role", "manager")])
+			})
+		}
+
+		fn document_attributes(&self, _document: &DocumentAddress) -> Result<Attributes, Error> {
+			self.resolutions.fetch_add(1, Ordering::SeqCst);
+			Ok(attributes(&[("owner_department", "finance")]))
+		}
+	}
+
+	fn check(expression: &str, requester: u64) -> Result<bool, Error> {
+		let acl_storage = AttributeAclStorage::new(Arc::new(DummyAttributeResolver::default()), expression)?;
+		acl_storage.check(&Requester::Address(Address::from(requester)), &DocumentAddress::from(1))
+	}
+
+	#[test]
+	fn policies_are_evaluated() {
+		let same_department = "requester.department == document.owner_department";
+		assert_eq!(check(same_department, 1), Ok(true));
+		assert_eq!(check(same_department, 2), Ok(false));
+
+		let auditor_or_manager = r#"requester.role == "auditor" || (requester.role == "manager" && requester.department != "sales")"#;
+		assert_eq!(check(auditor_or_manager, 1), Ok(true));
+		assert_eq!(check(auditor_or_manager, 2), Ok(false));
+
+		assert_eq!(check("!(requester.department == document.owner_department) && true", 2), Ok(true));
+		assert_eq!(check("false || !false", 2), Ok(true));
+
+		// missing attribute fails the check
+		assert!(check("requester.clearance == \"top\"", 1).is_err());
+	}
+
+	#[test]
+	fn scoped_checks_are_not_supported() {
+		let acl_storage = AttributeAclStorage::new(Arc::new(DummyAttributeResolver::default()), "true").unwrap();
+		let requester = Requester::Address(Address::from(1));
+		assert_eq!(acl_storage.check_scoped(&requester, &DocumentAddress::from(1), ""), Ok(true));
+		assert!(acl_storage.check_scoped(&requester, &DocumentAddress::from(1), "decrypt").is_err());
+		assert!(acl_storage.check_scoped_dry_run(&requester, &DocumentAddress::from(1), "decrypt").is_err());
+	}
+
+	#[test]
+	fn malformed_expressions_are_rejected() {
+		for expression in &["", "requester.", "requester.role ==", "requester.role = \"a\"", "(true", "true)", "user.role == \"a\"",
+			"\"unterminated == requester.role", "true && && false", "requester.role == \"a\" true", "requester.role < \"a\""] {
+			assert!(expression.parse::<AttributeExpression>().is_err(), "{} must be rejected", expression);
+		}
+	}
+
+	#[test]
+	fn oversized_expressions_are_rejected() {
+		let nested = |depth: usize| format!("{}true{}", "(".repeat(depth), ")".repeat(depth));
+		assert!(nested(super::MAX_EXPRESSION_DEPTH).parse::<AttributeExpression>().is_ok());
+		assert!(nested(super::MAX_EXPRESSION_DEPTH + 1).parse::<AttributeExpression>().is_err());
+		assert!("!".repeat(100000).parse::<AttributeExpression>().is_err());
+
+		let conjunction = |terms: usize| vec!["true"; terms].join(" && ");
+		assert!(conjunction(512).parse::<AttributeExpression>().is_ok());
+		assert!(conjunction(100000).parse::<AttributeExpression>().is_err());
+	}
+
+	#[test]
+	fn resolved_attributes_are_reused() {
+		let resolver = Arc::new(DummyAttributeResolver::default());
+		let clock = Arc::new(MockClock::default());
+		let acl_storage = AttributeAclStorage::with_clock(resolver.clone(), "requester.department == document.owner_department",
+			AttributeAclStorageConfig {
+				attributes_ttl: Duration::from_secs(10),
+				..Default::default()
+			}, clock.clone()).unwrap();
+		let requester = Requester::Address(Address::from(1));
+
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+		assert_eq!(resolver.resolutions.load(Ordering::SeqCst), 2);
+
+		clock.advance(Duration::from_secs(10));
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+		assert_eq!(resolver.resolutions.load(Ordering::SeqCst), 4);
+	}
+
+	#[test]
+	fn slow_resolution_fails_the_check() {
+		let resolver = Arc::new(DummyAttributeResolver {
+			delay: Some(Duration::from_millis(500)),
+			..Default::default()
+		});
+		let acl_storage = AttributeAclStorage::with_config(resolver, "true", AttributeAclStorageConfig {
+			resolution_timeout: Duration::from_millis(10),
+			..Default::default()
+		}).unwrap();
+
+		assert!(acl_storage.check(&Requester::Address(Address::from(1)), &DocumentAddress::from(1)).is_err());
+	}
+
+	#[test]
+	fn concurrent_checks_share_single_resolution() {
+		let resolver = Arc::new(DummyAttributeResolver {
+			delay: Some(Duration::from_millis(100)),
+			..Default::default()
+		});
+		let acl_storage = Arc::new(AttributeAclStorage::new(resolver.clone(), "requester.department == document.owner_department").unwrap());
+
+		let handles: Vec<_> = (0..4).map(|_| {
+			let acl_storage = acl_storage.clone();
+			thread::spawn(move || acl_storage.check(&Requester::Address(Address::from(1)), &DocumentAddress::from(1)))
+		}).collect();
+		for handle in handles {
+			assert_eq!(handle.join().unwrap(), Ok(true));
+		}
+
+		// requester and document attributes are resolved once
+		assert_eq!(resolver.resolutions.load(Ordering::SeqCst), 2);
+	}
+
+	#[test]
+	fn timed_out_resolutions_are_limited() {
+		let resolver = Arc::new(DummyAttributeResolver {
+			delay: Some(Duration::from_millis(500)),
+			..Default::default()
+		});
+		let acl_storage = AttributeAclStorage::with_config(resolver.clone(), "true", AttributeAclStorageConfig {
+			resolution_timeout: Duration::from_millis(10),
+			max_pending_resolutions: 2,
+			..Default::default()
+		}).unwrap();
+
+		assert!(acl_storage.check(&Requester::Address(Address::from(1)), &DocumentAddress::from(1)).is_err());
+		assert!(acl_storage.check(&Requester::Address(Address::from(2)), &DocumentAddress::from(1)).is_err());
+		assert_eq!(acl_storage.resolutions.in_flight(), 2);
+
+		// no more resolutions are started while timed out resolutions are running
+		assert_eq!(acl_storage.check(&Requester::Address(Address::from(3)), &DocumentAddress::from(1)),
+			Err(Error::Acl(AclError::Overloaded)));
+		assert_eq!(resolver.resolutions.load(Ordering::SeqCst), 2);
+	}
+
+	#[test]
+	fn attributes_cache_is_bounded() {
+		let resolver = Arc::new(DummyAttributeResolver::default());
+		let clock = Arc::new(MockClock::default());
+		let acl_storage = AttributeAclStorage::with_clock(resolver.clone(), "true", AttributeAclStorageConfig {
+			attributes_ttl: Duration::from_secs(10),
+			max_cached_attributes: 4,
+			..Default::default()
+		}, clock.clone()).unwrap();
+
+		for requester in 0..10u64 {
+			clock.advance(Duration::from_secs(1));
+			assert_eq!(acl_storage.check(&Requester::Address(Address::from(requester)), &DocumentAddress::from(1)), Ok(true));
+		}
+		assert_eq!(acl_storage.requesters.len(), 4);
+		assert_eq!(acl_storage.documents.len(), 1);
+
+		// the most recently resolved attributes are kept
+		let resolutions = resolver.resolutions.load(Ordering::SeqCst);
+		assert_eq!(acl_storage.check(&Requester::Address(Address::from(9)), &DocumentAddress::from(1)), Ok(true));
+		assert_eq!(resolver.resolutions.load(Ordering::SeqCst), resolutions);
+		assert_eq!(acl_storage.check(&Requester::Address(Address::from(0)), &DocumentAddress::from(1)), Ok(true));
+		assert_eq!(resolver.resolutions.load(Ordering::SeqCst), resolutions + 1);
+	}
+}
@@ -0,0 +1,518 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::{Arc, Weak};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::{HashMap, VecDeque};
+use parking_lot::{Mutex, RwLock, Condvar};
+use types::all::{Error, DocumentAddress, Address, Public, Requester};
+use super::{AclStorage, AclContext, AclReport, DecisionBlock, CacheableDecision, EffectivePolicy, MerkleAuditLog, Clock, SystemClock,
+	AclEvent, AclEventStream};
+
+/// Default number of events, buffered for single subscriber.
+pub const DEFAULT_DECISIONS_BUFFER_SIZE: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+/// ACL check decision, reported to audit subscribers.
+pub struct AclAuditEvent {
+	/// Requester, as it was passed to the check.
+	pub requester: Requester,
+	/// Checked document.
+	pub document: DocumentAddress,
+	/// Scope of the check. Empty for plain checks.
+	pub scope: String,
+	/// Block, at which the decision has been made. None if the underlying storage has not reported it.
+	pub block: Option<DecisionBlock>,
+	/// Unix time (in seconds) of the decision. None if time has not been known.
+	pub timestamp: Option<u64>,
+	/// Result of the check.
+	pub result: Result<bool, Error>,
+}
+
+/// ACL storage, which reports every decision of the underlying storage to subscribers.
+/// Decisions could also be appended to the tamper-evident audit log and emitted to the ACL events stream.
+pub struct AuditedAclStorage {
+	/// Underlying ACL storage.
+	inner: Arc<AclStorage>,
+	/// Audit log, every decision is appended to.
+	audit_log: Option<Arc<MerkleAuditLog>>,
+	/// Time source of decisions timestamps.
+	clock: Arc<Clock>,
+	/// Decisions subscribers.
+	subscribers: Arc<Subscribers>,
+	/// ACL events stream, every decision is emitted to.
+	events: Option<Arc<AclEventStream>>,
+}
+
+/// Receiving end of the decisions subscription.
+/// Subscription is cancelled when receiver is dropped.
+pub struct DecisionReceiver {
+	/// Subscription id.
+	id: usize,
+	/// Events queue of this subscription.
+	queue: Arc<DecisionsQueue>,
+	/// All subscribers of the storage.
+	subscribers: Weak<Subscribers>,
+}
+
+/// All active subscriptions.
+struct Subscribers {
+	/// Max number of events, buffered for single subscriber.
+	buffer_size: usize,
+	/// Number of active subscriptions. Read on every check, so that nothing is done when there are no subscribers.
+	count: AtomicUsize,
+	/// Id of the next subscription.
+	next_id: AtomicUsize,
+	/// Subscriptions queues.
+	queues: RwLock<HashMap<usize, Arc<DecisionsQueue>>>,
+}
+
+/// Bounded queue of single subscriber. Oldest events are dropped when the queue is full.
+struct DecisionsQueue {
+	/// Queue data.
+	data: Mutex<DecisionsQueueData>,
+	/// Signalled when new event is pushed or queue is closed.
+	available: Condvar,
+	/// Number of events, dropped because subscriber was too slow.
+	dropped: AtomicUsize,
+}
+
+/// Mutable data of subscriber queue.
+struct DecisionsQueueData {
+	/// Buffered events.
+	events: VecDeque<AclAuditEvent>,
+	/// True when storage is dropped and no more events are expected.
+	is_closed: bool,
+}
+
+impl AuditedAclStorage {
+	/// Create new audited ACL storage on top of `inner`.
+	pub fn new(inner: Arc<AclStorage>) -> Self {
+		AuditedAclStorage::with_buffer_size(inner, DEFAULT_DECISIONS_BUFFER_SIZE)
+	}
+
+	/// Create new audited ACL storage, which buffers up to `buffer_size` events for every subscriber.
+	pub fn with_buffer_size(inner: Arc<AclStorage>, buffer_size: usize) -> Self {
+		AuditedAclStorage::with_audit_log(inner, buffer_size, None)
+	}
+
+	/// Create new audited ACL storage, which also appends every decision to the `audit_log`.
+	/// When decision could not be appended, the check fails, so that there are no unaudited decisions.
+	pub fn with_audit_log(inner: Arc<AclStorage>, buffer_size: usize, audit_log: Option<Arc<MerkleAuditLog>>) -> Self {
+		AuditedAclStorage::with_clock(inner, buffer_size, audit_log, Arc::new(SystemClock))
+	}
+
+	/// Create new audited ACL storage, which uses given clock to timestamp decisions.
+	pub fn with_clock(inner: Arc<AclStorage>, buffer_size: usize, audit_log: Option<Arc<MerkleAuditLog>>, clock: Arc<Clock>) -> Self {
+		AuditedAclStorage::with_events(inner, buffer_size, audit_log, clock, None)
+	}
+
+	/// Create new audited ACL storage, which also emits every decision to the `events` stream.
+	/// The storage must not be wrapped by `EventStreamAclStorage` of the same stream, or decisions are emitted twice.
+	pub fn with_events(inner: Arc<AclStorage>, buffer_size: usize, audit_log: Option<Arc<MerkleAuditLog>>, clock: Arc<Clock>, events: Option<Arc<AclEventStream>>) -> Self {
+		assert!(buffer_size != 0, "decisions buffer must be able to hold at least one event");
+
+		AuditedAclStorage {
+			inner: inner,
+			audit_log: audit_log,
+			clock: clock,
+			subscribers: Arc::new(Subscribers {
+				buffer_size: buffer_size,
+				count: AtomicUsize::new(0),
+				next_id: AtomicUsize::new(0),
+				queues: RwLock::new(HashMap::new()),
+			}),
+			events: events,
+		}
+	}
+
+	/// Subscribe to the stream of ACL decisions.
+	/// Slow subscribers never block checks: when subscriber buffer is full, the oldest event is dropped.
+	pub fn subscribe_decisions(&self) -> DecisionReceiver {
+		let id = self.subscribers.next_id.fetch_add(1, Ordering::SeqCst);
+		let queue = Arc::new(DecisionsQueue {
+			data: Mutex::new(DecisionsQueueData {
+				events: VecDeque::new(),
+				is_closed: false,
+			}),
+			available: Condvar::new(),
+			dropped: AtomicUsize::new(0),
+		});
+
+		self.subscribers.queues.write().insert(id, queue.clone());
+		self.subscribers.count.fetch_add(1, Ordering::SeqCst);
+
+		DecisionReceiver {
+			id: id,
+			queue: queue,
+			subscribers: Arc::downgrade(&self.subscribers),
+		}
+	}
+
+	/// Get number of active subscriptions.
+	pub fn subscribers_count(&self) -> usize {
+		self.subscribers.count.load(Ordering::SeqCst)
+	}
+
+	/// Get audit log, decisions are appended to.
+	pub fn audit_log(&self) -> Option<&Arc<MerkleAuditLog>> {
+		self.audit_log.as_ref()
+	}
+
+	/// Report check result to the audit log, events stream and subscribers.
+	/// Fails the check if result can't be appended to the audit log.
+	fn audit(&self, requester: &Requester, document: &DocumentAddress, scope: &str, block: Option<DecisionBlock>, result: Result<bool, Error>) -> Result<bool, Error> {
+		if self.audit_log.is_none() && self.events.is_none() && self.subscribers.count.load(Ordering::Relaxed) == 0 {
+			return result;
+		}
+
+		let event = AclAuditEvent {
+			requester: requester.clone(),
+			document: document.clone(),
+			scope: scope.to_owned(),
+			block: block,
+			timestamp: self.clock.unix_time().ok(),
+			result: result.clone(),
+		};
+		if let Some(ref audit_log) = self.audit_log {
+			if let Err(error) = audit_log.append(&event) {
+				warn!(target: "secretstore", "Failed to append ACL decision to the audit log: {}", error);
+				return Err(error);
+			}
+		}
+		if let Some(ref events) = self.events {
+			events.emit(AclEvent::from(event.clone()));
+		}
+		if self.subscribers.count.load(Ordering::Relaxed) != 0 {
+			self.subscribers.publish(event);
+		}
+		result
+	}
+}
+
+impl AclStorage for AuditedAclStorage {
+	/// Plain checks are made with block, so that decision could be replayed at this block later.
+	fn check(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		self.check_with_block(requester, document).map(|(granted, _)| granted)
+	}
+
+	fn check_with_context(&self, requester: &Requester, document: &DocumentAddress, context: &AclContext) -> Result<bool, Error> {
+		let result = self.inner.check_with_context(requester, document, context);
+		self.audit(requester, document, "", None, result)
+	}
+
+	fn check_with_block(&self, requester: &Requester, document: &DocumentAddress) -> Result<(bool, Option<DecisionBlock>), Error> {
+		let result = self.inner.check_with_block(requester, document);
+		let block = result.as_ref().ok().and_then(|&(_, ref block)| block.clone());
+		self.audit(requester, document, "", block.clone(), result.map(|(granted, _)| granted)).map(|granted| (granted, block))
+	}
+
+	fn check_with_expiry(&self, requester: &Requester, document: &DocumentAddress) -> Result<CacheableDecision, Error> {
+		let result = self.inner.check_with_expiry(requester, document);
+		let block = result.as_ref().ok().and_then(|decision| decision.block.clone());
+		self.audit(requester, document, "", block, result.as_ref().map(|decision| decision.granted).map_err(Clone::clone))?;
+		result
+	}
+
+	fn check_scoped_with_expiry(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<CacheableDecision, Error> {
+		let result = self.inner.check_scoped_with_expiry(requester, document, scope);
+		let block = result.as_ref().ok().and_then(|decision| decision.block.clone());
+		self.audit(requester, document, scope, block, result.as_ref().map(|decision| decision.granted).map_err(Clone::clone))?;
+		result
+	}
+
+	fn check_scoped(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<bool, Error> {
+		let result = self.inner.check_scoped(requester, document, scope);
+		self.audit(requester, document, scope, None, result)
+	}
+
+	fn check_dry_run(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		self.inner.check_dry_run(requester, document)
+	}
+
+	fn check_scoped_dry_run(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<bool, Error> {
+		self.inner.check_scoped_dry_run(requester, document, scope)
+	}
+
+	fn check_and_consume(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		let result = self.inner.check_and_consume(requester, document);
+		self.audit(requester, document, "", None, result)
+	}
+
+	fn list_requestors(&self, document: &DocumentAddress) -> Result<Vec<Address>, Error> {
+		self.inner.list_requestors(document)
+	}
+
+	fn effective_policy(&self, public: &Public) -> Result<EffectivePolicy, Error> {
+		self.inner.effective_policy(public)
+	}
+
+	fn fill_report(&self, report: &mut AclReport) {
+		report.audit_subscribers += self.subscribers_count();
+		self.inner.fill_report(report)
+	}
+}
+
+impl DecisionReceiver {
+	/// Wait for the next decision.
+	/// Returns None when the storage is dropped and all buffered events are received.
+	pub fn recv(&self) -> Option<AclAuditEvent> {
+		let mut data = self.queue.data.lock();
+		loop {
+			if let Some(event) = data.events.pop_front() {
+				return Some(event);
+			}
+			if data.is_closed {
+				return None;
+			}
+			self.queue.available.wait(&mut data);
+		}
+	}
+
+	/// Receive the next decision if it is already available.
+	pub fn try_recv(&self) -> Option<AclAuditEvent> {
+		self.queue.data.lock().events.pop_front()
+	}
+
+	/// Get number of events, dropped because this subscriber was too slow.
+	pub fn dropped_count(&self) -> usize {
+		self.queue.dropped.load(Ordering::SeqCst)
+	}
+}
+
+impl Drop for DecisionReceiver {
+	fn drop(&mut self) {
+		if let Some(subscribers) = self.subscribers.upgrade() {
+			if subscribers.queues.write().remove(&self.id).is_some() {
+				subscribers.count.fetch_sub(1, Ordering::SeqCst);
+			}
+		}
+	}
+}
+
+impl Subscribers {
+	/// Push event to all subscribers queues.
+	fn publish(&self, event: AclAuditEvent) {
+		for queue in self.queues.read().values() {
+			let mut data = queue.data.lock();
+			if data.events.len() == self.buffer_size {
+				data.events.pop_front();
+				queue.dropped.fetch_add(1, Ordering::SeqCst);
+			}
+			data.events.push_back(event.clone());
+			queue.available.notify_one();
+		}
+	}
+}
+
+impl Drop for Subscribers {
+	fn drop(&mut self) {
+		for queue in self.queues.read().values() {
+			queue.data.lock().is_closed = true;
+			queue.available.notify_all();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::thread;
+	use std::time::Duration;
+	use ethkey::{Random, Generator};
+	use util::{Address, H256};
+	use types::all::{Error, DocumentAddress, Requester};
+	use acl_storage::{AclStorage, MerkleAuditLog, OnChainAclStorage, SingleUseAclStorage, DecisionBlock, audit_leaf};
+	use acl_storage::tests::{DummyAclStorage, DummyAclClient, ScopedAclStorage};
+	use acl_storage::clock::tests::{MockClock, MOCK_UNIX_START};
+	use super::{AuditedAclStorage, AclAuditEvent, DEFAULT_DECISIONS_BUFFER_SIZE};
+
+	fn event(requester: &Requester, document: u64, scope: &str, result: Result<bool, Error>) -> AclAuditEvent {
+		AclAuditEvent {
+			requester: requester.clone(),
+			document: DocumentAddress::from(document),
+			scope: scope.to_owned(),
+			block: None,
+			timestamp: Some(MOCK_UNIX_START),
+			result: result,
+		}
+	}
+
+	#[test]
+	fn decisions_are_delivered_in_order() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let acl_storage = AuditedAclStorage::with_clock(inner.clone(), DEFAULT_DECISIONS_BUFFER_SIZE, None, Arc::new(MockClock::default()));
+		let receiver = acl_storage.subscribe_decisions();
+		let public = Random.generate().unwrap().public().clone();
+		let requester = Requester::Public(public.clone());
+		inner.prohibit(public.clone(), DocumentAddress::from(2));
+
+		for i in 1..4 {
+			acl_storage.check(&requester, &DocumentAddress::from(i)).unwrap();
+		}
+
+		assert_eq!(receiver.try_recv(), Some(event(&requester, 1, "", Ok(true))));
+		assert_eq!(receiver.try_recv(), Some(event(&requester, 2, "", Ok(false))));
+		assert_eq!(receiver.try_recv(), Some(event(&requester, 3, "", Ok(true))));
+		assert_eq!(receiver.try_recv(), None);
+	}
+
+	#[test]
+	fn scope_is_reported() {
+		let inner = Arc::new(ScopedAclStorage::default());
+		let acl_storage = AuditedAclStorage::with_clock(inner.clone(), DEFAULT_DECISIONS_BUFFER_SIZE, None, Arc::new(MockClock::default()));
+		let receiver = acl_storage.subscribe_decisions();
+		let requester = Requester::Address(Address::from(1));
+		inner.allow(Address::from(1), DocumentAddress::from(1), "decrypt");
+
+		assert_eq!(acl_storage.check_scoped(&requester, &DocumentAddress::from(1), "decrypt"), Ok(true));
+		assert_eq!(receiver.try_recv(), Some(event(&requester, 1, "decrypt", Ok(true))));
+	}
+
+	#[test]
+	fn dry_run_check_is_not_audited() {
+		let acl_storage = AuditedAclStorage::new(Arc::new(DummyAclStorage::default()));
+		let receiver = acl_storage.subscribe_decisions();
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+
+		assert_eq!(acl_storage.check_dry_run(&requester, &DocumentAddress::from(1)), Ok(true));
+		assert_eq!(receiver.try_recv(), None);
+	}
+
+	#[test]
+	fn consumption_is_audited() {
+		let inner = Arc::new(SingleUseAclStorage::new(Arc::new(DummyAclStorage::default())));
+		let acl_storage = AuditedAclStorage::with_clock(inner, DEFAULT_DECISIONS_BUFFER_SIZE, None, Arc::new(MockClock::default()));
+		let receiver = acl_storage.subscribe_decisions();
+		let requester = Requester::Address(Address::from(1));
+
+		assert_eq!(acl_storage.check_and_consume(&requester, &DocumentAddress::from(1)), Ok(true));
+		assert_eq!(acl_storage.check_and_consume(&requester, &DocumentAddress::from(1)), Ok(false));
+		assert_eq!(receiver.try_recv(), Some(event(&requester, 1, "", Ok(true))));
+		assert_eq!(receiver.try_recv(), Some(event(&requester, 1, "", Ok(false))));
+	}
+
+	#[test]
+	fn every_subscriber_receives_all_decisions() {
+		let acl_storage = AuditedAclStorage::new(Arc::new(DummyAclStorage::default()));
+		let receiver1 = acl_storage.subscribe_decisions();
+		let receiver2 = acl_storage.subscribe_decisions();
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+
+		acl_storage.check(&requester, &DocumentAddress::from(1)).unwrap();
+
+		assert_eq!(receiver1.try_recv().map(|e| e.document), Some(DocumentAddress::from(1)));
+		assert_eq!(receiver2.try_recv().map(|e| e.document), Some(DocumentAddress::from(1)));
+	}
+
+	#[test]
+	fn oldest_decisions_are_dropped_for_stalled_subscriber() {
+		let acl_storage = AuditedAclStorage::with_buffer_size(Arc::new(DummyAclStorage::default()), 2);
+		let receiver = acl_storage.subscribe_decisions();
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+
+		for i in 1..6 {
+			acl_storage.check(&requester, &DocumentAddress::from(i)).unwrap();
+		}
+
+		assert_eq!(receiver.dropped_count(), 3);
+		assert_eq!(receiver.try_recv().map(|e| e.document), Some(DocumentAddress::from(4)));
+		assert_eq!(receiver.try_recv().map(|e| e.document), Some(DocumentAddress::from(5)));
+		assert_eq!(receiver.try_recv(), None);
+	}
+
+	#[test]
+	fn dropped_receiver_is_unsubscribed() {
+		let acl_storage = AuditedAclStorage::new(Arc::new(DummyAclStorage::default()));
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+		assert_eq!(acl_storage.subscribers_count(), 0);
+
+		// nothing is buffered while there are no subscribers
+		acl_storage.check(&requester, &DocumentAddress::from(1)).unwrap();
+		let receiver = acl_storage.subscribe_decisions();
+		assert_eq!(acl_storage.subscribers_count(), 1);
+		assert_eq!(receiver.try_recv(), None);
+
+		drop(receiver);
+		assert_eq!(acl_storage.subscribers_count(), 0);
+		assert!(acl_storage.subscribers.queues.read().is_empty());
+	}
+
+	#[test]
+	fn blocking_receiver_is_released_when_storage_is_dropped() {
+		let acl_storage = AuditedAclStorage::new(Arc::new(DummyAclStorage::default()));
+		let receiver = acl_storage.subscribe_decisions();
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+		acl_storage.check(&requester, &DocumentAddress::from(1)).unwrap();
+
+		let handle = thread::spawn(move || {
+			let mut received = 0;
+			while let Some(_) = receiver.recv() {
+				received += 1;
+			}
+			received
+		});
+
+		drop(acl_storage);
+		assert_eq!(handle.join().unwrap(), 1);
+	}
+
+	#[test]
+	fn decisions_are_appended_to_audit_log() {
+		let audit_log = Arc::new(MerkleAuditLog::in_memory());
+		let acl_storage = AuditedAclStorage::with_clock(Arc::new(DummyAclStorage::default()),
+			DEFAULT_DECISIONS_BUFFER_SIZE, Some(audit_log.clone()), Arc::new(MockClock::default()));
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+
+		// decisions are logged even if there are no subscribers
+		for i in 1..4 {
+			acl_storage.check(&requester, &DocumentAddress::from(i)).unwrap();
+		}
+		assert_eq!(audit_log.len(), 3);
+
+		let proof = audit_log.audit_proof(1).unwrap();
+		assert!(proof.verify(&audit_leaf(&event(&requester, 2, "", Ok(true))), &audit_log.audit_root()));
+	}
+
+	#[test]
+	fn decision_block_and_time_are_recorded() {
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(1))));
+		let block = DecisionBlock { number: 5, hash: H256::from(5) };
+		*client.best_block.lock() = block.clone();
+		let clock = Arc::new(MockClock::default());
+		let audit_log = Arc::new(MerkleAuditLog::in_memory());
+		let acl_storage = AuditedAclStorage::with_clock(Arc::new(OnChainAclStorage::new(client)),
+			DEFAULT_DECISIONS_BUFFER_SIZE, Some(audit_log.clone()), clock.clone());
+		let receiver = acl_storage.subscribe_decisions();
+		let requester = Requester::Address(Address::from(2));
+
+		clock.advance(Duration::from_secs(10));
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+		let recorded = receiver.try_recv().unwrap();
+		assert_eq!(recorded.block, Some(block));
+		assert_eq!(recorded.timestamp, Some(MOCK_UNIX_START + 10));
+
+		// block and time are the part of the logged record
+		let root = audit_log.audit_root();
+		assert!(audit_log.audit_proof(0).unwrap().verify(&audit_leaf(&recorded), &root));
+		let mut altered = recorded.clone();
+		altered.block = None;
+		assert!(!audit_log.audit_proof(0).unwrap().verify(&audit_leaf(&altered), &root));
+		let mut altered = recorded.clone();
+		altered.timestamp = Some(MOCK_UNIX_START);
+		assert!(!audit_log.audit_proof(0).unwrap().verify(&audit_leaf(&altered), &root));
+	}
+}
@@ -0,0 +1,117 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+
+use std::sync::Arc;
+use std::sync::mpsc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+use types::all::{Error, AclError};
+
+/// Blocking calls, made on background threads with timeout. Calls, which have timed out, are still
+/// running until they complete, so number of outstanding calls is limited: when the limit is reached,
+/// new calls are rejected with `AclError::Overloaded` instead of spawning more threads.
+pub struct BackgroundCalls {
+	/// Max number of outstanding calls.
+	max_in_flight: usize,
+	/// Number of outstanding calls, including calls, which have timed out.
+	in_flight: Arc<AtomicUsize>,
+}
+
+/// Releases slot of the outstanding call when the background thread completes.
+struct InFlightGuard {
+	/// Number of outstanding calls.
+	in_flight: Arc<AtomicUsize>,
+}
+
+impl BackgroundCalls {
+	/// Create new background calls limit.
+	pub fn new(max_in_flight: usize) -> Self {
+		assert!(max_in_flight != 0, "at least one background call must be allowed");
+
+		BackgroundCalls {
+			max_in_flight: max_in_flight,
+			in_flight: Arc::new(AtomicUsize::new(0)),
+		}
+	}
+
+	/// Get number of outstanding calls.
+	pub fn in_flight(&self) -> usize {
+		self.in_flight.load(Ordering::SeqCst)
+	}
+
+	/// Make the call on background thread and wait for its result for at most `timeout`.
+	/// Returns None if the call has timed out.
+	pub fn call<T, F>(&self, timeout: Duration, call: F) -> Result<Option<T>, Error>
+		where T: Send + 'static, F: FnOnce() -> T + Send + 'static {
+		if self.in_flight.fetch_add(1, Ordering::SeqCst) >= self.max_in_flight {
+			self.in_flight.fetch_sub(1, Ordering::SeqCst);
+			warn!(target: "secretstore", "Background ACL call is rejected: {} calls are outstanding", self.max_in_flight);
+			return Err(AclError::Overloaded.into());
+		}
+
+		let guard = InFlightGuard {
+			in_flight: self.in_flight.clone(),
+		};
+		let (sender, receiver) = mpsc::channel();
+		thread::spawn(move || {
+			let _guard = guard;
+			// receiver is dropped if call has timed out
+			let _ = sender.send(call());
+		});
+		Ok(receiver.recv_timeout(timeout).ok())
+	}
+}
+
+impl Drop for InFlightGuard {
+	fn drop(&mut self) {
+		self.in_flight.fetch_sub(1, Ordering::SeqCst);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::mpsc;
+	use std::thread;
+	use std::time::Duration;
+	use types::all::{Error, AclError};
+	use super::BackgroundCalls;
+
+	#[test]
+	fn timed_out_calls_are_limited() {
+		let calls = BackgroundCalls::new(2);
+		assert_eq!(calls.call(Duration::from_secs(5), || 42), Ok(Some(42)));
+
+		// calls are blocked until senders are dropped
+		let (sender1, receiver1) = mpsc::channel::<()>();
+		let (sender2, receiver2) = mpsc::channel::<()>();
+		assert_eq!(calls.call(Duration::from_millis(10), move || receiver1.recv().is_ok()), Ok(None));
+		assert_eq!(calls.call(Duration::from_millis(10), move || receiver2.recv().is_ok()), Ok(None));
+		assert_eq!(calls.in_flight(), 2);
+
+		// no more threads are spawned while timed out calls are running
+		assert_eq!(calls.call(Duration::from_secs(5), || 42), Err(Error::Acl(AclError::Overloaded)));
+
+		// slots are released when calls complete
+		drop(sender1);
+		drop(sender2);
+		while calls.in_flight() != 0 {
+			thread::sleep(Duration::from_millis(10));
+		}
+		assert_eq!(calls.call(Duration::from_secs(5), || 42), Ok(Some(42)));
+	}
+}
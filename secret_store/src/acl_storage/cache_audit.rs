@@ -0,0 +1,217 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+use types::all::{DocumentAddress, Address};
+use super::CachedAclStorage;
+
+/// Default interval between cache audits.
+const DEFAULT_AUDIT_INTERVAL_SECS: u64 = 60;
+/// Default number of cached decisions, checked by single audit.
+const DEFAULT_AUDIT_SAMPLE_SIZE: usize = 16;
+
+/// Handler of cached decisions, which differ from decisions of the underlying storage.
+pub type MismatchHandler = Arc<Fn(&CacheMismatch) + Send + Sync>;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Cached decision, which differs from the decision of the underlying storage.
+pub struct CacheMismatch {
+	/// Requester address.
+	pub requester: Address,
+	/// Checked document.
+	pub document: DocumentAddress,
+	/// Scope of the check.
+	pub scope: String,
+	/// Cached decision.
+	pub cached: bool,
+	/// Decision of the underlying storage.
+	pub actual: bool,
+}
+
+#[derive(Debug, Clone)]
+/// Cache auditor configuration.
+pub struct CacheAuditorConfig {
+	/// Interval between audits.
+	pub interval: Duration,
+	/// Number of cached decisions, checked by single audit.
+	pub sample_size: usize,
+}
+
+/// Background task, which periodically re-checks random cached decisions against the underlying
+/// storage and reports mismatches. Mismatches are only reported: served decisions are not changed,
+/// so that auditor could be used to detect invalidation bugs in production.
+/// Task is stopped when auditor is dropped.
+pub struct CacheAuditor {
+	/// Signals audit thread to stop when dropped.
+	stop: Option<mpsc::Sender<()>>,
+	/// Audit thread handle.
+	handle: Option<thread::JoinHandle<()>>,
+}
+
+impl Default for CacheAuditorConfig {
+	fn default() -> Self {
+		CacheAuditorConfig {
+			interval: Duration::from_secs(DEFAULT_AUDIT_INTERVAL_SECS),
+			sample_size: DEFAULT_AUDIT_SAMPLE_SIZE,
+		}
+	}
+}
+
+impl CacheAuditor {
+	/// Start auditing given cache.
+	pub fn start(cache: Arc<CachedAclStorage>, config: CacheAuditorConfig, on_mismatch: MismatchHandler) -> Self {
+		let (stop, stopped) = mpsc::channel();
+		let handle = thread::spawn(move || loop {
+			match stopped.recv_timeout(config.interval) {
+				Err(RecvTimeoutError::Timeout) => (),
+				_ => break,
+			}
+
+			for mismatch in cache.audit_sample(config.sample_size) {
+				warn!(target: "secretstore", "Cached ACL decision of {:?} for document {:?} (scope {:?}) is {}, while it is {} in ACL storage",
+					mismatch.requester, mismatch.document, mismatch.scope, mismatch.cached, mismatch.actual);
+				on_mismatch(&mismatch);
+			}
+		});
+
+		CacheAuditor {
+			stop: Some(stop),
+			handle: Some(handle),
+		}
+	}
+}
+
+impl Drop for CacheAuditor {
+	fn drop(&mut self) {
+		self.stop.take();
+		if let Some(handle) = self.handle.take() {
+			let _ = handle.join();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::sync::mpsc;
+	use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+	use std::time::Duration;
+	use parking_lot::Mutex;
+	use ethkey::{Random, Generator, public_to_address};
+	use types::all::{Error, DocumentAddress, Requester};
+	use acl_storage::{AclStorage, CachedAclStorage};
+	use acl_storage::tests::DummyAclStorage;
+	use super::{CacheAuditor, CacheAuditorConfig, CacheMismatch};
+
+	#[derive(Default)]
+	/// ACL storage, which only checks requesters with known public key and counts checks with side effects.
+	struct PublicOnlyAclStorage {
+		revoked: AtomicBool,
+		checks: AtomicUsize,
+		dry_runs: AtomicUsize,
+	}
+
+	impl PublicOnlyAclStorage {
+		fn decide(&self, requester: &Requester) -> Result<bool, Error> {
+			match *requester {
+				Requester::Public(_) => Ok(!self.revoked.load(Ordering::SeqCst)),
+				_ => Err(Error::Internal("public key is required".into())),
+			}
+		}
+	}
+
+	impl AclStorage for PublicOnlyAclStorage {
+		fn check(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+			self.check_scoped(requester, document, "")
+		}
+
+		fn check_scoped(&self, requester: &Requester, _document: &DocumentAddress, _scope: &str) -> Result<bool, Error> {
+			self.checks.fetch_add(1, Ordering::SeqCst);
+			self.decide(requester)
+		}
+
+		fn check_scoped_dry_run(&self, requester: &Requester, _document: &DocumentAddress, _scope: &str) -> Result<bool, Error> {
+			self.dry_runs.fetch_add(1, Ordering::SeqCst);
+			self.decide(requester)
+		}
+	}
+
+	#[test]
+	fn stale_entry_is_reported() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let cache = Arc::new(CachedAclStorage::new(inner.clone(), Duration::from_secs(3600)));
+		let public = Random.generate().unwrap().public().clone();
+		let requester = Requester::Public(public.clone());
+		cache.check(&requester, &DocumentAddress::from(1)).unwrap();
+		assert!(cache.audit_sample(16).is_empty());
+
+		// access is revoked, but cache is not invalidated
+		inner.prohibit(public.clone(), DocumentAddress::from(1));
+		assert_eq!(cache.audit_sample(16), vec![CacheMismatch {
+			requester: public_to_address(&public),
+			document: DocumentAddress::from(1),
+			scope: String::new(),
+			cached: true,
+			actual: false,
+		}]);
+		// served decision is not changed
+		assert_eq!(cache.check(&requester, &DocumentAddress::from(1)), Ok(true));
+	}
+
+	#[test]
+	fn entry_is_rechecked_with_original_requester_without_side_effects() {
+		let inner = Arc::new(PublicOnlyAclStorage::default());
+		let cache = Arc::new(CachedAclStorage::new(inner.clone(), Duration::from_secs(3600)));
+		let public = Random.generate().unwrap().public().clone();
+		assert_eq!(cache.check_scoped(&Requester::Public(public.clone()), &DocumentAddress::from(1), "decrypt"), Ok(true));
+		assert!(cache.audit_sample(16).is_empty());
+
+		inner.revoked.store(true, Ordering::SeqCst);
+		assert_eq!(cache.audit_sample(16), vec![CacheMismatch {
+			requester: public_to_address(&public),
+			document: DocumentAddress::from(1),
+			scope: "decrypt".into(),
+			cached: true,
+			actual: false,
+		}]);
+		assert_eq!(inner.checks.load(Ordering::SeqCst), 1);
+		assert_eq!(inner.dry_runs.load(Ordering::SeqCst), 2);
+	}
+
+	#[test]
+	fn auditor_reports_mismatches_in_background() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let cache = Arc::new(CachedAclStorage::new(inner.clone(), Duration::from_secs(3600)));
+		let public = Random.generate().unwrap().public().clone();
+		cache.check(&Requester::Public(public.clone()), &DocumentAddress::from(1)).unwrap();
+		inner.prohibit(public.clone(), DocumentAddress::from(1));
+
+		let (sender, receiver) = mpsc::channel();
+		let sender = Mutex::new(sender);
+		let auditor = CacheAuditor::start(cache, CacheAuditorConfig {
+			interval: Duration::from_millis(10),
+			sample_size: 1,
+		}, Arc::new(move |mismatch: &CacheMismatch| { let _ = sender.lock().send(mismatch.clone()); }));
+
+		let mismatch = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+		assert_eq!(mismatch.document, DocumentAddress::from(1));
+		assert!(mismatch.cached && !mismatch.actual);
+		drop(auditor);
+	}
+}
@@ -0,0 +1,1418 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::hash_map::{DefaultHasher, RandomState};
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::time::{Duration, Instant};
+use parking_lot::{Mutex, RwLock};
+use ethcore::header::BlockNumber;
+use types::all::{Error, DocumentAddress, Address, Public, Requester, ResolvedRequester};
+use super::{AclStorage, AclContext, AclReport, CacheReport, CacheMismatch, DecisionBlock, CacheableDecision, EffectivePolicy, Clock, SystemClock,
+	AclEventStream};
+
+/// Default time-to-live of cached decisions.
+const DEFAULT_CACHE_TTL_SECS: u64 = 60;
+/// Default number of cache shards.
+const DEFAULT_CACHE_SHARDS: usize = 16;
+/// Number of document invalidation generation counters. Documents share counters by hash of the address.
+const DOCUMENT_GENERATIONS: usize = 256;
+
+#[derive(Debug, Clone)]
+/// Cached ACL storage configuration.
+pub struct CachedAclStorageConfig {
+	/// Time-to-live of cached decisions.
+	pub ttl: Duration,
+	/// Kind of cached decisions.
+	pub mode: CacheMode,
+	/// Number of independently locked cache segments. Decisions are spread over shards by requester and document,
+	/// so more shards means less lock contention under high check rates.
+	pub shards: usize,
+	/// Max age of cached decision. Older decisions are re-checked even if their TTL has not passed yet.
+	pub max_staleness: Option<Duration>,
+	/// Max number of cached decisions of single requester. When requester reaches the limit, its least
+	/// recently used decision is evicted, so that one noisy requester can't evict decisions of other requesters.
+	pub max_entries_per_requester: Option<usize>,
+	/// Max number of cached decisions. When the limit is reached, least recently used decision of the shard, which
+	/// the new decision belongs to, is evicted. Decisions of other shards are only evicted, if this shard is empty.
+	pub max_entries: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+/// Cache prewarming statistics.
+pub struct PrewarmStats {
+	/// Number of entries, checked by the underlying storage and cached.
+	pub warmed: usize,
+	/// Number of entries, which already had fresh cached decision.
+	pub skipped: usize,
+	/// Number of entries, for which check has failed.
+	pub failed: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Kind of decisions, which are cached.
+pub enum CacheMode {
+	/// Both grants and denials are cached. Any change of ACL takes effect within cache TTL.
+	All,
+	/// Only grants are cached, denials are always re-checked by the underlying storage.
+	/// Newly granted access takes effect immediately, revoked access takes effect within cache TTL.
+	GrantsOnly,
+	/// Only denials are cached, grants are always re-checked by the underlying storage.
+	/// Revoked access takes effect immediately, newly granted access takes effect within cache TTL.
+	DenialsOnly,
+}
+
+/// Key of the cached decision: resolved requester, checked document, scope of the check and is public key of the
+/// requester known. Access could be granted to candidate addresses of the known public key, so decisions, made for
+/// the public key, are never reused for the requester, known by address only, and vice versa.
+type DecisionKey = (Address, DocumentAddress, String, bool);
+
+/// Keys of cached decisions of every requester.
+type RequesterDecisions = Mutex<HashMap<Address, HashSet<DecisionKey>>>;
+
+#[derive(Default)]
+/// Single cache shard.
+struct CacheShard {
+	/// Cached decisions.
+	decisions: HashMap<DecisionKey, CachedDecision>,
+	/// Keys of cached decisions by value of the use counter, at which they have been queued for eviction.
+	/// Queue is only updated on eviction, so that decisions are served from the cache under the read lock.
+	recently_used: BTreeMap<usize, DecisionKey>,
+}
+
+/// Cached ACL check result.
+struct CachedDecision {
+	/// Is access granted.
+	granted: bool,
+	/// Time when this decision has been cached.
+	cached_at: Instant,
+	/// Time when this decision must be re-checked.
+	expires: Instant,
+	/// Block, at which the decision has been made by the underlying storage.
+	block: Option<DecisionBlock>,
+	/// Use counter value, when this decision has been cached or served last time.
+	last_used: AtomicUsize,
+	/// Use counter value, at which this decision is queued for eviction.
+	queued: usize,
+	/// Requester, as it has been passed to the underlying storage.
+	requester: Requester,
+}
+
+/// ACL storage, which caches results of the underlying storage checks.
+/// Only successful checks are cached, errors are always reported by the underlying storage.
+pub struct CachedAclStorage {
+	/// Underlying (authoritative) ACL storage.
+	inner: Arc<AclStorage>,
+	/// Time-to-live of cached decisions.
+	ttl: Duration,
+	/// Kind of cached decisions.
+	mode: CacheMode,
+	/// Max age of cached decision.
+	max_staleness: RwLock<Option<Duration>>,
+	/// Time source.
+	clock: Arc<Clock>,
+	/// Max number of cached decisions of single requester.
+	max_entries_per_requester: Option<usize>,
+	/// Max number of cached decisions.
+	max_entries: Option<usize>,
+	/// Use counter, incremented every time decision is cached or served from the cache.
+	uses: AtomicUsize,
+	/// Number of cached decisions.
+	entries: AtomicUsize,
+	/// Is caching currently enabled.
+	caching_enabled: AtomicBool,
+	/// Cached decisions, sharded by hash of requester and document.
+	cache: Vec<RwLock<CacheShard>>,
+	/// Keys of cached decisions of every requester, sharded by hash of requester. Only maintained
+	/// when number of cached decisions of single requester is limited.
+	requester_decisions: Vec<RequesterDecisions>,
+	/// Generation of the whole cache, incremented when decisions of many documents are invalidated.
+	generation: AtomicUsize,
+	/// Generations of documents, incremented when decisions for the document are invalidated.
+	/// Decision is not cached if generation has changed while the underlying storage was asked,
+	/// so that in-flight check can't re-insert decision, made before invalidation.
+	document_generations: Vec<AtomicUsize>,
+	/// ACL events stream, runtime configuration changes are emitted to.
+	events: Option<Arc<AclEventStream>>,
+}
+
+impl Default for CachedAclStorageConfig {
+	fn default() -> Self {
+		CachedAclStorageConfig {
+			ttl: Duration::from_secs(DEFAULT_CACHE_TTL_SECS),
+			mode: CacheMode::All,
+			shards: DEFAULT_CACHE_SHARDS,
+			max_staleness: None,
+			max_entries_per_requester: None,
+			max_entries: None,
+		}
+	}
+}
+
+impl CachedAclStorage {
+	/// Create new cached ACL storage on top of `inner`.
+	pub fn new(inner: Arc<AclStorage>, ttl: Duration) -> Self {
+		CachedAclStorage::with_clock(inner, ttl, Arc::new(SystemClock))
+	}
+
+	/// Create new cached ACL storage, which uses given clock to expire decisions.
+	pub fn with_clock(inner: Arc<AclStorage>, ttl: Duration, clock: Arc<Clock>) -> Self {
+		CachedAclStorage::with_mode(inner, ttl, CacheMode::All, clock)
+	}
+
+	/// Create new cached ACL storage, which only caches decisions of given kind.
+	pub fn with_mode(inner: Arc<AclStorage>, ttl: Duration, mode: CacheMode, clock: Arc<Clock>) -> Self {
+		CachedAclStorage::with_config(inner, CachedAclStorageConfig {
+			ttl: ttl,
+			mode: mode,
+			..Default::default()
+		}, clock)
+	}
+
+	/// Create new cached ACL storage with given configuration.
+	pub fn with_config(inner: Arc<AclStorage>, config: CachedAclStorageConfig, clock: Arc<Clock>) -> Self {
+		CachedAclStorage::with_events(inner, config, clock, None)
+	}
+
+	/// Create new cached ACL storage, which emits runtime configuration changes to the `events` stream.
+	pub fn with_events(inner: Arc<AclStorage>, config: CachedAclStorageConfig, clock: Arc<Clock>, events: Option<Arc<AclEventStream>>) -> Self {
+		assert!(config.shards != 0, "cache must have at least one shard");
+		assert!(config.max_entries_per_requester != Some(0), "requester must be able to cache at least one decision");
+		assert!(config.max_entries != Some(0), "cache must be able to hold at least one decision");
+
+		CachedAclStorage {
+			inner: inner,
+			ttl: config.ttl,
+			mode: config.mode,
+			max_staleness: RwLock::new(config.max_staleness),
+			max_entries_per_requester: config.max_entries_per_requester,
+			max_entries: config.max_entries,
+			uses: AtomicUsize::new(0),
+			entries: AtomicUsize::new(0),
+			clock: clock,
+			caching_enabled: AtomicBool::new(true),
+			cache: (0..config.shards).map(|_| RwLock::new(CacheShard::default())).collect(),
+			requester_decisions: (0..config.shards).map(|_| Mutex::new(HashMap::new())).collect(),
+			generation: AtomicUsize::new(0),
+			document_generations: (0..DOCUMENT_GENERATIONS).map(|_| AtomicUsize::new(0)).collect(),
+			events: events,
+		}
+	}
+
+	/// Enable or disable caching at runtime.
+	/// While disabled, every check goes to the underlying storage and nothing is cached.
+	/// Disabling also drops all previously cached decisions, so that no stale decision
+	/// is served once caching is enabled again.
+	pub fn set_caching_enabled(&self, enabled: bool) {
+		let was_enabled = self.caching_enabled.swap(enabled, Ordering::SeqCst);
+		if was_enabled == enabled {
+			return;
+		}
+
+		if enabled {
+			info!(target: "secretstore", "ACL checks caching is enabled");
+		} else {
+			self.clear();
+			warn!(target: "secretstore", "ACL checks caching is disabled");
+		}
+		if let Some(ref events) = self.events {
+			events.config_changed("caching_enabled", if enabled { "true" } else { "false" });
+		}
+	}
+
+	/// Is caching currently enabled.
+	pub fn is_caching_enabled(&self) -> bool {
+		self.caching_enabled.load(Ordering::SeqCst)
+	}
+
+	/// Set max age of cached decisions, independent of TTL.
+	/// Decisions, which are older, are treated as missing.
+	pub fn set_max_staleness(&self, max_staleness: Option<Duration>) {
+		*self.max_staleness.write() = max_staleness;
+		if let Some(ref events) = self.events {
+			let value = match max_staleness {
+				Some(max_staleness) => format!("{}s", max_staleness.as_secs()),
+				None => "none".into(),
+			};
+			events.config_changed("max_staleness", &value);
+		}
+	}
+
+	/// Drop all cached decisions.
+	pub fn clear(&self) {
+		self.generation.fetch_add(1, Ordering::SeqCst);
+		for shard in &self.cache {
+			let removed = shard.write().remove_matching(|_, _| true);
+			self.forget(removed);
+		}
+	}
+
+	/// Drop all cached decisions of given requester.
+	pub fn invalidate_requester(&self, requester: &Address) {
+		self.generation.fetch_add(1, Ordering::SeqCst);
+		for shard in &self.cache {
+			let removed = shard.write().remove_matching(|key, _| key.0 == *requester);
+			self.forget(removed);
+		}
+	}
+
+	/// Drop all cached decisions for given document, made for any scope.
+	pub fn invalidate_document(&self, document: &DocumentAddress) {
+		self.document_generation(document).fetch_add(1, Ordering::SeqCst);
+		for shard in &self.cache {
+			let removed = shard.write().remove_matching(|key, _| key.1 == *document);
+			self.forget(removed);
+		}
+	}
+
+	/// Get number of cached decisions.
+	pub fn len(&self) -> usize {
+		self.entries.load(Ordering::SeqCst)
+	}
+
+	/// Drop all cached decisions, made before block with given number.
+	/// Decisions of storages, which are not block-based, are kept.
+	pub fn invalidate_older_than(&self, block_number: BlockNumber) {
+		self.generation.fetch_add(1, Ordering::SeqCst);
+		for shard in &self.cache {
+			let removed = shard.write().remove_matching(|_, decision| decision.block.as_ref().map(|block| block.number < block_number).unwrap_or(false));
+			self.forget(removed);
+		}
+	}
+
+	/// Get block, at which cached (unscoped) decision for given requester and document has been made.
+	/// Decisions, made both with and without known public key of the requester, are looked for.
+	pub fn cached_block(&self, requester: &Address, document: &DocumentAddress) -> Option<DecisionBlock> {
+		let shard = self.shard(requester, document).read();
+		let block = [false, true].iter()
+			.filter_map(|is_public_known| shard.decisions.get(&(requester.clone(), document.clone(), String::new(), *is_public_known)))
+			.filter_map(|decision| decision.block.clone())
+			.next();
+		block
+	}
+
+	/// Get number of the oldest block, at which cached decision has been made.
+	pub fn oldest_block(&self) -> Option<BlockNumber> {
+		self.cache.iter()
+			.filter_map(|shard| shard.read().decisions.values()
+				.filter_map(|decision| decision.block.as_ref().map(|block| block.number))
+				.min())
+			.min()
+	}
+
+	/// Get number of cached decisions of given requester.
+	pub fn requester_len(&self, requester: &Address) -> usize {
+		self.cache.iter().map(|shard| shard.read().decisions.keys().filter(|key| key.0 == *requester).count()).sum()
+	}
+
+	/// Warm cache with decisions for given entries, skipping entries with fresh cached decision.
+	/// Nothing is checked while caching is disabled.
+	pub fn prewarm_missing(&self, entries: &[(Requester, DocumentAddress)]) -> PrewarmStats {
+		let mut stats = PrewarmStats::default();
+		if !self.is_caching_enabled() {
+			stats.skipped = entries.len();
+			return stats;
+		}
+
+		for &(ref requester, ref document) in entries {
+			let requester = match requester.resolve(document) {
+				Ok(requester) => requester,
+				Err(_) => {
+					stats.failed += 1;
+					continue;
+				},
+			};
+
+			if self.cached_decision(&requester, document, "").is_some() {
+				stats.skipped += 1;
+				continue;
+			}
+
+			match self.check_and_cache(requester, document, "") {
+				Ok(_) => stats.warmed += 1,
+				Err(err) => {
+					trace!(target: "secretstore", "Failed to prewarm ACL decision for document {:?}: {}", document, err);
+					stats.failed += 1;
+				},
+			}
+		}
+
+		stats
+	}
+
+	/// Re-check up to `sample_size` randomly chosen fresh cached decisions against the underlying storage
+	/// and return decisions, which differ. Cached decisions are not changed. Decisions are re-checked with
+	/// the same requester, as the one originally passed to the underlying storage, and without side effects.
+	pub fn audit_sample(&self, sample_size: usize) -> Vec<CacheMismatch> {
+		let random_state = RandomState::new();
+		let random = |seed: usize| {
+			let mut hasher = random_state.build_hasher();
+			seed.hash(&mut hasher);
+			hasher.finish() as usize
+		};
+
+		let now = self.clock.now();
+		let mut sample: Vec<(DecisionKey, Requester, bool)> = Vec::with_capacity(sample_size);
+		for index in 0..sample_size {
+			// lock is not held while the underlying storage is asked
+			let shard = self.cache[random(index) % self.cache.len()].read();
+			if shard.decisions.is_empty() {
+				continue;
+			}
+			let (key, decision) = shard.decisions.iter().nth(random(index + sample_size) % shard.decisions.len())
+				.expect("index is less than number of entries; qed");
+			let is_sampled = sample.iter().any(|&(ref k, _, _)| k == key);
+			if decision.expires > now && !is_sampled {
+				sample.push((key.clone(), decision.requester.clone(), decision.granted));
+			}
+		}
+
+		let mut mismatches = Vec::new();
+		for ((requester, document, scope, _), checked_requester, cached) in sample {
+			match self.inner.check_scoped_dry_run(&checked_requester, &document, &scope) {
+				Ok(actual) if actual != cached => mismatches.push(CacheMismatch {
+					requester: requester,
+					document: document,
+					scope: scope,
+					cached: cached,
+					actual: actual,
+				}),
+				Ok(_) => (),
+				Err(err) => trace!(target: "secretstore", "Failed to audit cached ACL decision for document {:?}: {}", document, err),
+			}
+		}
+		mismatches
+	}
+
+	/// Get index of the shard, holding decisions of given requester for given document.
+	fn shard_index(&self, requester: &Address, document: &DocumentAddress) -> usize {
+		let mut hasher = DefaultHasher::new();
+		requester.hash(&mut hasher);
+		document.hash(&mut hasher);
+		(hasher.finish() % self.cache.len() as u64) as usize
+	}
+
+	/// Get shard, holding decisions of given requester for given document.
+	fn shard(&self, requester: &Address, document: &DocumentAddress) -> &RwLock<CacheShard> {
+		&self.cache[self.shard_index(requester, document)]
+	}
+
+	/// Get keys of cached decisions of given requester.
+	fn requester_decisions(&self, requester: &Address) -> &RequesterDecisions {
+		let mut hasher = DefaultHasher::new();
+		requester.hash(&mut hasher);
+		&self.requester_decisions[(hasher.finish() % self.requester_decisions.len() as u64) as usize]
+	}
+
+	/// Get fresh cached decision and time, when it must be re-checked.
+	fn cached_decision(&self, requester: &ResolvedRequester, document: &DocumentAddress, scope: &str) -> Option<(bool, Option<DecisionBlock>, Instant)> {
+		let now = self.clock.now();
+		let max_staleness = *self.max_staleness.read();
+		let key = (requester.address.clone(), document.clone(), scope.to_owned(), requester.public.is_some());
+		self.shard(&requester.address, document).read().decisions.get(&key)
+			.and_then(|decision| {
+				let not_after = self.not_after(decision.cached_at, decision.expires, max_staleness);
+				if not_after <= now {
+					return None;
+				}
+
+				decision.last_used.store(self.next_use(), Ordering::Relaxed);
+				Some((decision.granted, decision.block.clone(), not_after))
+			})
+	}
+
+	/// Get generation counter of given document.
+	fn document_generation(&self, document: &DocumentAddress) -> &AtomicUsize {
+		let mut hasher = DefaultHasher::new();
+		document.hash(&mut hasher);
+		&self.document_generations[(hasher.finish() % self.document_generations.len() as u64) as usize]
+	}
+
+	/// Get current generations of the whole cache and of given document.
+	fn generations(&self, document: &DocumentAddress) -> (usize, usize) {
+		(self.generation.load(Ordering::SeqCst), self.document_generation(document).load(Ordering::SeqCst))
+	}
+
+	/// Get next value of the use counter.
+	fn next_use(&self) -> usize {
+		self.uses.fetch_add(1, Ordering::Relaxed)
+	}
+
+	/// Reserve place for the new decision of given shard. When the cache is full, least recently used decision
+	/// of this shard is evicted. Decisions of other shards are only evicted if this shard is empty.
+	/// Returns keys of evicted decisions.
+	fn reserve_entry(&self, shard_index: usize) -> Vec<DecisionKey> {
+		let mut evicted = Vec::new();
+		let max_entries = match self.max_entries {
+			Some(max_entries) => max_entries,
+			None => {
+				self.entries.fetch_add(1, Ordering::SeqCst);
+				return evicted;
+			},
+		};
+
+		loop {
+			let entries = self.entries.load(Ordering::SeqCst);
+			if entries < max_entries {
+				if self.entries.compare_and_swap(entries, entries + 1, Ordering::SeqCst) == entries {
+					return evicted;
+				}
+				continue;
+			}
+
+			let least_recently_used = (0..self.cache.len())
+				.filter_map(|offset| self.cache[(shard_index + offset) % self.cache.len()].write().remove_least_recently_used())
+				.next();
+			match least_recently_used {
+				Some(key) => {
+					self.entries.fetch_sub(1, Ordering::SeqCst);
+					evicted.push(key);
+				},
+				// all counted decisions are still being inserted by concurrent checks
+				None => {
+					self.entries.fetch_add(1, Ordering::SeqCst);
+					return evicted;
+				},
+			}
+		}
+	}
+
+	/// Evict least recently used decision of the requester, if it has reached the limit of cached decisions.
+	/// Must be called with the requester keys lock held.
+	fn evict_requester_least_recently_used(&self, keys: &mut HashSet<DecisionKey>, max_entries: usize) {
+		if keys.len() < max_entries {
+			return;
+		}
+
+		let least_recently_used = keys.iter()
+			.filter_map(|key| self.shard(&key.0, &key.1).read().decisions.get(key)
+				.map(|decision| (decision.last_used.load(Ordering::Relaxed), key.clone())))
+			.min_by_key(|&(last_used, _)| last_used)
+			.map(|(_, key)| key);
+		if let Some(least_recently_used) = least_recently_used {
+			if self.shard(&least_recently_used.0, &least_recently_used.1).write().remove(&least_recently_used).is_some() {
+				self.entries.fetch_sub(1, Ordering::SeqCst);
+			}
+			keys.remove(&least_recently_used);
+		}
+	}
+
+	/// Account decisions, removed from the cache.
+	fn forget(&self, removed: Vec<DecisionKey>) {
+		self.entries.fetch_sub(removed.len(), Ordering::SeqCst);
+		self.forget_requester_decisions(removed);
+	}
+
+	/// Remove keys of decisions, removed from the cache, from keys of their requesters.
+	/// Must be called without any cache lock held.
+	fn forget_requester_decisions(&self, removed: Vec<DecisionKey>) {
+		if self.max_entries_per_requester.is_none() {
+			return;
+		}
+
+		for key in removed {
+			let mut requester_decisions = self.requester_decisions(&key.0).lock();
+			// decision could be cached again after it has been removed
+			if self.shard(&key.0, &key.1).read().decisions.contains_key(&key) {
+				continue;
+			}
+
+			let is_emptied = match requester_decisions.get_mut(&key.0) {
+				Some(keys) => {
+					keys.remove(&key);
+					keys.is_empty()
+				},
+				None => false,
+			};
+			if is_emptied {
+				requester_decisions.remove(&key.0);
+			}
+		}
+	}
+
+	/// Get time, when decision, cached at `cached_at`, must be re-checked.
+	fn not_after(&self, cached_at: Instant, expires: Instant, max_staleness: Option<Duration>) -> Instant {
+		match max_staleness {
+			Some(max_staleness) if cached_at + max_staleness < expires => cached_at + max_staleness,
+			_ => expires,
+		}
+	}
+
+	/// Ask the underlying storage and cache its decision.
+	/// Returns the decision and time, when it must be re-checked. Decisions, which are not cached, must be re-checked immediately.
+	fn check_and_cache(&self, requester: ResolvedRequester, document: &DocumentAddress, scope: &str) -> Result<(bool, Option<DecisionBlock>, Instant), Error> {
+		let address = requester.address.clone();
+		let is_public_known = requester.public.is_some();
+		let requester: Requester = requester.into();
+		let generations = self.generations(document);
+		let decision = self.inner.check_scoped_with_expiry(&requester, document, scope)?;
+		let (granted, block) = (decision.granted, decision.block);
+		let now = self.clock.now();
+		// decision is never cached for longer, than the underlying storage allows
+		let expires = match decision.not_after {
+			Some(not_after) if not_after < now + self.ttl => not_after,
+			_ => now + self.ttl,
+		};
+		// caching could be disabled while we were waiting for the underlying storage
+		if !self.mode.is_cached(granted) || !self.is_caching_enabled() || expires <= now {
+			return Ok((granted, block, now));
+		}
+
+		let key = (address.clone(), document.clone(), scope.to_owned(), is_public_known);
+		let shard_index = self.shard_index(&address, document);
+		let mut evicted = Vec::new();
+		let is_inserted = {
+			// requester keys are locked before shards, so that the requester can't exceed its limit
+			let mut requester_decisions = self.max_entries_per_requester.map(|_| self.requester_decisions(&address).lock());
+			let is_cached = self.cache[shard_index].read().decisions.contains_key(&key);
+			if !is_cached {
+				if let (Some(max_entries), Some(requester_decisions)) = (self.max_entries_per_requester, requester_decisions.as_mut()) {
+					let keys = requester_decisions.entry(address.clone()).or_insert_with(HashSet::new);
+					self.evict_requester_least_recently_used(keys, max_entries);
+				}
+				evicted = self.reserve_entry(shard_index);
+			}
+
+			let mut shard = self.cache[shard_index].write();
+			// decisions could be invalidated while we were waiting for the underlying storage
+			if self.generations(document) != generations {
+				if !is_cached {
+					self.entries.fetch_sub(1, Ordering::SeqCst);
+				}
+				false
+			} else {
+				let last_used = self.next_use();
+				let replaced = shard.insert(key.clone(), CachedDecision {
+					granted: granted,
+					cached_at: now,
+					expires: expires,
+					block: block.clone(),
+					last_used: AtomicUsize::new(last_used),
+					queued: last_used,
+					requester: requester,
+				});
+				// decision could be cached or removed by concurrent check, since we have looked for it
+				match (replaced.is_some(), is_cached) {
+					(true, false) => { self.entries.fetch_sub(1, Ordering::SeqCst); },
+					(false, true) => { self.entries.fetch_add(1, Ordering::SeqCst); },
+					_ => (),
+				}
+				if let Some(ref mut requester_decisions) = requester_decisions {
+					requester_decisions.entry(address).or_insert_with(HashSet::new).insert(key);
+				}
+				true
+			}
+		};
+		self.forget_requester_decisions(evicted);
+
+		if !is_inserted {
+			trace!(target: "secretstore", "ACL decision for document {:?} is not cached: cache has been invalidated during the check", document);
+			return Ok((granted, block, now));
+		}
+
+		let max_staleness = *self.max_staleness.read();
+		Ok((granted, block, self.not_after(now, expires, max_staleness)))
+	}
+}
+
+impl CacheShard {
+	/// Insert decision and queue it for eviction. Returns replaced decision.
+	fn insert(&mut self, key: DecisionKey, decision: CachedDecision) -> Option<CachedDecision> {
+		self.recently_used.insert(decision.queued, key.clone());
+		let replaced = self.decisions.insert(key, decision);
+		if let Some(ref replaced) = replaced {
+			self.recently_used.remove(&replaced.queued);
+		}
+		replaced
+	}
+
+	/// Remove decision.
+	fn remove(&mut self, key: &DecisionKey) -> Option<CachedDecision> {
+		let removed = self.decisions.remove(key);
+		if let Some(ref removed) = removed {
+			self.recently_used.remove(&removed.queued);
+		}
+		removed
+	}
+
+	/// Remove all decisions, matching given predicate. Returns keys of removed decisions.
+	fn remove_matching<F>(&mut self, predicate: F) -> Vec<DecisionKey> where F: Fn(&DecisionKey, &CachedDecision) -> bool {
+		let removed: Vec<_> = self.decisions.iter()
+			.filter(|&(key, decision)| predicate(key, decision))
+			.map(|(key, _)| key.clone())
+			.collect();
+		for key in &removed {
+			self.remove(key);
+		}
+		removed
+	}
+
+	/// Remove least recently used decision. Returns key of removed decision or None if shard is empty.
+	fn remove_least_recently_used(&mut self) -> Option<DecisionKey> {
+		loop {
+			let (queued, key) = match self.recently_used.iter().next() {
+				Some((queued, key)) => (*queued, key.clone()),
+				None => return None,
+			};
+			self.recently_used.remove(&queued);
+
+			let last_used = match self.decisions.get_mut(&key) {
+				Some(decision) => {
+					let last_used = decision.last_used.load(Ordering::Relaxed);
+					decision.queued = last_used;
+					last_used
+				},
+				None => continue,
+			};
+			// decisions are queued at or before their last use, so the first decision, which has not been used
+			// since it has been queued, is the least recently used one
+			if last_used == queued {
+				self.decisions.remove(&key);
+				return Some(key);
+			}
+
+			self.recently_used.insert(last_used, key);
+		}
+	}
+}
+
+impl CacheMode {
+	/// Is decision of given kind cached in this mode.
+	fn is_cached(&self, granted: bool) -> bool {
+		match *self {
+			CacheMode::All => true,
+			CacheMode::GrantsOnly => granted,
+			CacheMode::DenialsOnly => !granted,
+		}
+	}
+}
+
+impl AclStorage for CachedAclStorage {
+	fn check(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		self.check_with_block(requester, document).map(|(granted, _)| granted)
+	}
+
+	/// Cached decisions are not bound to the check context, so checks with a non-empty context
+	/// always go to the underlying storage and are not cached.
+	fn check_with_context(&self, requester: &Requester, document: &DocumentAddress, context: &AclContext) -> Result<bool, Error> {
+		if *context == AclContext::default() {
+			return self.check(requester, document);
+		}
+
+		self.inner.check_with_context(requester, document, context)
+	}
+
+	fn check_with_block(&self, requester: &Requester, document: &DocumentAddress) -> Result<(bool, Option<DecisionBlock>), Error> {
+		if !self.is_caching_enabled() {
+			return self.inner.check_with_block(requester, document);
+		}
+
+		let requester = requester.resolve(document)?;
+		if let Some((granted, block, _)) = self.cached_decision(&requester, document, "") {
+			return Ok((granted, block));
+		}
+
+		self.check_and_cache(requester, document, "").map(|(granted, block, _)| (granted, block))
+	}
+
+	fn check_scoped(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<bool, Error> {
+		if !self.is_caching_enabled() {
+			return self.inner.check_scoped(requester, document, scope);
+		}
+
+		let requester = requester.resolve(document)?;
+		if let Some((granted, _, _)) = self.cached_decision(&requester, document, scope) {
+			return Ok(granted);
+		}
+
+		self.check_and_cache(requester, document, scope).map(|(granted, _, _)| granted)
+	}
+
+	fn check_with_expiry(&self, requester: &Requester, document: &DocumentAddress) -> Result<CacheableDecision, Error> {
+		self.check_scoped_with_expiry(requester, document, "")
+	}
+
+	fn check_scoped_with_expiry(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<CacheableDecision, Error> {
+		if !self.is_caching_enabled() {
+			return self.inner.check_scoped_with_expiry(requester, document, scope);
+		}
+
+		let requester = requester.resolve(document)?;
+		let (granted, block, not_after) = match self.cached_decision(&requester, document, scope) {
+			Some(decision) => decision,
+			None => self.check_and_cache(requester, document, scope)?,
+		};
+		Ok(CacheableDecision {
+			granted: granted,
+			not_after: Some(not_after),
+			block: block,
+		})
+	}
+
+	fn check_dry_run(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		self.inner.check_dry_run(requester, document)
+	}
+
+	fn check_scoped_dry_run(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<bool, Error> {
+		self.inner.check_scoped_dry_run(requester, document, scope)
+	}
+
+	/// Consumption is always made by the underlying storage. Once access is consumed, cached decisions for
+	/// the document are dropped, so that grants, made before the document has been consumed, are not served.
+	fn check_and_consume(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		let granted = self.inner.check_and_consume(requester, document)?;
+		if granted {
+			self.invalidate_document(document);
+		}
+		Ok(granted)
+	}
+
+	fn list_requestors(&self, document: &DocumentAddress) -> Result<Vec<Address>, Error> {
+		self.inner.list_requestors(document)
+	}
+
+	fn effective_policy(&self, public: &Public) -> Result<EffectivePolicy, Error> {
+		self.inner.effective_policy(public)
+	}
+
+	fn fill_report(&self, report: &mut AclReport) {
+		report.caches.push(CacheReport {
+			caching_enabled: self.is_caching_enabled(),
+			entries: self.len(),
+			shards: self.cache.len(),
+			oldest_block: self.oldest_block(),
+		});
+		self.inner.fill_report(report)
+	}
+}
+
+/// ACL storage, which caches grants of the underlying storage, but always re-checks denials.
+/// See `CacheMode::GrantsOnly` for freshness guarantees.
+pub struct GrantOnlyCacheAclStorage {
+	/// Grants cache.
+	cache: CachedAclStorage,
+}
+
+impl GrantOnlyCacheAclStorage {
+	/// Create new grants-caching ACL storage on top of `inner`.
+	pub fn new(inner: Arc<AclStorage>, ttl: Duration) -> Self {
+		GrantOnlyCacheAclStorage::with_clock(inner, ttl, Arc::new(SystemClock))
+	}
+
+	/// Create new grants-caching ACL storage, which uses given clock to expire grants.
+	pub fn with_clock(inner: Arc<AclStorage>, ttl: Duration, clock: Arc<Clock>) -> Self {
+		GrantOnlyCacheAclStorage {
+			cache: CachedAclStorage::with_mode(inner, ttl, CacheMode::GrantsOnly, clock),
+		}
+	}
+
+	/// Drop all cached grants.
+	pub fn clear(&self) {
+		self.cache.clear();
+	}
+}
+
+impl AclStorage for GrantOnlyCacheAclStorage {
+	fn check(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		self.cache.check(requester, document)
+	}
+
+	fn check_with_context(&self, requester: &Requester, document: &DocumentAddress, context: &AclContext) -> Result<bool, Error> {
+		self.cache.check_with_context(requester, document, context)
+	}
+
+	fn check_with_block(&self, requester: &Requester, document: &DocumentAddress) -> Result<(bool, Option<DecisionBlock>), Error> {
+		self.cache.check_with_block(requester, document)
+	}
+
+	fn check_scoped(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<bool, Error> {
+		self.cache.check_scoped(requester, document, scope)
+	}
+
+	fn check_with_expiry(&self, requester: &Requester, document: &DocumentAddress) -> Result<CacheableDecision, Error> {
+		self.cache.check_with_expiry(requester, document)
+	}
+
+	fn check_scoped_with_expiry(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<CacheableDecision, Error> {
+		self.cache.check_scoped_with_expiry(requester, document, scope)
+	}
+
+	fn check_dry_run(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		self.cache.check_dry_run(requester, document)
+	}
+
+	fn check_scoped_dry_run(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<bool, Error> {
+		self.cache.check_scoped_dry_run(requester, document, scope)
+	}
+
+	fn check_and_consume(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		self.cache.check_and_consume(requester, document)
+	}
+
+	fn list_requestors(&self, document: &DocumentAddress) -> Result<Vec<Address>, Error> {
+		self.cache.list_requestors(document)
+	}
+
+	fn effective_policy(&self, public: &Public) -> Result<EffectivePolicy, Error> {
+		self.cache.effective_policy(public)
+	}
+
+	fn fill_report(&self, report: &mut AclReport) {
+		self.cache.fill_report(report)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::{Arc, Weak};
+	use std::sync::atomic::{AtomicBool, Ordering};
+	use std::time::Duration;
+	use parking_lot::Mutex;
+	use ethkey::{Random, Generator, sign};
+	use ethcore::client::BlockId;
+	use util::{Address, H256};
+	use types::all::{DocumentAddress, Requester};
+	use acl_storage::{AclStorage, OnChainAclStorage, DecisionBlock, CacheableDecision, Clock, TemporaryGrantAclStorage,
+		TemporaryGrantAclStorageConfig};
+	use acl_storage::tests::{DummyAclStorage, DummyAclClient, ScopedAclStorage};
+	use acl_storage::clock::tests::MockClock;
+	use super::{CachedAclStorage, CachedAclStorageConfig, CacheMode, GrantOnlyCacheAclStorage, PrewarmStats};
+
+	#[test]
+	fn cached_decision_is_reused() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let acl_storage = CachedAclStorage::new(inner.clone(), Duration::from_secs(60));
+		let key_pair = Random.generate().unwrap();
+		let document = DocumentAddress::from(1);
+
+		// requester is resolved before caching, so the same decision is used for any requester form with known public key
+		let signature = sign(key_pair.secret(), &document).unwrap();
+		assert_eq!(acl_storage.check(&Requester::Public(key_pair.public().clone()), &document), Ok(true));
+		assert_eq!(acl_storage.check(&Requester::Signature(signature), &document), Ok(true));
+		assert_eq!(inner.checks_count(), 1);
+
+		// decision, made for the public key, is not reused for the address
+		assert_eq!(acl_storage.check(&Requester::Address(key_pair.address()), &document), Ok(true));
+		assert_eq!(acl_storage.check(&Requester::Address(key_pair.address()), &document), Ok(true));
+		assert_eq!(inner.checks_count(), 2);
+	}
+
+	#[test]
+	fn grant_to_candidate_address_is_not_reused_for_address() {
+		use types::all::Error;
+
+		// storage, which grants access by candidate addresses of the public key
+		struct CandidateAclStorage;
+
+		impl AclStorage for CandidateAclStorage {
+			fn check(&self, requester: &Requester, _document: &DocumentAddress) -> Result<bool, Error> {
+				Ok(match *requester {
+					Requester::Public(_) => true,
+					_ => false,
+				})
+			}
+		}
+
+		let acl_storage = CachedAclStorage::new(Arc::new(CandidateAclStorage), Duration::from_secs(60));
+		let key_pair = Random.generate().unwrap();
+		let document = DocumentAddress::from(1);
+
+		assert_eq!(acl_storage.check(&Requester::Public(key_pair.public().clone()), &document), Ok(true));
+		assert_eq!(acl_storage.check(&Requester::Address(key_pair.address()), &document), Ok(false));
+		assert_eq!(acl_storage.check(&Requester::Public(key_pair.public().clone()), &document), Ok(true));
+		assert_eq!(acl_storage.len(), 2);
+	}
+
+	#[test]
+	fn caching_can_be_toggled_at_runtime() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let acl_storage = CachedAclStorage::new(inner.clone(), Duration::from_secs(60));
+		let public = Random.generate().unwrap().public().clone();
+		let requester = Requester::Public(public.clone());
+		let document = DocumentAddress::from(1);
+
+		// decision is cached
+		assert_eq!(acl_storage.check(&requester, &document), Ok(true));
+		inner.prohibit(public.clone(), document.clone());
+		assert_eq!(acl_storage.check(&requester, &document), Ok(true));
+		assert_eq!(inner.checks_count(), 1);
+
+		// when caching is disabled, every check hits the underlying storage
+		acl_storage.set_caching_enabled(false);
+		assert!(!acl_storage.is_caching_enabled());
+		assert_eq!(acl_storage.check(&requester, &document), Ok(false));
+		assert_eq!(acl_storage.check(&requester, &document), Ok(false));
+		assert_eq!(inner.checks_count(), 3);
+
+		// nothing is cached while disabled, so the first check after enabling also hits the storage
+		acl_storage.set_caching_enabled(true);
+		assert_eq!(acl_storage.check(&requester, &document), Ok(false));
+		assert_eq!(acl_storage.check(&requester, &document), Ok(false));
+		assert_eq!(inner.checks_count(), 4);
+	}
+
+	#[test]
+	fn only_missing_entries_are_prewarmed() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let acl_storage = CachedAclStorage::new(inner.clone(), Duration::from_secs(60));
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+
+		acl_storage.check(&requester, &DocumentAddress::from(1)).unwrap();
+		acl_storage.check(&requester, &DocumentAddress::from(2)).unwrap();
+		assert_eq!(inner.checks_count(), 2);
+
+		let entries: Vec<_> = (1..6).map(|i| (requester.clone(), DocumentAddress::from(i))).collect();
+		assert_eq!(acl_storage.prewarm_missing(&entries), PrewarmStats { warmed: 3, skipped: 2, failed: 0 });
+		assert_eq!(inner.checks_count(), 5);
+
+		// everything is fresh now
+		assert_eq!(acl_storage.prewarm_missing(&entries), PrewarmStats { warmed: 0, skipped: 5, failed: 0 });
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(5)), Ok(true));
+		assert_eq!(inner.checks_count(), 5);
+	}
+
+	#[test]
+	fn expired_decision_is_rechecked() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let clock = Arc::new(MockClock::default());
+		let acl_storage = CachedAclStorage::with_clock(inner.clone(), Duration::from_secs(60), clock.clone());
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+		let document = DocumentAddress::from(1);
+
+		assert_eq!(acl_storage.check(&requester, &document), Ok(true));
+		clock.advance(Duration::from_secs(59));
+		assert_eq!(acl_storage.check(&requester, &document), Ok(true));
+		assert_eq!(inner.checks_count(), 1);
+
+		clock.advance(Duration::from_secs(1));
+		assert_eq!(acl_storage.check(&requester, &document), Ok(true));
+		assert_eq!(inner.checks_count(), 2);
+	}
+
+	#[test]
+	fn dry_run_check_is_not_cached() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let acl_storage = CachedAclStorage::new(inner.clone(), Duration::from_secs(60));
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+		let document = DocumentAddress::from(1);
+
+		assert_eq!(acl_storage.check_dry_run(&requester, &document), Ok(true));
+		assert_eq!(acl_storage.check(&requester, &document), Ok(true));
+		assert_eq!(inner.checks_count(), 2);
+
+		// cached decision is not used by dry-run check either
+		assert_eq!(acl_storage.check_dry_run(&requester, &document), Ok(true));
+		assert_eq!(inner.checks_count(), 3);
+	}
+
+	#[test]
+	fn grant_only_cache_always_rechecks_denials() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let acl_storage = GrantOnlyCacheAclStorage::new(inner.clone(), Duration::from_secs(60));
+		let public = Random.generate().unwrap().public().clone();
+		let requester = Requester::Public(public.clone());
+		inner.prohibit(public.clone(), DocumentAddress::from(2));
+
+		// grant is cached
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+		assert_eq!(inner.checks_count(), 1);
+
+		// denial is not
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(2)), Ok(false));
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(2)), Ok(false));
+		assert_eq!(inner.checks_count(), 3);
+	}
+
+	#[test]
+	fn denials_only_cache_always_rechecks_grants() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let clock = Arc::new(MockClock::default());
+		let acl_storage = CachedAclStorage::with_mode(inner.clone(), Duration::from_secs(60), CacheMode::DenialsOnly, clock);
+		let public = Random.generate().unwrap().public().clone();
+		let requester = Requester::Public(public.clone());
+		inner.prohibit(public.clone(), DocumentAddress::from(2));
+
+		// grant is not cached
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+		assert_eq!(inner.checks_count(), 2);
+
+		// denial is
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(2)), Ok(false));
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(2)), Ok(false));
+		assert_eq!(inner.checks_count(), 3);
+	}
+
+	#[test]
+	fn decisions_are_invalidated_across_shards() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let acl_storage = CachedAclStorage::with_config(inner.clone(), CachedAclStorageConfig {
+			shards: 4,
+			..Default::default()
+		}, Arc::new(MockClock::default()));
+
+		for requester in 0..8u64 {
+			for document in 0..4u64 {
+				acl_storage.check(&Requester::Address(Address::from(requester)), &DocumentAddress::from(document)).unwrap();
+			}
+		}
+		assert_eq!(acl_storage.len(), 32);
+		// decisions are spread over shards
+		assert!(acl_storage.cache.iter().filter(|shard| !shard.read().decisions.is_empty()).count() > 1);
+
+		acl_storage.invalidate_requester(&Address::from(1));
+		assert_eq!(acl_storage.len(), 28);
+		acl_storage.invalidate_document(&DocumentAddress::from(0));
+		assert_eq!(acl_storage.len(), 21);
+		assert_eq!(acl_storage.requester_len(&Address::from(2)), 3);
+		acl_storage.clear();
+		assert_eq!(acl_storage.len(), 0);
+	}
+
+	#[test]
+	fn decision_expires_at_max_staleness() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let clock = Arc::new(MockClock::default());
+		let acl_storage = CachedAclStorage::with_config(inner.clone(), CachedAclStorageConfig {
+			ttl: Duration::from_secs(3600),
+			max_staleness: Some(Duration::from_secs(60)),
+			..Default::default()
+		}, clock.clone());
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+		let document = DocumentAddress::from(1);
+
+		assert_eq!(acl_storage.check(&requester, &document), Ok(true));
+		clock.advance(Duration::from_secs(59));
+		assert_eq!(acl_storage.check(&requester, &document), Ok(true));
+		assert_eq!(inner.checks_count(), 1);
+		clock.advance(Duration::from_secs(1));
+		assert_eq!(acl_storage.check(&requester, &document), Ok(true));
+		assert_eq!(inner.checks_count(), 2);
+
+		// ceiling could be tightened at runtime
+		acl_storage.set_max_staleness(Some(Duration::from_secs(10)));
+		clock.advance(Duration::from_secs(10));
+		assert_eq!(acl_storage.check(&requester, &document), Ok(true));
+		assert_eq!(inner.checks_count(), 3);
+	}
+
+	#[test]
+	fn requester_flood_does_not_evict_other_requesters_decisions() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let clock = Arc::new(MockClock::default());
+		let acl_storage = CachedAclStorage::with_config(inner.clone(), CachedAclStorageConfig {
+			shards: 1,
+			max_entries_per_requester: Some(4),
+			..Default::default()
+		}, clock.clone());
+		let requester = Requester::Address(Address::from(1));
+		let noisy_requester = Requester::Address(Address::from(2));
+
+		acl_storage.check(&requester, &DocumentAddress::from(1)).unwrap();
+		for document in 0..100u64 {
+			clock.advance(Duration::from_millis(10));
+			acl_storage.check(&noisy_requester, &DocumentAddress::from(document)).unwrap();
+		}
+		assert_eq!(acl_storage.requester_len(&Address::from(2)), 4);
+		assert_eq!(acl_storage.len(), 5);
+
+		// decision of other requester is still cached
+		let checks_count = inner.checks_count();
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+		assert_eq!(inner.checks_count(), checks_count);
+
+		// oldest decisions of the noisy requester have been evicted
+		assert_eq!(acl_storage.check(&noisy_requester, &DocumentAddress::from(99)), Ok(true));
+		assert_eq!(inner.checks_count(), checks_count);
+		assert_eq!(acl_storage.check(&noisy_requester, &DocumentAddress::from(0)), Ok(true));
+		assert_eq!(inner.checks_count(), checks_count + 1);
+	}
+
+	#[test]
+	fn decision_block_is_recorded() {
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(1))));
+		let block5 = DecisionBlock { number: 5, hash: H256::from(5) };
+		*client.best_block.lock() = block5.clone();
+		let acl_storage = CachedAclStorage::new(Arc::new(OnChainAclStorage::new(client.clone())), Duration::from_secs(60));
+		let requester = Requester::Address(Address::from(2));
+
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+		assert_eq!(client.calls.lock()[0].0, BlockId::Hash(H256::from(5)));
+		assert_eq!(acl_storage.cached_block(&Address::from(2), &DocumentAddress::from(1)), Some(block5.clone()));
+		assert_eq!(acl_storage.check_with_block(&requester, &DocumentAddress::from(1)), Ok((true, Some(block5))));
+
+		*client.best_block.lock() = DecisionBlock { number: 7, hash: H256::from(7) };
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(2)), Ok(true));
+		assert_eq!(acl_storage.oldest_block(), Some(5));
+
+		acl_storage.invalidate_older_than(6);
+		assert_eq!(acl_storage.len(), 1);
+		assert_eq!(acl_storage.oldest_block(), Some(7));
+	}
+
+	#[test]
+	fn decision_expiry_is_governed_by_ttl_and_max_staleness() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let clock = Arc::new(MockClock::default());
+		let acl_storage = CachedAclStorage::with_clock(inner.clone(), Duration::from_secs(60), clock.clone());
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+		let cached_at = clock.now();
+
+		// fresh and cached decisions expire at the end of TTL
+		assert_eq!(acl_storage.check_with_expiry(&requester, &DocumentAddress::from(1)), Ok(CacheableDecision {
+			granted: true,
+			not_after: Some(cached_at + Duration::from_secs(60)),
+			block: None,
+		}));
+		clock.advance(Duration::from_secs(10));
+		assert_eq!(acl_storage.check_with_expiry(&requester, &DocumentAddress::from(1)).unwrap().not_after, Some(cached_at + Duration::from_secs(60)));
+		assert_eq!(inner.checks_count(), 1);
+
+		// max staleness is shorter than TTL
+		acl_storage.set_max_staleness(Some(Duration::from_secs(30)));
+		assert_eq!(acl_storage.check_with_expiry(&requester, &DocumentAddress::from(1)).unwrap().not_after, Some(cached_at + Duration::from_secs(30)));
+
+		// caching is disabled => underlying storage does not limit decision lifetime
+		acl_storage.set_caching_enabled(false);
+		assert_eq!(acl_storage.check_with_expiry(&requester, &DocumentAddress::from(1)).unwrap().not_after, None);
+	}
+
+	#[test]
+	fn decision_expires_with_underlying_storage_decision() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let clock = Arc::new(MockClock::default());
+		let temporary = Arc::new(TemporaryGrantAclStorage::with_config(inner.clone(), TemporaryGrantAclStorageConfig {
+			clock_skew_tolerance: Duration::from_secs(0),
+		}, clock.clone()));
+		let acl_storage = CachedAclStorage::with_clock(temporary.clone(), Duration::from_secs(3600), clock.clone());
+		let key_pair = Random.generate().unwrap();
+		let requester = Requester::Public(key_pair.public().clone());
+		inner.prohibit(key_pair.public().clone(), DocumentAddress::from(1));
+
+		temporary.grant(key_pair.address(), DocumentAddress::from(1), Duration::from_secs(10));
+		assert_eq!(acl_storage.check_with_expiry(&requester, &DocumentAddress::from(1)).unwrap().not_after, Some(clock.now() + Duration::from_secs(10)));
+		clock.advance(Duration::from_secs(9));
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+		assert_eq!(inner.checks_count(), 0);
+
+		// grant has expired => cached decision is not served, even though TTL has not elapsed
+		clock.advance(Duration::from_secs(1));
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(false));
+		assert_eq!(inner.checks_count(), 1);
+	}
+
+	#[test]
+	fn scoped_decision_expires_with_underlying_storage_decision() {
+		let inner = Arc::new(ScopedAclStorage::default());
+		let clock = Arc::new(MockClock::default());
+		let temporary = Arc::new(TemporaryGrantAclStorage::with_config(inner.clone(), TemporaryGrantAclStorageConfig {
+			clock_skew_tolerance: Duration::from_secs(0),
+		}, clock.clone()));
+		let acl_storage = CachedAclStorage::with_clock(temporary.clone(), Duration::from_secs(3600), clock.clone());
+		let requester = Requester::Address(Address::from(1));
+
+		// temporary grant applies to every scope
+		temporary.grant(Address::from(1), DocumentAddress::from(1), Duration::from_secs(10));
+		assert_eq!(acl_storage.check_scoped_with_expiry(&requester, &DocumentAddress::from(1), "decrypt").unwrap().not_after,
+			Some(clock.now() + Duration::from_secs(10)));
+		clock.advance(Duration::from_secs(9));
+		assert_eq!(acl_storage.check_scoped(&requester, &DocumentAddress::from(1), "decrypt"), Ok(true));
+		assert_eq!(inner.checks.load(Ordering::SeqCst), 0);
+
+		// grant has expired => cached scoped decision is not served
+		clock.advance(Duration::from_secs(1));
+		assert_eq!(acl_storage.check_scoped(&requester, &DocumentAddress::from(1), "decrypt"), Ok(false));
+		assert_eq!(inner.checks.load(Ordering::SeqCst), 1);
+	}
+
+	#[test]
+	fn not_cached_decision_expires_immediately() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let clock = Arc::new(MockClock::default());
+		let acl_storage = GrantOnlyCacheAclStorage::with_clock(inner.clone(), Duration::from_secs(60), clock.clone());
+		let key_pair = Random.generate().unwrap();
+		inner.prohibit(key_pair.public().clone(), DocumentAddress::from(1));
+
+		assert_eq!(acl_storage.check_with_expiry(&Requester::Public(key_pair.public().clone()), &DocumentAddress::from(1)), Ok(CacheableDecision {
+			granted: false,
+			not_after: Some(clock.now()),
+			block: None,
+		}));
+	}
+
+	#[test]
+	fn decisions_of_different_scopes_are_cached_independently() {
+		let inner = Arc::new(ScopedAclStorage::default());
+		let acl_storage = CachedAclStorage::new(inner.clone(), Duration::from_secs(60));
+		let requester = Requester::Address(Address::from(1));
+		let document = DocumentAddress::from(1);
+		inner.allow(Address::from(1), document.clone(), "decrypt");
+
+		assert_eq!(acl_storage.check_scoped(&requester, &document, "decrypt"), Ok(true));
+		assert_eq!(acl_storage.check_scoped(&requester, &document, "re-share"), Ok(false));
+		assert_eq!(acl_storage.check(&requester, &document), Ok(false));
+		assert_eq!(acl_storage.len(), 3);
+
+		// every scope is served from its own entry
+		assert_eq!(acl_storage.check_scoped(&requester, &document, "decrypt"), Ok(true));
+		assert_eq!(acl_storage.check_scoped(&requester, &document, "re-share"), Ok(false));
+		assert_eq!(inner.checks.load(Ordering::SeqCst), 3);
+
+		// invalidation of the document drops decisions of all scopes
+		acl_storage.invalidate_document(&document);
+		assert_eq!(acl_storage.len(), 0);
+	}
+
+	#[test]
+	fn least_recently_used_decision_is_evicted_when_cache_is_full() {
+		let inner = Arc::new(DummyAclStorage::default());
+		// least recently used decision is evicted from the shard of the new decision
+		let acl_storage = CachedAclStorage::with_config(inner.clone(), CachedAclStorageConfig {
+			shards: 1,
+			max_entries: Some(3),
+			..Default::default()
+		}, Arc::new(MockClock::default()));
+		let (requester1, requester2) = (Requester::Address(Address::from(1)), Requester::Address(Address::from(2)));
+
+		acl_storage.check(&requester1, &DocumentAddress::from(1)).unwrap();
+		acl_storage.check(&requester2, &DocumentAddress::from(2)).unwrap();
+		acl_storage.check(&requester1, &DocumentAddress::from(3)).unwrap();
+		// first decision is the oldest one, but it is used again
+		acl_storage.check(&requester1, &DocumentAddress::from(1)).unwrap();
+		assert_eq!(inner.checks_count(), 3);
+
+		acl_storage.check(&requester2, &DocumentAddress::from(4)).unwrap();
+		assert_eq!(acl_storage.len(), 3);
+		assert_eq!(inner.checks_count(), 4);
+
+		// least recently used decision has been evicted
+		acl_storage.check(&requester1, &DocumentAddress::from(1)).unwrap();
+		acl_storage.check(&requester1, &DocumentAddress::from(3)).unwrap();
+		assert_eq!(inner.checks_count(), 4);
+		acl_storage.check(&requester2, &DocumentAddress::from(2)).unwrap();
+		assert_eq!(inner.checks_count(), 5);
+		assert_eq!(acl_storage.len(), 3);
+	}
+
+	#[test]
+	fn recently_used_decision_of_requester_is_kept() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let acl_storage = CachedAclStorage::with_config(inner.clone(), CachedAclStorageConfig {
+			max_entries_per_requester: Some(2),
+			..Default::default()
+		}, Arc::new(MockClock::default()));
+		let requester = Requester::Address(Address::from(1));
+
+		acl_storage.check(&requester, &DocumentAddress::from(1)).unwrap();
+		acl_storage.check(&requester, &DocumentAddress::from(2)).unwrap();
+		acl_storage.check(&requester, &DocumentAddress::from(1)).unwrap();
+		acl_storage.check(&requester, &DocumentAddress::from(3)).unwrap();
+		assert_eq!(inner.checks_count(), 3);
+
+		// document 2 has been evicted, document 1 is still cached
+		acl_storage.check(&requester, &DocumentAddress::from(1)).unwrap();
+		assert_eq!(inner.checks_count(), 3);
+		acl_storage.check(&requester, &DocumentAddress::from(2)).unwrap();
+		assert_eq!(inner.checks_count(), 4);
+	}
+
+	#[test]
+	fn cache_limit_is_kept_across_shards() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let acl_storage = CachedAclStorage::with_config(inner.clone(), CachedAclStorageConfig {
+			shards: 4,
+			max_entries: Some(5),
+			..Default::default()
+		}, Arc::new(MockClock::default()));
+
+		for document in 0..32u64 {
+			acl_storage.check(&Requester::Address(Address::from(document % 3)), &DocumentAddress::from(document)).unwrap();
+		}
+		assert_eq!(acl_storage.len(), 5);
+		assert_eq!(acl_storage.cache.iter().map(|shard| shard.read().decisions.len()).sum::<usize>(), 5);
+
+		// the most recent decision is kept
+		let checks_count = inner.checks_count();
+		assert_eq!(acl_storage.check(&Requester::Address(Address::from(31 % 3)), &DocumentAddress::from(31)), Ok(true));
+		assert_eq!(inner.checks_count(), checks_count);
+	}
+
+	#[test]
+	fn requester_limit_is_kept_across_shards() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let acl_storage = CachedAclStorage::with_config(inner.clone(), CachedAclStorageConfig {
+			shards: 4,
+			max_entries_per_requester: Some(3),
+			..Default::default()
+		}, Arc::new(MockClock::default()));
+		let requester = Requester::Address(Address::from(1));
+
+		for document in 0..32u64 {
+			acl_storage.check(&requester, &DocumentAddress::from(document)).unwrap();
+		}
+		assert_eq!(acl_storage.requester_len(&Address::from(1)), 3);
+		assert_eq!(acl_storage.len(), 3);
+
+		// invalidated decisions are not counted against the limit
+		acl_storage.invalidate_requester(&Address::from(1));
+		assert_eq!(acl_storage.len(), 0);
+		for document in 0..3u64 {
+			acl_storage.check(&requester, &DocumentAddress::from(document)).unwrap();
+		}
+		assert_eq!(acl_storage.requester_len(&Address::from(1)), 3);
+	}
+
+	#[test]
+	fn decision_is_not_cached_if_invalidated_during_check() {
+		use types::all::Error;
+
+		// storage, which revokes access while the first check is in flight
+		#[derive(Default)]
+		struct RevokingAclStorage {
+			cache: Mutex<Weak<CachedAclStorage>>,
+			revoked: AtomicBool,
+		}
+
+		impl AclStorage for RevokingAclStorage {
+			fn check(&self, _requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+				let granted = !self.revoked.swap(true, Ordering::SeqCst);
+				if let Some(cache) = self.cache.lock().upgrade() {
+					cache.invalidate_document(document);
+				}
+				Ok(granted)
+			}
+		}
+
+		let inner = Arc::new(RevokingAclStorage::default());
+		let acl_storage = Arc::new(CachedAclStorage::new(inner.clone(), Duration::from_secs(60)));
+		*inner.cache.lock() = Arc::downgrade(&acl_storage);
+		let requester = Requester::Address(Address::from(1));
+		let document = DocumentAddress::from(1);
+
+		// stale grant is returned to the caller, but is not cached
+		assert_eq!(acl_storage.check(&requester, &document), Ok(true));
+		assert_eq!(acl_storage.len(), 0);
+		assert_eq!(acl_storage.check(&requester, &document), Ok(false));
+
+		// decisions of other documents are cached
+		*inner.cache.lock() = Weak::new();
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(2)), Ok(false));
+		assert_eq!(acl_storage.len(), 1);
+	}
+}
@@ -0,0 +1,115 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use types::all::Error;
+
+/// Source of time for time-dependent ACL storages.
+pub trait Clock: Send + Sync {
+	/// Get current time.
+	fn now(&self) -> Instant;
+
+	/// Get current unix time (in seconds). Used to check timestamps, issued by other parties.
+	fn unix_time(&self) -> Result<u64, Error>;
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+/// System clock.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+	fn now(&self) -> Instant {
+		Instant::now()
+	}
+
+	fn unix_time(&self) -> Result<u64, Error> {
+		SystemTime::now().duration_since(UNIX_EPOCH)
+			.map(|duration| duration.as_secs())
+			.map_err(|_| Error::Internal("system time is before unix epoch".to_owned()))
+	}
+}
+
+#[cfg(test)]
+pub mod tests {
+	use std::time::{Duration, Instant};
+	use parking_lot::Mutex;
+	use types::all::Error;
+	use super::Clock;
+
+	/// Unix time of the mock clock creation by default.
+	pub const MOCK_UNIX_START: u64 = 1500000000;
+
+	#[derive(Debug)]
+	/// Manually advanced clock.
+	pub struct MockClock {
+		/// Time at which clock was created.
+		start: Instant,
+		/// Unix time at which clock was created. None if unix time is not available.
+		unix_start: Mutex<Option<u64>>,
+		/// Time passed since creation.
+		elapsed: Mutex<Duration>,
+	}
+
+	impl MockClock {
+		/// Advance clock by given duration.
+		pub fn advance(&self, duration: Duration) {
+			let mut elapsed = self.elapsed.lock();
+			*elapsed = *elapsed + duration;
+		}
+
+		/// Set unix time of the clock creation. None makes unix time unavailable.
+		pub fn set_unix_start(&self, unix_start: Option<u64>) {
+			*self.unix_start.lock() = unix_start;
+		}
+	}
+
+	impl Default for MockClock {
+		fn default() -> Self {
+			MockClock {
+				start: Instant::now(),
+				unix_start: Mutex::new(Some(MOCK_UNIX_START)),
+				elapsed: Mutex::new(Duration::from_secs(0)),
+			}
+		}
+	}
+
+	impl Clock for MockClock {
+		fn now(&self) -> Instant {
+			self.start + *self.elapsed.lock()
+		}
+
+		fn unix_time(&self) -> Result<u64, Error> {
+			match *self.unix_start.lock() {
+				Some(unix_start) => Ok(unix_start + self.elapsed.lock().as_secs()),
+				None => Err(Error::Internal("unix time is not available".to_owned())),
+			}
+		}
+	}
+
+	#[test]
+	fn mock_clock_is_advanced_manually() {
+		let clock = MockClock::default();
+		let now = clock.now();
+		assert_eq!(clock.now(), now);
+
+		clock.advance(Duration::from_secs(10));
+		assert_eq!(clock.now(), now + Duration::from_secs(10));
+		assert_eq!(clock.unix_time(), Ok(MOCK_UNIX_START + 10));
+
+		clock.set_unix_start(None);
+		assert!(clock.unix_time().is_err());
+	}
+}
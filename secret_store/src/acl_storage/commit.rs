@@ -0,0 +1,310 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use parking_lot::Mutex;
+use ethkey::public_to_address;
+use types::all::{Error, DocumentAddress, Address, Public, Requester};
+use super::{AclStorage, AclContext, AclReport, DecisionBlock, CacheableDecision, EffectivePolicy, Clock, SystemClock};
+
+/// Handler of the granted access outcomes.
+pub type AccessHandler = Arc<Fn(&AccessRecord) + Send + Sync>;
+
+#[derive(Debug, PartialEq)]
+/// Pending grant, returned by `CommitAclStorage::check_with_commit`. Token must be passed either to `commit`,
+/// when document is actually accessed, or to `abort`, when access is cancelled. Tokens, which are neither
+/// committed nor aborted within configured TTL, expire. Every token could only be used once.
+pub struct GrantToken {
+	/// Token id.
+	id: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Final state of the pending grant.
+pub enum AccessOutcome {
+	/// Document has been accessed.
+	Committed,
+	/// Access has been cancelled.
+	Aborted,
+	/// Grant has been neither committed nor aborted in time.
+	Expired,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Outcome of the granted access.
+pub struct AccessRecord {
+	/// Requester address.
+	pub requester: Address,
+	/// Granted document.
+	pub document: DocumentAddress,
+	/// Final state of the grant.
+	pub outcome: AccessOutcome,
+}
+
+/// ACL storage, which separates access authorization from the actual access. Grants of the underlying storage
+/// are returned as pending tokens and outcome of every token is reported to the access handler, so that
+/// audit log only records documents as retrieved when access has been committed.
+pub struct CommitAclStorage {
+	/// Underlying ACL storage.
+	inner: Arc<AclStorage>,
+	/// Time, during which pending grant could be committed.
+	token_ttl: Duration,
+	/// Time source.
+	clock: Arc<Clock>,
+	/// Handler of access outcomes.
+	on_access: AccessHandler,
+	/// Id of the next token.
+	next_token_id: AtomicUsize,
+	/// Pending grants.
+	pending: Mutex<HashMap<usize, PendingGrant>>,
+}
+
+/// Grant, which is neither committed nor aborted yet.
+struct PendingGrant {
+	/// Requester address.
+	requester: Address,
+	/// Granted document.
+	document: DocumentAddress,
+	/// Time, when grant expires.
+	expires: Instant,
+}
+
+impl CommitAclStorage {
+	/// Create new committing ACL storage on top of `inner`.
+	pub fn new(inner: Arc<AclStorage>, token_ttl: Duration, on_access: AccessHandler) -> Self {
+		CommitAclStorage::with_clock(inner, token_ttl, on_access, Arc::new(SystemClock))
+	}
+
+	/// Create new committing ACL storage, which uses given clock to expire tokens.
+	pub fn with_clock(inner: Arc<AclStorage>, token_ttl: Duration, on_access: AccessHandler, clock: Arc<Clock>) -> Self {
+		CommitAclStorage {
+			inner: inner,
+			token_ttl: token_ttl,
+			clock: clock,
+			on_access: on_access,
+			next_token_id: AtomicUsize::new(0),
+			pending: Mutex::new(HashMap::new()),
+		}
+	}
+
+	/// Check if requester with given public key can access document with hash `document`.
+	/// Returns token of the pending grant if access is granted and None otherwise.
+	pub fn check_with_commit(&self, public: &Public, document: &DocumentAddress) -> Result<Option<GrantToken>, Error> {
+		self.prune_expired();
+		if !self.inner.check(&Requester::Public(public.clone()), document)? {
+			return Ok(None);
+		}
+
+		let id = self.next_token_id.fetch_add(1, Ordering::SeqCst);
+		self.pending.lock().insert(id, PendingGrant {
+			requester: public_to_address(public),
+			document: document.clone(),
+			expires: self.clock.now() + self.token_ttl,
+		});
+		Ok(Some(GrantToken {
+			id: id,
+		}))
+	}
+
+	/// Record that the document of the pending grant has been accessed.
+	/// Returns error if grant has already expired.
+	pub fn commit(&self, token: GrantToken) -> Result<(), Error> {
+		self.finalize(token, AccessOutcome::Committed)
+	}
+
+	/// Cancel the pending grant. Returns error if grant has already expired.
+	pub fn abort(&self, token: GrantToken) -> Result<(), Error> {
+		self.finalize(token, AccessOutcome::Aborted)
+	}
+
+	/// Expire all pending grants, which have not been committed or aborted in time.
+	/// Returns number of expired grants.
+	pub fn prune_expired(&self) -> usize {
+		let now = self.clock.now();
+		let expired: Vec<_> = {
+			let mut pending = self.pending.lock();
+			let expired_ids: Vec<_> = pending.iter()
+				.filter(|&(_, grant)| grant.expires <= now)
+				.map(|(id, _)| *id)
+				.collect();
+			expired_ids.into_iter().filter_map(|id| pending.remove(&id)).collect()
+		};
+
+		for grant in &expired {
+			self.report(grant, AccessOutcome::Expired);
+		}
+		expired.len()
+	}
+
+	/// Get number of pending grants.
+	pub fn pending_count(&self) -> usize {
+		self.pending.lock().len()
+	}
+
+	fn finalize(&self, token: GrantToken, outcome: AccessOutcome) -> Result<(), Error> {
+		let grant = self.pending.lock().remove(&token.id);
+		match grant {
+			Some(ref grant) if grant.expires > self.clock.now() => {
+				self.report(grant, outcome);
+				Ok(())
+			},
+			Some(ref grant) => {
+				self.report(grant, AccessOutcome::Expired);
+				Err(Error::Internal("grant token has expired".to_owned()))
+			},
+			None => Err(Error::Internal("grant token has expired".to_owned())),
+		}
+	}
+
+	fn report(&self, grant: &PendingGrant, outcome: AccessOutcome) {
+		trace!(target: "secretstore", "Access of {:?} to document {:?} is {:?}", grant.requester, grant.document, outcome);
+		(self.on_access)(&AccessRecord {
+			requester: grant.requester.clone(),
+			document: grant.document.clone(),
+			outcome: outcome,
+		});
+	}
+}
+
+impl AclStorage for CommitAclStorage {
+	fn check(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		self.inner.check(requester, document)
+	}
+
+	fn check_with_context(&self, requester: &Requester, document: &DocumentAddress, context: &AclContext) -> Result<bool, Error> {
+		self.inner.check_with_context(requester, document, context)
+	}
+
+	fn check_with_block(&self, requester: &Requester, document: &DocumentAddress) -> Result<(bool, Option<DecisionBlock>), Error> {
+		self.inner.check_with_block(requester, document)
+	}
+
+	fn check_with_expiry(&self, requester: &Requester, document: &DocumentAddress) -> Result<CacheableDecision, Error> {
+		self.inner.check_with_expiry(requester, document)
+	}
+
+	fn check_scoped_with_expiry(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<CacheableDecision, Error> {
+		self.inner.check_scoped_with_expiry(requester, document, scope)
+	}
+
+	fn check_scoped(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<bool, Error> {
+		self.inner.check_scoped(requester, document, scope)
+	}
+
+	fn check_dry_run(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		self.inner.check_dry_run(requester, document)
+	}
+
+	fn check_scoped_dry_run(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<bool, Error> {
+		self.inner.check_scoped_dry_run(requester, document, scope)
+	}
+
+	fn check_and_consume(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		self.inner.check_and_consume(requester, document)
+	}
+
+	fn list_requestors(&self, document: &DocumentAddress) -> Result<Vec<Address>, Error> {
+		self.inner.list_requestors(document)
+	}
+
+	fn effective_policy(&self, public: &Public) -> Result<EffectivePolicy, Error> {
+		self.inner.effective_policy(public)
+	}
+
+	fn fill_report(&self, report: &mut AclReport) {
+		self.inner.fill_report(report)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::time::Duration;
+	use parking_lot::Mutex;
+	use ethkey::{Random, Generator, KeyPair};
+	use types::all::DocumentAddress;
+	use acl_storage::tests::DummyAclStorage;
+	use acl_storage::clock::tests::MockClock;
+	use super::{CommitAclStorage, AccessRecord, AccessOutcome};
+
+	fn acl_storage(inner: Arc<DummyAclStorage>, clock: Arc<MockClock>) -> (CommitAclStorage, Arc<Mutex<Vec<AccessRecord>>>) {
+		let records = Arc::new(Mutex::new(Vec::new()));
+		let handler_records = records.clone();
+		let acl_storage = CommitAclStorage::with_clock(inner, Duration::from_secs(10),
+			Arc::new(move |record: &AccessRecord| handler_records.lock().push(record.clone())), clock);
+		(acl_storage, records)
+	}
+
+	fn record(key_pair: &KeyPair, outcome: AccessOutcome) -> AccessRecord {
+		AccessRecord {
+			requester: key_pair.address(),
+			document: DocumentAddress::from(1),
+			outcome: outcome,
+		}
+	}
+
+	#[test]
+	fn committed_access_is_recorded() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let (acl_storage, records) = acl_storage(inner.clone(), Arc::new(MockClock::default()));
+		let key_pair = Random.generate().unwrap();
+
+		let token = acl_storage.check_with_commit(key_pair.public(), &DocumentAddress::from(1)).unwrap().unwrap();
+		assert_eq!(acl_storage.pending_count(), 1);
+		assert!(records.lock().is_empty());
+		assert_eq!(acl_storage.commit(token), Ok(()));
+		assert_eq!(acl_storage.pending_count(), 0);
+		assert_eq!(*records.lock(), vec![record(&key_pair, AccessOutcome::Committed)]);
+
+		// denied access has no token
+		inner.prohibit(key_pair.public().clone(), DocumentAddress::from(1));
+		assert_eq!(acl_storage.check_with_commit(key_pair.public(), &DocumentAddress::from(1)), Ok(None));
+	}
+
+	#[test]
+	fn aborted_access_is_recorded() {
+		let (acl_storage, records) = acl_storage(Arc::new(DummyAclStorage::default()), Arc::new(MockClock::default()));
+		let key_pair = Random.generate().unwrap();
+
+		let token = acl_storage.check_with_commit(key_pair.public(), &DocumentAddress::from(1)).unwrap().unwrap();
+		assert_eq!(acl_storage.abort(token), Ok(()));
+		assert_eq!(acl_storage.pending_count(), 0);
+		assert_eq!(*records.lock(), vec![record(&key_pair, AccessOutcome::Aborted)]);
+	}
+
+	#[test]
+	fn pending_grant_expires() {
+		let clock = Arc::new(MockClock::default());
+		let (acl_storage, records) = acl_storage(Arc::new(DummyAclStorage::default()), clock.clone());
+		let key_pair = Random.generate().unwrap();
+
+		let late_token = acl_storage.check_with_commit(key_pair.public(), &DocumentAddress::from(1)).unwrap().unwrap();
+		let pruned_token = acl_storage.check_with_commit(key_pair.public(), &DocumentAddress::from(1)).unwrap().unwrap();
+		clock.advance(Duration::from_secs(10));
+
+		// grant could not be committed after expiration
+		assert!(acl_storage.commit(late_token).is_err());
+		assert_eq!(*records.lock(), vec![record(&key_pair, AccessOutcome::Expired)]);
+
+		assert_eq!(acl_storage.prune_expired(), 1);
+		assert_eq!(acl_storage.pending_count(), 0);
+		assert!(acl_storage.abort(pruned_token).is_err());
+		assert_eq!(*records.lock(), vec![record(&key_pair, AccessOutcome::Expired), record(&key_pair, AccessOutcome::Expired)]);
+	}
+}
@@ -0,0 +1,513 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::cell::RefCell;
+use std::sync::Arc;
+use std::time::Instant;
+use types::all::{Error, DocumentAddress, Address, Public, Requester};
+use super::{AclStorage, AclContext, AclReport, DecisionBlock, CacheableDecision, EffectivePolicy, limit_listed_requestors};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// How decisions of composite storage backends are combined.
+pub enum CombinePolicy {
+	/// Access is granted if at least one backend grants it.
+	Any,
+	/// Access is granted only if all backends grant it.
+	All,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// How composite storage treats backend, which has failed to check access.
+pub enum OnBackendError {
+	/// Failed backend denies access. This is the safest option: with `CombinePolicy::All` any failure
+	/// denies access, with `CombinePolicy::Any` access is granted only if some other backend grants it.
+	Deny,
+	/// Failed backend is skipped. With `CombinePolicy::All` restrictions of the failed backend are not
+	/// enforced, so access could be granted to requesters, which it would deny. Access is denied if all backends fail.
+	Abstain,
+	/// Backend error is returned as the result of the check. Decision is not made in this case, so the
+	/// caller must handle the error. Backends are asked in order until the decision is known, so errors of
+	/// backends, following the deciding one, are not reported.
+	Propagate,
+}
+
+impl Default for OnBackendError {
+	fn default() -> Self {
+		OnBackendError::Deny
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Composite ACL check decision with per-backend breakdown.
+/// Backends are identified by their index in the composite storage.
+pub struct CompositeDecision {
+	/// Final decision.
+	pub granted: bool,
+	/// Backends, which have granted access.
+	pub granted_by: Vec<usize>,
+	/// Backends, which have denied access.
+	pub denied_by: Vec<usize>,
+	/// Backends, which have failed to check access.
+	pub errors: Vec<(usize, Error)>,
+}
+
+/// ACL storage, which combines decisions of several backends.
+/// By default backend errors are treated as denials, so that composite storage fails closed.
+pub struct CompositeAclStorage {
+	/// Combined backends.
+	backends: Vec<Arc<AclStorage>>,
+	/// Combine policy.
+	policy: CombinePolicy,
+	/// How backend errors are handled.
+	on_backend_error: OnBackendError,
+}
+
+impl CompositeAclStorage {
+	/// Create new composite ACL storage, which treats backend errors as denials.
+	pub fn new(backends: Vec<Arc<AclStorage>>, policy: CombinePolicy) -> Self {
+		CompositeAclStorage::with_error_policy(backends, policy, OnBackendError::default())
+	}
+
+	/// Create new composite ACL storage with given handling of backend errors.
+	pub fn with_error_policy(backends: Vec<Arc<AclStorage>>, policy: CombinePolicy, on_backend_error: OnBackendError) -> Self {
+		assert!(!backends.is_empty(), "composite ACL storage requires at least one backend");
+
+		CompositeAclStorage {
+			backends: backends,
+			policy: policy,
+			on_backend_error: on_backend_error,
+		}
+	}
+
+	/// Check access, asking every backend, and report decision of each backend.
+	pub fn check_explained(&self, requester: &Requester, document: &DocumentAddress) -> Result<CompositeDecision, Error> {
+		let requester: Requester = requester.resolve(document)?.into();
+		let mut decision = CompositeDecision {
+			granted: false,
+			granted_by: Vec::new(),
+			denied_by: Vec::new(),
+			errors: Vec::new(),
+		};
+
+		let results: Vec<_> = self.backends.iter().map(|backend| backend.check(&requester, document)).collect();
+		for (index, result) in results.iter().enumerate() {
+			match *result {
+				Ok(true) => decision.granted_by.push(index),
+				Ok(false) => decision.denied_by.push(index),
+				Err(ref err) => decision.errors.push((index, err.clone())),
+			}
+		}
+
+		// final decision is made the same way as by regular check, so that errors of backends, following
+		// the deciding one, are ignored
+		decision.granted = self.combine_results(results.into_iter())?;
+		Ok(decision)
+	}
+
+	/// Combine decisions of backends, asking them in order until the decision is known.
+	fn combine<F>(&self, check: F) -> Result<bool, Error> where F: Fn(&AclStorage) -> Result<bool, Error> {
+		self.combine_results(self.backends.iter().map(|backend| check(&**backend)))
+	}
+
+	/// Combine ordered results of backends checks. Results are consumed until the decision is known.
+	fn combine_results<I>(&self, results: I) -> Result<bool, Error> where I: Iterator<Item=Result<bool, Error>> {
+		let mut has_votes = false;
+		for result in results {
+			let granted = match result {
+				Ok(granted) => granted,
+				Err(error) => match self.on_backend_error {
+					OnBackendError::Deny => false,
+					OnBackendError::Abstain => continue,
+					OnBackendError::Propagate => return Err(error),
+				},
+			};
+
+			has_votes = true;
+			match self.policy {
+				CombinePolicy::Any if granted => return Ok(true),
+				CombinePolicy::All if !granted => return Ok(false),
+				_ => (),
+			}
+		}
+
+		Ok(match self.policy {
+			CombinePolicy::Any => false,
+			CombinePolicy::All => has_votes,
+		})
+	}
+
+	/// Combine decisions of backends, asking them in order until the decision is known. Combined decision expires
+	/// with the earliest decision of the backends, which have been asked, and is made at the oldest block of these backends.
+	fn combine_with_expiry<F>(&self, check: F) -> Result<CacheableDecision, Error> where F: Fn(&AclStorage) -> Result<CacheableDecision, Error> {
+		let earliest_not_after: RefCell<Option<Instant>> = RefCell::new(None);
+		let oldest_block: RefCell<Option<DecisionBlock>> = RefCell::new(None);
+		let granted = self.combine(|backend| {
+			let decision = check(backend)?;
+			if let Some(not_after) = decision.not_after {
+				let mut earliest = earliest_not_after.borrow_mut();
+				if earliest.map(|earliest| not_after < earliest).unwrap_or(true) {
+					*earliest = Some(not_after);
+				}
+			}
+			if let Some(block) = decision.block {
+				let mut oldest = oldest_block.borrow_mut();
+				if oldest.as_ref().map(|oldest| block.number < oldest.number).unwrap_or(true) {
+					*oldest = Some(block);
+				}
+			}
+			Ok(decision.granted)
+		})?;
+		Ok(CacheableDecision {
+			granted: granted,
+			not_after: earliest_not_after.into_inner(),
+			block: oldest_block.into_inner(),
+		})
+	}
+}
+
+impl AclStorage for CompositeAclStorage {
+	fn check(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		// resolve once, so that signature is not recovered by every backend
+		let requester: Requester = requester.resolve(document)?.into();
+		self.combine(|backend| backend.check(&requester, document))
+	}
+
+	fn check_with_context(&self, requester: &Requester, document: &DocumentAddress, context: &AclContext) -> Result<bool, Error> {
+		let requester: Requester = requester.resolve(document)?.into();
+		self.combine(|backend| backend.check_with_context(&requester, document, context))
+	}
+
+	/// Decision is reported to be made at the oldest block of the backends, which have been asked.
+	fn check_with_block(&self, requester: &Requester, document: &DocumentAddress) -> Result<(bool, Option<DecisionBlock>), Error> {
+		let requester: Requester = requester.resolve(document)?.into();
+		let oldest_block: RefCell<Option<DecisionBlock>> = RefCell::new(None);
+		let granted = self.combine(|backend| {
+			let (granted, block) = backend.check_with_block(&requester, document)?;
+			if let Some(block) = block {
+				let mut oldest = oldest_block.borrow_mut();
+				if oldest.as_ref().map(|oldest| block.number < oldest.number).unwrap_or(true) {
+					*oldest = Some(block);
+				}
+			}
+			Ok(granted)
+		})?;
+		Ok((granted, oldest_block.into_inner()))
+	}
+
+	/// Decision could be cached until the earliest expiry of the backends, which have been asked,
+	/// and is reported to be made at the oldest block of these backends.
+	fn check_with_expiry(&self, requester: &Requester, document: &DocumentAddress) -> Result<CacheableDecision, Error> {
+		let requester: Requester = requester.resolve(document)?.into();
+		self.combine_with_expiry(|backend| backend.check_with_expiry(&requester, document))
+	}
+
+	fn check_scoped_with_expiry(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<CacheableDecision, Error> {
+		let requester: Requester = requester.resolve(document)?.into();
+		self.combine_with_expiry(|backend| backend.check_scoped_with_expiry(&requester, document, scope))
+	}
+
+	fn check_scoped(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<bool, Error> {
+		let requester: Requester = requester.resolve(document)?.into();
+		self.combine(|backend| backend.check_scoped(&requester, document, scope))
+	}
+
+	fn check_dry_run(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		let requester: Requester = requester.resolve(document)?.into();
+		self.combine(|backend| backend.check_dry_run(&requester, document))
+	}
+
+	fn check_scoped_dry_run(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<bool, Error> {
+		let requester: Requester = requester.resolve(document)?.into();
+		self.combine(|backend| backend.check_scoped_dry_run(&requester, document, scope))
+	}
+
+	/// Consume access if it is granted by the combined decision. Every backend must support single-use documents:
+	/// access is checked by all backends before it is consumed, so that the document is not consumed by one
+	/// backend, when another one denies access.
+	fn check_and_consume(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		let requester: Requester = requester.resolve(document)?.into();
+		if !self.combine(|backend| backend.check(&requester, document))? {
+			return Ok(false);
+		}
+
+		self.combine(|backend| backend.check_and_consume(&requester, document))
+	}
+
+	/// List requestors, allowed by the backends. Every backend must support enumeration.
+	fn list_requestors(&self, document: &DocumentAddress) -> Result<Vec<Address>, Error> {
+		let mut requestors = self.backends[0].list_requestors(document)?;
+		for backend in &self.backends[1..] {
+			let backend_requestors = backend.list_requestors(document)?;
+			match self.policy {
+				CombinePolicy::Any => for requestor in backend_requestors {
+					if !requestors.contains(&requestor) {
+						requestors.push(requestor);
+					}
+				},
+				CombinePolicy::All => requestors.retain(|requestor| backend_requestors.contains(requestor)),
+			}
+		}
+
+		limit_listed_requestors(requestors)
+	}
+
+	/// Combine policies of the backends. Every backend must support enumeration.
+	fn effective_policy(&self, public: &Public) -> Result<EffectivePolicy, Error> {
+		let mut policies = Vec::with_capacity(self.backends.len());
+		for backend in &self.backends {
+			policies.push(backend.effective_policy(public)?);
+		}
+
+		Ok(match self.policy {
+			CombinePolicy::Any => EffectivePolicy::union(policies),
+			CombinePolicy::All => EffectivePolicy::intersection(policies),
+		})
+	}
+
+	fn fill_report(&self, report: &mut AclReport) {
+		for backend in &self.backends {
+			backend.fill_report(report);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::time::Duration;
+	use ethkey::{Random, Generator};
+	use util::Address;
+	use types::all::{Error, DocumentAddress, Public, Requester};
+	use acl_storage::{AclStorage, TemporaryGrantAclStorage};
+	use acl_storage::tests::{DummyAclStorage, AllowlistAclStorage};
+	use acl_storage::clock::tests::MockClock;
+	use super::{CompositeAclStorage, CombinePolicy, CompositeDecision, OnBackendError};
+
+	struct FailingAclStorage;
+
+	impl AclStorage for FailingAclStorage {
+		fn check(&self, _requester: &Requester, _document: &DocumentAddress) -> Result<bool, Error> {
+			Err(Error::Internal("backend is broken".into()))
+		}
+	}
+
+	fn prepare_backends(public: &Public, document: &DocumentAddress) -> Vec<Arc<AclStorage>> {
+		let denying = DummyAclStorage::default();
+		denying.prohibit(public.clone(), document.clone());
+		vec![Arc::new(DummyAclStorage::default()), Arc::new(denying), Arc::new(FailingAclStorage)]
+	}
+
+	#[test]
+	fn any_policy_breakdown() {
+		let public = Random.generate().unwrap().public().clone();
+		let document = DocumentAddress::from(1);
+		let acl_storage = CompositeAclStorage::new(prepare_backends(&public, &document), CombinePolicy::Any);
+
+		assert_eq!(acl_storage.check(&public.clone().into(), &document), Ok(true));
+		assert_eq!(acl_storage.check_explained(&public.clone().into(), &document), Ok(CompositeDecision {
+			granted: true,
+			granted_by: vec![0],
+			denied_by: vec![1],
+			errors: vec![(2, Error::Internal("backend is broken".into()))],
+		}));
+	}
+
+	#[test]
+	fn all_policy_breakdown() {
+		let public = Random.generate().unwrap().public().clone();
+		let document = DocumentAddress::from(1);
+		let acl_storage = CompositeAclStorage::new(prepare_backends(&public, &document), CombinePolicy::All);
+
+		assert_eq!(acl_storage.check(&public.clone().into(), &document), Ok(false));
+		assert_eq!(acl_storage.check_explained(&public.clone().into(), &document), Ok(CompositeDecision {
+			granted: false,
+			granted_by: vec![0],
+			denied_by: vec![1],
+			errors: vec![(2, Error::Internal("backend is broken".into()))],
+		}));
+	}
+
+	#[test]
+	fn requestors_are_combined() {
+		let document = DocumentAddress::from(1);
+		let (requestor1, requestor2) = (Random.generate().unwrap().address(), Random.generate().unwrap().address());
+		let backend1 = Arc::new(AllowlistAclStorage::default());
+		backend1.allow(requestor1.clone(), document.clone());
+		backend1.allow(requestor2.clone(), document.clone());
+		let backend2 = Arc::new(AllowlistAclStorage::default());
+		backend2.allow(requestor2.clone(), document.clone());
+		let backends: Vec<Arc<AclStorage>> = vec![backend1, backend2];
+
+		let acl_storage = CompositeAclStorage::new(backends.clone(), CombinePolicy::Any);
+		assert_eq!(acl_storage.list_requestors(&document), Ok(vec![requestor1.clone(), requestor2.clone()]));
+		let acl_storage = CompositeAclStorage::new(backends.clone(), CombinePolicy::All);
+		assert_eq!(acl_storage.list_requestors(&document), Ok(vec![requestor2.clone()]));
+
+		// enumeration fails if any backend does not support it
+		let backends: Vec<Arc<AclStorage>> = vec![backends[0].clone(), Arc::new(DummyAclStorage::default())];
+		let acl_storage = CompositeAclStorage::new(backends, CombinePolicy::Any);
+		assert!(acl_storage.list_requestors(&document).is_err());
+	}
+
+	#[test]
+	fn errors_are_treated_as_denials() {
+		let public = Random.generate().unwrap().public().clone();
+		let document = DocumentAddress::from(1);
+		let backends: Vec<Arc<AclStorage>> = vec![Arc::new(DummyAclStorage::default()), Arc::new(FailingAclStorage)];
+		let acl_storage = CompositeAclStorage::new(backends, CombinePolicy::All);
+
+		assert_eq!(acl_storage.check(&public.clone().into(), &document), Ok(false));
+		let decision = acl_storage.check_explained(&public.clone().into(), &document).unwrap();
+		assert!(!decision.granted);
+		assert_eq!(decision.granted_by, vec![0]);
+		assert_eq!(decision.errors.len(), 1);
+	}
+
+	#[test]
+	fn backend_errors_are_handled_according_to_policy() {
+		let public = Random.generate().unwrap().public().clone();
+		let document = DocumentAddress::from(1);
+		let granting: Arc<AclStorage> = Arc::new(DummyAclStorage::default());
+		let denying = DummyAclStorage::default();
+		denying.prohibit(public.clone(), document.clone());
+		let denying: Arc<AclStorage> = Arc::new(denying);
+		let failing: Arc<AclStorage> = Arc::new(FailingAclStorage);
+		let error = Err(Error::Internal("backend is broken".into()));
+
+		let scenarios = vec![
+			vec![granting.clone(), failing.clone()],
+			vec![denying.clone(), failing.clone()],
+			vec![failing.clone()],
+			vec![failing.clone(), granting.clone()],
+		];
+		let expectations = vec![
+			(CombinePolicy::Any, OnBackendError::Deny, vec![Ok(true), Ok(false), Ok(false), Ok(true)]),
+			(CombinePolicy::Any, OnBackendError::Abstain, vec![Ok(true), Ok(false), Ok(false), Ok(true)]),
+			(CombinePolicy::Any, OnBackendError::Propagate, vec![Ok(true), error.clone(), error.clone(), error.clone()]),
+			(CombinePolicy::All, OnBackendError::Deny, vec![Ok(false), Ok(false), Ok(false), Ok(false)]),
+			(CombinePolicy::All, OnBackendError::Abstain, vec![Ok(true), Ok(false), Ok(false), Ok(true)]),
+			(CombinePolicy::All, OnBackendError::Propagate, vec![error.clone(), Ok(false), error.clone(), error.clone()]),
+		];
+
+		for (policy, on_backend_error, results) in expectations {
+			for (backends, result) in scenarios.iter().zip(results) {
+				let acl_storage = CompositeAclStorage::with_error_policy(backends.clone(), policy, on_backend_error);
+				assert_eq!(acl_storage.check(&public.clone().into(), &document), result,
+					"policy: {:?}, on error: {:?}, backends: {}", policy, on_backend_error, backends.len());
+				assert_eq!(acl_storage.check_dry_run(&public.clone().into(), &document), result);
+			}
+		}
+	}
+
+	#[test]
+	fn explained_decision_respects_error_policy() {
+		let public = Random.generate().unwrap().public().clone();
+		let document = DocumentAddress::from(1);
+		let backends: Vec<Arc<AclStorage>> = vec![Arc::new(DummyAclStorage::default()), Arc::new(FailingAclStorage)];
+
+		let acl_storage = CompositeAclStorage::with_error_policy(backends.clone(), CombinePolicy::All, OnBackendError::Abstain);
+		assert!(acl_storage.check_explained(&public.clone().into(), &document).unwrap().granted);
+		let acl_storage = CompositeAclStorage::with_error_policy(backends.clone(), CombinePolicy::All, OnBackendError::Propagate);
+		assert_eq!(acl_storage.check_explained(&public.clone().into(), &document), Err(Error::Internal("backend is broken".into())));
+		let acl_storage = CompositeAclStorage::with_error_policy(vec![backends[1].clone()], CombinePolicy::All, OnBackendError::Abstain);
+		assert!(!acl_storage.check_explained(&public.clone().into(), &document).unwrap().granted);
+	}
+
+	#[test]
+	fn explained_decision_matches_check() {
+		let public = Random.generate().unwrap().public().clone();
+		let document = DocumentAddress::from(1);
+		let granting: Arc<AclStorage> = Arc::new(DummyAclStorage::default());
+		let denying = DummyAclStorage::default();
+		denying.prohibit(public.clone(), document.clone());
+		let denying: Arc<AclStorage> = Arc::new(denying);
+		let failing: Arc<AclStorage> = Arc::new(FailingAclStorage);
+
+		// backend, following the granting one, is not deciding
+		let acl_storage = CompositeAclStorage::with_error_policy(vec![granting.clone(), failing.clone()], CombinePolicy::Any, OnBackendError::Propagate);
+		assert_eq!(acl_storage.check(&public.clone().into(), &document), Ok(true));
+		assert_eq!(acl_storage.check_explained(&public.clone().into(), &document), Ok(CompositeDecision {
+			granted: true,
+			granted_by: vec![0],
+			denied_by: vec![],
+			errors: vec![(1, Error::Internal("backend is broken".into()))],
+		}));
+
+		let scenarios = vec![
+			vec![granting.clone(), failing.clone()],
+			vec![denying.clone(), failing.clone()],
+			vec![failing.clone(), granting.clone()],
+			vec![failing.clone(), denying.clone()],
+			vec![granting.clone(), denying.clone(), failing.clone()],
+		];
+		for &policy in &[CombinePolicy::Any, CombinePolicy::All] {
+			for &on_backend_error in &[OnBackendError::Deny, OnBackendError::Abstain, OnBackendError::Propagate] {
+				for backends in &scenarios {
+					let acl_storage = CompositeAclStorage::with_error_policy(backends.clone(), policy, on_backend_error);
+					assert_eq!(acl_storage.check_explained(&public.clone().into(), &document).map(|decision| decision.granted),
+						acl_storage.check(&public.clone().into(), &document),
+						"policy: {:?}, on error: {:?}, backends: {}", policy, on_backend_error, backends.len());
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn policies_of_backends_are_combined() {
+		let key_pair = Random.generate().unwrap();
+		let backend1 = Arc::new(AllowlistAclStorage::default());
+		backend1.allow(key_pair.address(), DocumentAddress::from(1));
+		backend1.allow(key_pair.address(), DocumentAddress::from(2));
+		let backend2 = Arc::new(AllowlistAclStorage::default());
+		backend2.allow(key_pair.address(), DocumentAddress::from(2));
+		let backends: Vec<Arc<AclStorage>> = vec![backend1, backend2];
+
+		let acl_storage = CompositeAclStorage::new(backends.clone(), CombinePolicy::Any);
+		let policy = acl_storage.effective_policy(key_pair.public()).unwrap();
+		assert!(policy.allows(&DocumentAddress::from(1)) && policy.allows(&DocumentAddress::from(2)));
+		let acl_storage = CompositeAclStorage::new(backends.clone(), CombinePolicy::All);
+		let policy = acl_storage.effective_policy(key_pair.public()).unwrap();
+		assert!(!policy.allows(&DocumentAddress::from(1)) && policy.allows(&DocumentAddress::from(2)));
+
+		// enumeration fails if any backend does not support it
+		let backends: Vec<Arc<AclStorage>> = vec![backends[0].clone(), Arc::new(FailingAclStorage)];
+		let acl_storage = CompositeAclStorage::new(backends, CombinePolicy::Any);
+		assert!(acl_storage.effective_policy(key_pair.public()).is_err());
+	}
+
+	#[test]
+	fn decision_expires_with_earliest_asked_backend_decision() {
+		let clock = Arc::new(MockClock::default());
+		let backend1 = Arc::new(TemporaryGrantAclStorage::with_clock(Arc::new(AllowlistAclStorage::default()), clock.clone()));
+		backend1.grant(Address::from(2), DocumentAddress::from(1), Duration::from_secs(20));
+		let backend2 = Arc::new(TemporaryGrantAclStorage::with_clock(Arc::new(AllowlistAclStorage::default()), clock.clone()));
+		backend2.grant(Address::from(2), DocumentAddress::from(1), Duration::from_secs(10));
+		let backends: Vec<Arc<AclStorage>> = vec![backend1, backend2];
+		let requester = Requester::Address(Address::from(2));
+
+		// both backends are asked => decision expires with the earliest grant
+		let acl_storage = CompositeAclStorage::new(backends.clone(), CombinePolicy::All);
+		let decision = acl_storage.check_with_expiry(&requester, &DocumentAddress::from(1)).unwrap();
+		assert!(decision.granted);
+		assert_eq!(decision.not_after, Some(clock.now() + Duration::from_secs(10)));
+
+		// the first backend decides alone => its grant is the only one, limiting decision lifetime
+		let acl_storage = CompositeAclStorage::new(backends, CombinePolicy::Any);
+		let decision = acl_storage.check_with_expiry(&requester, &DocumentAddress::from(1)).unwrap();
+		assert!(decision.granted);
+		assert_eq!(decision.not_after, Some(clock.now() + Duration::from_secs(20)));
+	}
+}
@@ -0,0 +1,141 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use byteorder::{BigEndian, WriteBytesExt};
+use ethkey::{self, Secret, Signature};
+use util::{H256, Hashable};
+use types::all::{Error, DocumentAddress, Address, Public};
+
+/// Domain separator of ACL decision hashes.
+const ACL_DECISION_DOMAIN: &'static [u8] = b"secretstore_acl_decision";
+
+#[derive(Debug, Clone, PartialEq)]
+/// ACL decision, made by key server. Decisions are signed by key server key, so that
+/// other key servers could trust them.
+pub struct AclDecision {
+	/// Requester address.
+	pub requester: Address,
+	/// Checked document.
+	pub document: DocumentAddress,
+	/// Hash of the block, at which decision has been made.
+	pub block: H256,
+	/// Is access granted.
+	pub granted: bool,
+	/// Unix timestamp (in seconds), when decision has been made.
+	pub timestamp: u64,
+}
+
+impl AclDecision {
+	/// Get hash of the decision, which is signed by the key server:
+	/// `keccak256("secretstore_acl_decision" ++ requester ++ document ++ block ++ granted ++ u64_be(timestamp))`.
+	/// Domain separator guarantees that signature of other secret store message is never accepted as decision signature.
+	pub fn hash(&self) -> H256 {
+		let mut data = Vec::with_capacity(ACL_DECISION_DOMAIN.len() + 20 + 32 + 32 + 1 + 8);
+		data.extend_from_slice(ACL_DECISION_DOMAIN);
+		data.extend_from_slice(&*self.requester);
+		data.extend_from_slice(&*self.document);
+		data.extend_from_slice(&*self.block);
+		data.push(if self.granted { 1 } else { 0 });
+		data.write_u64::<BigEndian>(self.timestamp).expect("writing to vec never fails; qed");
+		data.sha3()
+	}
+}
+
+/// Sign ACL decision with key server secret key.
+pub fn sign_decision(secret: &Secret, decision: &AclDecision) -> Result<Signature, Error> {
+	Ok(ethkey::sign(secret, &decision.hash())?)
+}
+
+/// Check that ACL decision is signed by key server with given public key.
+pub fn verify_decision(public: &Public, decision: &AclDecision, signature: &Signature) -> Result<bool, Error> {
+	Ok(ethkey::verify_public(public, signature, &decision.hash())?)
+}
+
+#[cfg(test)]
+mod tests {
+	use byteorder::{BigEndian, WriteBytesExt};
+	use ethkey::{self, Random, Generator, KeyPair};
+	use util::{H256, Hashable};
+	use types::all::DocumentAddress;
+	use acl_storage::{AccessTicket, sign_ticket};
+	use super::{AclDecision, sign_decision, verify_decision};
+
+	fn decision(key_pair: &KeyPair) -> AclDecision {
+		AclDecision {
+			requester: key_pair.address(),
+			document: DocumentAddress::from(1),
+			block: H256::from(2),
+			granted: true,
+			timestamp: 1500000000,
+		}
+	}
+
+	#[test]
+	fn signed_decision_is_verified() {
+		let requester = Random.generate().unwrap();
+		let node = Random.generate().unwrap();
+		let decision = decision(&requester);
+
+		let signature = sign_decision(node.secret(), &decision).unwrap();
+		assert_eq!(verify_decision(node.public(), &decision, &signature), Ok(true));
+	}
+
+	#[test]
+	fn tampered_decision_is_rejected() {
+		let requester = Random.generate().unwrap();
+		let node = Random.generate().unwrap();
+		let decision = decision(&requester);
+		let signature = sign_decision(node.secret(), &decision).unwrap();
+
+		let mut tampered = decision.clone();
+		tampered.granted = false;
+		assert_eq!(verify_decision(node.public(), &tampered, &signature), Ok(false));
+
+		let mut tampered = decision.clone();
+		tampered.timestamp += 1;
+		assert_eq!(verify_decision(node.public(), &tampered, &signature), Ok(false));
+
+		// decision, signed by other node
+		let other_node = Random.generate().unwrap();
+		assert_eq!(verify_decision(other_node.public(), &decision, &signature), Ok(false));
+	}
+
+	#[test]
+	fn signatures_of_other_messages_are_not_accepted() {
+		let requester = Random.generate().unwrap();
+		let node = Random.generate().unwrap();
+		let decision = decision(&requester);
+
+		// access ticket signature
+		let ticket = AccessTicket {
+			requester: decision.requester.clone(),
+			document: decision.document.clone(),
+			expires: decision.timestamp,
+		};
+		let signature = sign_ticket(node.secret(), &ticket).unwrap();
+		assert_eq!(verify_decision(node.public(), &decision, &signature), Ok(false));
+
+		// signature of the same fields without domain separator
+		let mut data = Vec::new();
+		data.extend_from_slice(&*decision.requester);
+		data.extend_from_slice(&*decision.document);
+		data.extend_from_slice(&*decision.block);
+		data.push(1);
+		data.write_u64::<BigEndian>(decision.timestamp).unwrap();
+		let signature = ethkey::sign(node.secret(), &data.sha3()).unwrap();
+		assert_eq!(verify_decision(node.public(), &decision, &signature), Ok(false));
+	}
+}
@@ -0,0 +1,494 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::VecDeque;
+use parking_lot::{Mutex, RwLock, Condvar};
+use types::all::{Error, DocumentAddress, Address, Public, Requester};
+use super::{AclStorage, AclContext, AclReport, AclAuditEvent, DecisionBlock, CacheableDecision, EffectivePolicy};
+
+#[derive(Debug, Clone, PartialEq)]
+/// Typed ACL event, delivered to every sink of the event stream.
+pub enum AclEvent {
+	/// Access has been granted.
+	Granted {
+		/// Requester, as it was passed to the check.
+		requester: Requester,
+		/// Checked document.
+		document: DocumentAddress,
+		/// Scope of the check. Empty for plain checks.
+		scope: String,
+	},
+	/// Access has been denied.
+	Denied {
+		/// Requester, as it was passed to the check.
+		requester: Requester,
+		/// Checked document.
+		document: DocumentAddress,
+		/// Scope of the check. Empty for plain checks.
+		scope: String,
+	},
+	/// Check has failed.
+	Failed {
+		/// Requester, as it was passed to the check.
+		requester: Requester,
+		/// Checked document.
+		document: DocumentAddress,
+		/// Scope of the check. Empty for plain checks.
+		scope: String,
+		/// Check error.
+		error: Error,
+	},
+	/// ACL configuration has been changed at runtime.
+	ConfigChanged {
+		/// Name of the changed setting.
+		setting: String,
+		/// New value of the setting.
+		value: String,
+	},
+}
+
+/// Sink of ACL events. Sinks are called synchronously, in the order of events, so they must not block for long.
+pub trait AclEventSink: Send + Sync {
+	/// Handle single event.
+	fn emit(&self, event: &AclEvent);
+}
+
+/// Sink, which writes events to the node log.
+pub struct LogEventSink;
+
+/// Sink, which appends events to the file, one event per line.
+pub struct FileEventSink {
+	/// Events file.
+	file: Mutex<File>,
+}
+
+/// Sink, which buffers events for the receiver. Slow receiver never blocks the stream: when the buffer
+/// is full, the oldest event is dropped. Events are not buffered when receiver is dropped.
+pub struct ChannelEventSink {
+	/// Events queue.
+	queue: Arc<EventsQueue>,
+}
+
+/// Receiving end of the channel sink.
+pub struct AclEventReceiver {
+	/// Events queue.
+	queue: Arc<EventsQueue>,
+}
+
+/// Bounded queue of the channel sink.
+struct EventsQueue {
+	/// Max number of buffered events.
+	buffer_size: usize,
+	/// Queue data.
+	data: Mutex<EventsQueueData>,
+	/// Signalled when new event is pushed or queue is closed.
+	available: Condvar,
+	/// Number of events, dropped because receiver was too slow.
+	dropped: AtomicUsize,
+}
+
+/// Mutable data of the channel sink queue.
+struct EventsQueueData {
+	/// Buffered events.
+	events: VecDeque<AclEvent>,
+	/// True when either sink or receiver is dropped.
+	is_closed: bool,
+}
+
+/// Single stream of ACL events (decisions, denials, errors and configuration changes), delivered to all sinks.
+pub struct AclEventStream {
+	/// Registered sinks.
+	sinks: RwLock<Vec<Arc<AclEventSink>>>,
+}
+
+/// ACL storage, which emits every decision of the underlying storage to the event stream.
+/// Dry-run checks have no side effects, so they are not emitted.
+pub struct EventStreamAclStorage {
+	/// Underlying ACL storage.
+	inner: Arc<AclStorage>,
+	/// Events stream.
+	stream: Arc<AclEventStream>,
+}
+
+impl AclEvent {
+	/// Create event of the check with given result.
+	pub fn decision(requester: &Requester, document: &DocumentAddress, scope: &str, result: &Result<bool, Error>) -> Self {
+		let (requester, document, scope) = (requester.clone(), document.clone(), scope.to_owned());
+		match *result {
+			Ok(true) => AclEvent::Granted { requester: requester, document: document, scope: scope },
+			Ok(false) => AclEvent::Denied { requester: requester, document: document, scope: scope },
+			Err(ref error) => AclEvent::Failed { requester: requester, document: document, scope: scope, error: error.clone() },
+		}
+	}
+}
+
+impl From<AclAuditEvent> for AclEvent {
+	fn from(event: AclAuditEvent) -> Self {
+		AclEvent::decision(&event.requester, &event.document, &event.scope, &event.result)
+	}
+}
+
+impl fmt::Display for AclEvent {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			AclEvent::Granted { ref requester, ref document, ref scope } =>
+				write!(f, "granted: requester={:?} document={:?} scope={:?}", requester, document, scope),
+			AclEvent::Denied { ref requester, ref document, ref scope } =>
+				write!(f, "denied: requester={:?} document={:?} scope={:?}", requester, document, scope),
+			AclEvent::Failed { ref requester, ref document, ref scope, ref error } =>
+				write!(f, "failed: requester={:?} document={:?} scope={:?} error={}", requester, document, scope, error),
+			AclEvent::ConfigChanged { ref setting, ref value } =>
+				write!(f, "config changed: {}={}", setting, value),
+		}
+	}
+}
+
+impl AclEventSink for LogEventSink {
+	fn emit(&self, event: &AclEvent) {
+		match *event {
+			AclEvent::Granted { .. } => trace!(target: "secretstore", "ACL event: {}", event),
+			AclEvent::Denied { .. } => debug!(target: "secretstore", "ACL event: {}", event),
+			AclEvent::Failed { .. } => warn!(target: "secretstore", "ACL event: {}", event),
+			AclEvent::ConfigChanged { .. } => info!(target: "secretstore", "ACL event: {}", event),
+		}
+	}
+}
+
+impl FileEventSink {
+	/// Open events file at given path. Events are appended to the existing file.
+	pub fn open(path: &str) -> Result<Self, Error> {
+		let file = OpenOptions::new().create(true).append(true).open(path)
+			.map_err(|error| Error::Internal(format!("failed to open ACL events file {}: {}", path, error)))?;
+		Ok(FileEventSink {
+			file: Mutex::new(file),
+		})
+	}
+}
+
+impl AclEventSink for FileEventSink {
+	fn emit(&self, event: &AclEvent) {
+		if let Err(error) = writeln!(self.file.lock(), "{}", event) {
+			warn!(target: "secretstore", "Failed to write ACL event to file: {}", error);
+		}
+	}
+}
+
+impl ChannelEventSink {
+	/// Create new sink, which buffers up to `buffer_size` events, and its receiver.
+	pub fn new(buffer_size: usize) -> (Self, AclEventReceiver) {
+		assert!(buffer_size != 0, "events buffer must be able to hold at least one event");
+
+		let queue = Arc::new(EventsQueue {
+			buffer_size: buffer_size,
+			data: Mutex::new(EventsQueueData {
+				events: VecDeque::new(),
+				is_closed: false,
+			}),
+			available: Condvar::new(),
+			dropped: AtomicUsize::new(0),
+		});
+		(ChannelEventSink { queue: queue.clone() }, AclEventReceiver { queue: queue })
+	}
+}
+
+impl AclEventSink for ChannelEventSink {
+	fn emit(&self, event: &AclEvent) {
+		let mut data = self.queue.data.lock();
+		// receiver is dropped => nobody is interested in events
+		if data.is_closed {
+			return;
+		}
+
+		if data.events.len() == self.queue.buffer_size {
+			data.events.pop_front();
+			self.queue.dropped.fetch_add(1, Ordering::SeqCst);
+		}
+		data.events.push_back(event.clone());
+		self.queue.available.notify_one();
+	}
+}
+
+impl Drop for ChannelEventSink {
+	fn drop(&mut self) {
+		self.queue.data.lock().is_closed = true;
+		self.queue.available.notify_all();
+	}
+}
+
+impl AclEventReceiver {
+	/// Wait for the next event.
+	/// Returns None when the sink is dropped and all buffered events are received.
+	pub fn recv(&self) -> Option<AclEvent> {
+		let mut data = self.queue.data.lock();
+		loop {
+			if let Some(event) = data.events.pop_front() {
+				return Some(event);
+			}
+			if data.is_closed {
+				return None;
+			}
+			self.queue.available.wait(&mut data);
+		}
+	}
+
+	/// Receive the next event if it is already available.
+	pub fn try_recv(&self) -> Option<AclEvent> {
+		self.queue.data.lock().events.pop_front()
+	}
+
+	/// Get number of events, dropped because this receiver was too slow.
+	pub fn dropped_count(&self) -> usize {
+		self.queue.dropped.load(Ordering::SeqCst)
+	}
+}
+
+impl Drop for AclEventReceiver {
+	fn drop(&mut self) {
+		let mut data = self.queue.data.lock();
+		data.is_closed = true;
+		data.events.clear();
+	}
+}
+
+impl AclEventStream {
+	/// Create new event stream without sinks.
+	pub fn new() -> Self {
+		AclEventStream {
+			sinks: RwLock::new(Vec::new()),
+		}
+	}
+
+	/// Register new sink. Sink receives events, emitted after registration.
+	pub fn add_sink(&self, sink: Arc<AclEventSink>) {
+		self.sinks.write().push(sink);
+	}
+
+	/// Deliver event to all sinks.
+	pub fn emit(&self, event: AclEvent) {
+		for sink in self.sinks.read().iter() {
+			sink.emit(&event);
+		}
+	}
+
+	/// Deliver configuration change event to all sinks.
+	pub fn config_changed(&self, setting: &str, value: &str) {
+		self.emit(AclEvent::ConfigChanged {
+			setting: setting.to_owned(),
+			value: value.to_owned(),
+		})
+	}
+}
+
+impl EventStreamAclStorage {
+	/// Create new ACL storage on top of `inner`, emitting decisions to the `stream`.
+	pub fn new(inner: Arc<AclStorage>, stream: Arc<AclEventStream>) -> Self {
+		EventStreamAclStorage {
+			inner: inner,
+			stream: stream,
+		}
+	}
+
+	/// Emit decision event and return the check result.
+	fn emit(&self, requester: &Requester, document: &DocumentAddress, scope: &str, result: Result<bool, Error>) -> Result<bool, Error> {
+		self.stream.emit(AclEvent::decision(requester, document, scope, &result));
+		result
+	}
+}
+
+impl AclStorage for EventStreamAclStorage {
+	fn check(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		let result = self.inner.check(requester, document);
+		self.emit(requester, document, "", result)
+	}
+
+	fn check_with_context(&self, requester: &Requester, document: &DocumentAddress, context: &AclContext) -> Result<bool, Error> {
+		let result = self.inner.check_with_context(requester, document, context);
+		self.emit(requester, document, "", result)
+	}
+
+	fn check_with_block(&self, requester: &Requester, document: &DocumentAddress) -> Result<(bool, Option<DecisionBlock>), Error> {
+		let result = self.inner.check_with_block(requester, document);
+		let block = result.as_ref().ok().and_then(|&(_, ref block)| block.clone());
+		self.emit(requester, document, "", result.map(|(granted, _)| granted)).map(|granted| (granted, block))
+	}
+
+	fn check_with_expiry(&self, requester: &Requester, document: &DocumentAddress) -> Result<CacheableDecision, Error> {
+		let result = self.inner.check_with_expiry(requester, document);
+		self.stream.emit(AclEvent::decision(requester, document, "", &result.as_ref().map(|decision| decision.granted).map_err(Clone::clone)));
+		result
+	}
+
+	fn check_scoped_with_expiry(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<CacheableDecision, Error> {
+		let result = self.inner.check_scoped_with_expiry(requester, document, scope);
+		self.stream.emit(AclEvent::decision(requester, document, scope, &result.as_ref().map(|decision| decision.granted).map_err(Clone::clone)));
+		result
+	}
+
+	fn check_scoped(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<bool, Error> {
+		let result = self.inner.check_scoped(requester, document, scope);
+		self.emit(requester, document, scope, result)
+	}
+
+	fn check_dry_run(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		self.inner.check_dry_run(requester, document)
+	}
+
+	fn check_scoped_dry_run(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<bool, Error> {
+		self.inner.check_scoped_dry_run(requester, document, scope)
+	}
+
+	fn check_and_consume(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		let result = self.inner.check_and_consume(requester, document);
+		self.emit(requester, document, "", result)
+	}
+
+	fn list_requestors(&self, document: &DocumentAddress) -> Result<Vec<Address>, Error> {
+		self.inner.list_requestors(document)
+	}
+
+	fn effective_policy(&self, public: &Public) -> Result<EffectivePolicy, Error> {
+		self.inner.effective_policy(public)
+	}
+
+	fn fill_report(&self, report: &mut AclReport) {
+		self.inner.fill_report(report)
+	}
+}
+
+#[cfg(test)]
+pub mod tests {
+	use std::fs::File;
+	use std::io::Read;
+	use std::sync::Arc;
+	use devtools::RandomTempPath;
+	use ethkey::{Random, Generator};
+	use types::all::{DocumentAddress, Requester};
+	use std::time::Duration;
+	use acl_storage::{AclStorage, AclAuditEvent, AuditedAclStorage, CachedAclStorage};
+	use acl_storage::tests::DummyAclStorage;
+	use acl_storage::clock::tests::MockClock;
+	use super::{AclEvent, AclEventSink, AclEventStream, AclEventReceiver, EventStreamAclStorage, ChannelEventSink, FileEventSink};
+
+	pub fn received_events(receiver: &AclEventReceiver) -> Vec<AclEvent> {
+		let mut events = Vec::new();
+		while let Some(event) = receiver.try_recv() {
+			events.push(event);
+		}
+		events
+	}
+
+	#[test]
+	fn events_are_streamed_in_order() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let stream = Arc::new(AclEventStream::new());
+		let (sink, receiver) = ChannelEventSink::new(16);
+		stream.add_sink(Arc::new(sink));
+		let acl_storage = EventStreamAclStorage::new(inner.clone(), stream.clone());
+		let public = Random.generate().unwrap().public().clone();
+		let requester = Requester::Public(public.clone());
+		inner.prohibit(public, DocumentAddress::from(2));
+
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(2)), Ok(false));
+		stream.config_changed("caching_enabled", "false");
+		assert_eq!(acl_storage.check_dry_run(&requester, &DocumentAddress::from(1)), Ok(true));
+		// dummy storage can't distinguish scopes
+		let scoped_result = acl_storage.check_scoped(&requester, &DocumentAddress::from(1), "decrypt");
+		assert!(scoped_result.is_err());
+
+		let events = received_events(&receiver);
+		assert_eq!(events, vec![
+			AclEvent::Granted { requester: requester.clone(), document: DocumentAddress::from(1), scope: String::new() },
+			AclEvent::Denied { requester: requester.clone(), document: DocumentAddress::from(2), scope: String::new() },
+			AclEvent::ConfigChanged { setting: "caching_enabled".into(), value: "false".into() },
+			AclEvent::Failed { requester: requester.clone(), document: DocumentAddress::from(1), scope: "decrypt".into(), error: scoped_result.unwrap_err() },
+		]);
+
+		// audit events are converted to the same events
+		assert_eq!(AclEvent::from(AclAuditEvent {
+			requester: requester.clone(),
+			document: DocumentAddress::from(2),
+			scope: String::new(),
+			block: None,
+			timestamp: None,
+			result: Ok(false),
+		}), events[1]);
+	}
+
+	#[test]
+	fn oldest_events_are_dropped_when_receiver_is_slow() {
+		let (sink, receiver) = ChannelEventSink::new(2);
+		for value in &["1", "2", "3"] {
+			sink.emit(&AclEvent::ConfigChanged { setting: "caching_enabled".into(), value: value.to_string() });
+		}
+
+		assert_eq!(receiver.dropped_count(), 1);
+		assert_eq!(received_events(&receiver), vec![
+			AclEvent::ConfigChanged { setting: "caching_enabled".into(), value: "2".into() },
+			AclEvent::ConfigChanged { setting: "caching_enabled".into(), value: "3".into() },
+		]);
+
+		// all buffered events are received => sink is closed
+		drop(sink);
+		assert_eq!(receiver.recv(), None);
+	}
+
+	#[test]
+	fn audit_and_config_events_share_stream() {
+		let stream = Arc::new(AclEventStream::new());
+		let (sink, receiver) = ChannelEventSink::new(16);
+		stream.add_sink(Arc::new(sink));
+		let clock = Arc::new(MockClock::default());
+		let cached = Arc::new(CachedAclStorage::with_events(Arc::new(DummyAclStorage::default()), Default::default(),
+			clock.clone(), Some(stream.clone())));
+		let acl_storage = AuditedAclStorage::with_events(cached.clone(), 16, None, clock, Some(stream.clone()));
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+		cached.set_caching_enabled(false);
+		// unchanged setting is not reported
+		cached.set_caching_enabled(false);
+		cached.set_max_staleness(Some(Duration::from_secs(30)));
+		cached.set_max_staleness(None);
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(2)), Ok(true));
+
+		assert_eq!(received_events(&receiver), vec![
+			AclEvent::Granted { requester: requester.clone(), document: DocumentAddress::from(1), scope: String::new() },
+			AclEvent::ConfigChanged { setting: "caching_enabled".into(), value: "false".into() },
+			AclEvent::ConfigChanged { setting: "max_staleness".into(), value: "30s".into() },
+			AclEvent::ConfigChanged { setting: "max_staleness".into(), value: "none".into() },
+			AclEvent::Granted { requester: requester.clone(), document: DocumentAddress::from(2), scope: String::new() },
+		]);
+	}
+
+	#[test]
+	fn events_are_appended_to_file() {
+		let path = RandomTempPath::new();
+		let stream = AclEventStream::new();
+		stream.add_sink(Arc::new(FileEventSink::open(path.as_str()).unwrap()));
+		stream.config_changed("caching_enabled", "false");
+		stream.config_changed("caching_enabled", "true");
+
+		let mut contents = String::new();
+		File::open(path.as_str()).unwrap().read_to_string(&mut contents).unwrap();
+		assert_eq!(contents, "config changed: caching_enabled=false\nconfig changed: caching_enabled=true\n");
+	}
+}
@@ -0,0 +1,302 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use parking_lot::Mutex;
+use types::all::{Error, DocumentAddress, Address, Public, Requester};
+use super::{AclStorage, AclContext, AclReport, DecisionBlock, CacheableDecision, EffectivePolicy, Clock, SystemClock};
+
+/// ACL storage, which denies access when the underlying storage check fails.
+/// Failures are not logged one-by-one: a single summarized warning is logged per interval,
+/// so that logs are readable during backend outage. Failures, which are not yet summarized, are logged
+/// on the next successful check or when `flush_failures` is called (i.e. by timer).
+pub struct FailClosedAclStorage {
+	/// Underlying ACL storage.
+	inner: Arc<AclStorage>,
+	/// Time source.
+	clock: Arc<Clock>,
+	/// Failed checks log.
+	failures: Mutex<FailuresLog>,
+}
+
+/// Failed checks, which are not yet reported.
+struct FailuresLog {
+	/// Min interval between summaries.
+	interval: Duration,
+	/// Time of the last summary.
+	last_reported: Option<Instant>,
+	/// Number of failed checks since the last summary.
+	failed: usize,
+}
+
+#[derive(Debug, PartialEq)]
+/// Summary of failed checks.
+struct FailuresSummary {
+	/// Number of failed checks.
+	failed: usize,
+	/// Time, passed since the previous summary. None if this is the first summary.
+	period: Option<Duration>,
+}
+
+impl FailClosedAclStorage {
+	/// Create new fail-closed ACL storage on top of `inner`, summarizing failures once per `log_interval`.
+	pub fn new(inner: Arc<AclStorage>, log_interval: Duration) -> Self {
+		FailClosedAclStorage::with_clock(inner, log_interval, Arc::new(SystemClock))
+	}
+
+	/// Create new fail-closed ACL storage, which uses given clock to rate-limit warnings.
+	pub fn with_clock(inner: Arc<AclStorage>, log_interval: Duration, clock: Arc<Clock>) -> Self {
+		FailClosedAclStorage {
+			inner: inner,
+			clock: clock,
+			failures: Mutex::new(FailuresLog::new(log_interval)),
+		}
+	}
+
+	/// Log summary of failed checks, which are not yet reported.
+	pub fn flush_failures(&self) {
+		if let Some(summary) = self.failures.lock().flush(self.clock.now()) {
+			warn!(target: "secretstore", "{} ACL checks failed closed due to ACL storage error in the last {}s",
+				summary.failed, summary.period.map(|period| period.as_secs()).unwrap_or(0));
+		}
+	}
+
+	/// Deny access if check has failed.
+	fn fail_closed(&self, result: Result<bool, Error>) -> Result<bool, Error> {
+		match result {
+			Ok(granted) => {
+				if let Some(summary) = self.failures.lock().flush(self.clock.now()) {
+					warn!(target: "secretstore", "{} ACL checks failed closed due to ACL storage error in the last {}s before ACL storage has recovered",
+						summary.failed, summary.period.map(|period| period.as_secs()).unwrap_or(0));
+				}
+				Ok(granted)
+			},
+			Err(error) => {
+				trace!(target: "secretstore", "ACL check has failed closed: {}", error);
+				if let Some(summary) = self.failures.lock().record(self.clock.now()) {
+					match summary.period {
+						Some(period) => warn!(target: "secretstore", "{} ACL checks failed closed due to ACL storage error in the last {}s. Last error: {}",
+							summary.failed, period.as_secs(), error),
+						None => warn!(target: "secretstore", "ACL check failed closed due to ACL storage error: {}", error),
+					}
+				}
+				Ok(false)
+			},
+		}
+	}
+
+	/// Deny access if check has failed. Denial expires immediately, so that it is not cached by the caller.
+	fn fail_closed_with_expiry(&self, result: Result<CacheableDecision, Error>) -> Result<CacheableDecision, Error> {
+		match result {
+			Ok(decision) => self.fail_closed(Ok(decision.granted)).map(|_| decision),
+			Err(error) => self.fail_closed(Err(error)).map(|granted| CacheableDecision {
+				granted: granted,
+				not_after: Some(self.clock.now()),
+				block: None,
+			}),
+		}
+	}
+}
+
+impl FailuresLog {
+	fn new(interval: Duration) -> Self {
+		FailuresLog {
+			interval: interval,
+			last_reported: None,
+			failed: 0,
+		}
+	}
+
+	/// Record failed check. Returns summary, if it must be reported now. The first failure is reported
+	/// immediately, subsequent failures are aggregated until the interval passes.
+	fn record(&mut self, now: Instant) -> Option<FailuresSummary> {
+		self.failed += 1;
+		let period = match self.last_reported {
+			Some(last_reported) if now < last_reported + self.interval => return None,
+			Some(last_reported) => Some(now - last_reported),
+			None => None,
+		};
+
+		let summary = FailuresSummary {
+			failed: self.failed,
+			period: period,
+		};
+		self.last_reported = Some(now);
+		self.failed = 0;
+		Some(summary)
+	}
+
+	/// Get summary of failures, which are not yet reported, regardless of the interval.
+	/// Returns None if there are no such failures.
+	fn flush(&mut self, now: Instant) -> Option<FailuresSummary> {
+		if self.failed == 0 {
+			return None;
+		}
+
+		let summary = FailuresSummary {
+			failed: self.failed,
+			period: self.last_reported.map(|last_reported| now - last_reported),
+		};
+		self.last_reported = Some(now);
+		self.failed = 0;
+		Some(summary)
+	}
+}
+
+impl AclStorage for FailClosedAclStorage {
+	fn check(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		self.fail_closed(self.inner.check(requester, document))
+	}
+
+	fn check_with_context(&self, requester: &Requester, document: &DocumentAddress, context: &AclContext) -> Result<bool, Error> {
+		self.fail_closed(self.inner.check_with_context(requester, document, context))
+	}
+
+	fn check_with_block(&self, requester: &Requester, document: &DocumentAddress) -> Result<(bool, Option<DecisionBlock>), Error> {
+		match self.inner.check_with_block(requester, document) {
+			Ok((granted, block)) => self.fail_closed(Ok(granted)).map(|granted| (granted, block)),
+			Err(error) => self.fail_closed(Err(error)).map(|granted| (granted, None)),
+		}
+	}
+
+	/// Denials, made because the check has failed, expire immediately, so that they are not cached by the caller.
+	fn check_with_expiry(&self, requester: &Requester, document: &DocumentAddress) -> Result<CacheableDecision, Error> {
+		self.fail_closed_with_expiry(self.inner.check_with_expiry(requester, document))
+	}
+
+	fn check_scoped_with_expiry(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<CacheableDecision, Error> {
+		self.fail_closed_with_expiry(self.inner.check_scoped_with_expiry(requester, document, scope))
+	}
+
+	fn check_scoped(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<bool, Error> {
+		self.fail_closed(self.inner.check_scoped(requester, document, scope))
+	}
+
+	fn check_dry_run(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		self.fail_closed(self.inner.check_dry_run(requester, document))
+	}
+
+	fn check_scoped_dry_run(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<bool, Error> {
+		self.fail_closed(self.inner.check_scoped_dry_run(requester, document, scope))
+	}
+
+	fn check_and_consume(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		self.fail_closed(self.inner.check_and_consume(requester, document))
+	}
+
+	fn list_requestors(&self, document: &DocumentAddress) -> Result<Vec<Address>, Error> {
+		self.inner.list_requestors(document)
+	}
+
+	fn effective_policy(&self, public: &Public) -> Result<EffectivePolicy, Error> {
+		self.inner.effective_policy(public)
+	}
+
+	fn fill_report(&self, report: &mut AclReport) {
+		self.inner.fill_report(report)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::time::Duration;
+	use ethkey::{Random, Generator};
+	use util::Address;
+	use types::all::{DocumentAddress, Requester};
+	use acl_storage::{AclStorage, OnChainAclStorage, Clock};
+	use acl_storage::tests::{DummyAclClient, encode_bool};
+	use acl_storage::clock::tests::MockClock;
+	use super::{FailClosedAclStorage, FailuresLog, FailuresSummary};
+
+	#[test]
+	fn failed_check_is_denied() {
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(1))));
+		*client.call_result.lock() = Err("call failed".into());
+		let acl_storage = FailClosedAclStorage::new(Arc::new(OnChainAclStorage::new(client.clone())), Duration::from_secs(60));
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(false));
+		*client.call_result.lock() = Ok(encode_bool(true));
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+	}
+
+	#[test]
+	fn failures_are_summarized_once_per_interval() {
+		let clock = MockClock::default();
+		let mut log = FailuresLog::new(Duration::from_secs(60));
+
+		// first failure is reported immediately
+		assert_eq!(log.record(clock.now()), Some(FailuresSummary { failed: 1, period: None }));
+
+		// then failures are aggregated
+		for _ in 0..10 {
+			clock.advance(Duration::from_secs(5));
+			assert_eq!(log.record(clock.now()), None);
+		}
+		clock.advance(Duration::from_secs(10));
+		assert_eq!(log.record(clock.now()), Some(FailuresSummary { failed: 11, period: Some(Duration::from_secs(60)) }));
+
+		clock.advance(Duration::from_secs(59));
+		assert_eq!(log.record(clock.now()), None);
+		clock.advance(Duration::from_secs(120));
+		assert_eq!(log.record(clock.now()), Some(FailuresSummary { failed: 2, period: Some(Duration::from_secs(179)) }));
+	}
+
+	#[test]
+	fn trailing_failures_are_flushed() {
+		let clock = MockClock::default();
+		let mut log = FailuresLog::new(Duration::from_secs(60));
+		assert_eq!(log.flush(clock.now()), None);
+
+		assert_eq!(log.record(clock.now()), Some(FailuresSummary { failed: 1, period: None }));
+		clock.advance(Duration::from_secs(5));
+		assert_eq!(log.record(clock.now()), None);
+		clock.advance(Duration::from_secs(5));
+		assert_eq!(log.record(clock.now()), None);
+
+		// trailing failures are reported when flushed, even if the interval has not passed
+		clock.advance(Duration::from_secs(5));
+		assert_eq!(log.flush(clock.now()), Some(FailuresSummary { failed: 2, period: Some(Duration::from_secs(15)) }));
+		assert_eq!(log.flush(clock.now()), None);
+	}
+
+	#[test]
+	fn trailing_failures_are_flushed_on_successful_check() {
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(1))));
+		*client.call_result.lock() = Err("call failed".into());
+		let clock = Arc::new(MockClock::default());
+		let acl_storage = FailClosedAclStorage::with_clock(Arc::new(OnChainAclStorage::new(client.clone())), Duration::from_secs(60), clock.clone());
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+
+		for _ in 0..3 {
+			assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(false));
+		}
+		assert_eq!(acl_storage.failures.lock().failed, 2);
+
+		*client.call_result.lock() = Ok(encode_bool(true));
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+		assert_eq!(acl_storage.failures.lock().failed, 0);
+
+		// flushing without pending failures does nothing
+		*client.call_result.lock() = Err("call failed".into());
+		clock.advance(Duration::from_secs(120));
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(false));
+		acl_storage.flush_failures();
+		assert_eq!(acl_storage.failures.lock().failed, 0);
+	}
+}
@@ -0,0 +1,206 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use types::all::{Error, AclError, DocumentAddress, Address, Public, Requester};
+use super::{AclStorage, AclContext, AclReport, CacheableDecision, DecisionBlock, EffectivePolicy};
+
+/// Default max number of outstanding ACL checks of the node.
+const DEFAULT_MAX_OUTSTANDING_CHECKS: usize = 1024;
+
+/// Node-wide admission control of ACL work. Governor is shared by every governed ACL storage layer.
+/// When max number of outstanding checks is reached, new checks are failing with `AclError::Overloaded`.
+pub struct AclGovernor {
+	/// Max number of outstanding checks.
+	max_outstanding: usize,
+	/// Number of outstanding checks.
+	outstanding: AtomicUsize,
+}
+
+/// Outstanding ACL work. Work is deregistered when dropped.
+pub struct AclWork<'a> {
+	/// Governor, tracking the work.
+	governor: &'a AclGovernor,
+}
+
+/// ACL storage, which registers every check of the underlying storage with the governor.
+/// Wrap every backend once: nested layers, sharing the same governor, count the same check twice.
+pub struct GovernedAclStorage {
+	/// Underlying ACL storage.
+	inner: Arc<AclStorage>,
+	/// Node-wide governor.
+	governor: Arc<AclGovernor>,
+}
+
+impl AclGovernor {
+	/// Create new governor, allowing at most `max_outstanding` checks at once.
+	pub fn new(max_outstanding: usize) -> Self {
+		AclGovernor {
+			max_outstanding: max_outstanding,
+			outstanding: AtomicUsize::new(0),
+		}
+	}
+
+	/// Register new work. Returns `AclError::Overloaded` if max number of outstanding checks is reached.
+	pub fn begin(&self) -> Result<AclWork, Error> {
+		if self.outstanding.fetch_add(1, Ordering::SeqCst) >= self.max_outstanding {
+			self.outstanding.fetch_sub(1, Ordering::SeqCst);
+			trace!(target: "secretstore", "ACL check is rejected: {} checks are outstanding", self.max_outstanding);
+			return Err(AclError::Overloaded.into());
+		}
+
+		Ok(AclWork {
+			governor: self,
+		})
+	}
+
+	/// Get number of outstanding checks.
+	pub fn load(&self) -> usize {
+		self.outstanding.load(Ordering::SeqCst)
+	}
+
+	/// Get max number of outstanding checks.
+	pub fn max_outstanding(&self) -> usize {
+		self.max_outstanding
+	}
+}
+
+impl Default for AclGovernor {
+	fn default() -> Self {
+		AclGovernor::new(DEFAULT_MAX_OUTSTANDING_CHECKS)
+	}
+}
+
+impl<'a> Drop for AclWork<'a> {
+	fn drop(&mut self) {
+		self.governor.outstanding.fetch_sub(1, Ordering::SeqCst);
+	}
+}
+
+impl GovernedAclStorage {
+	/// Create new governed ACL storage on top of `inner`.
+	pub fn new(inner: Arc<AclStorage>, governor: Arc<AclGovernor>) -> Self {
+		GovernedAclStorage {
+			inner: inner,
+			governor: governor,
+		}
+	}
+}
+
+impl AclStorage for GovernedAclStorage {
+	fn check(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		let _work = self.governor.begin()?;
+		self.inner.check(requester, document)
+	}
+
+	fn check_with_context(&self, requester: &Requester, document: &DocumentAddress, context: &AclContext) -> Result<bool, Error> {
+		let _work = self.governor.begin()?;
+		self.inner.check_with_context(requester, document, context)
+	}
+
+	fn check_scoped(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<bool, Error> {
+		let _work = self.governor.begin()?;
+		self.inner.check_scoped(requester, document, scope)
+	}
+
+	fn check_with_block(&self, requester: &Requester, document: &DocumentAddress) -> Result<(bool, Option<DecisionBlock>), Error> {
+		let _work = self.governor.begin()?;
+		self.inner.check_with_block(requester, document)
+	}
+
+	fn check_with_expiry(&self, requester: &Requester, document: &DocumentAddress) -> Result<CacheableDecision, Error> {
+		let _work = self.governor.begin()?;
+		self.inner.check_with_expiry(requester, document)
+	}
+
+	fn check_scoped_with_expiry(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<CacheableDecision, Error> {
+		let _work = self.governor.begin()?;
+		self.inner.check_scoped_with_expiry(requester, document, scope)
+	}
+
+	fn check_dry_run(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		let _work = self.governor.begin()?;
+		self.inner.check_dry_run(requester, document)
+	}
+
+	fn check_scoped_dry_run(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<bool, Error> {
+		let _work = self.governor.begin()?;
+		self.inner.check_scoped_dry_run(requester, document, scope)
+	}
+
+	fn check_and_consume(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		let _work = self.governor.begin()?;
+		self.inner.check_and_consume(requester, document)
+	}
+
+	fn list_requestors(&self, document: &DocumentAddress) -> Result<Vec<Address>, Error> {
+		let _work = self.governor.begin()?;
+		self.inner.list_requestors(document)
+	}
+
+	fn effective_policy(&self, public: &Public) -> Result<EffectivePolicy, Error> {
+		let _work = self.governor.begin()?;
+		self.inner.effective_policy(public)
+	}
+
+	fn fill_report(&self, report: &mut AclReport) {
+		report.outstanding_checks = Some(self.governor.load());
+		self.inner.fill_report(report)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use ethkey::{Random, Generator};
+	use types::all::{Error, AclError, DocumentAddress, Requester};
+	use acl_storage::AclStorage;
+	use acl_storage::tests::DummyAclStorage;
+	use super::{AclGovernor, GovernedAclStorage};
+
+	#[test]
+	fn checks_are_rejected_at_ceiling() {
+		let governor = Arc::new(AclGovernor::new(2));
+		let first = GovernedAclStorage::new(Arc::new(DummyAclStorage::default()), governor.clone());
+		let second = GovernedAclStorage::new(Arc::new(DummyAclStorage::default()), governor.clone());
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+		assert_eq!(first.check(&requester, &DocumentAddress::from(1)), Ok(true));
+		assert_eq!(governor.load(), 0);
+
+		// outstanding work of other checks occupies the governor
+		let work1 = governor.begin().unwrap();
+		let _work2 = governor.begin().unwrap();
+		assert_eq!(governor.load(), 2);
+		assert_eq!(first.check(&requester, &DocumentAddress::from(1)), Err(Error::Acl(AclError::Overloaded)));
+		assert_eq!(second.check(&requester, &DocumentAddress::from(1)), Err(Error::Acl(AclError::Overloaded)));
+		assert_eq!(first.report().outstanding_checks, Some(2));
+
+		// load is below the ceiling again
+		drop(work1);
+		assert_eq!(second.check(&requester, &DocumentAddress::from(1)), Ok(true));
+		assert_eq!(governor.load(), 1);
+	}
+
+	#[test]
+	fn failed_check_deregisters_work() {
+		let governor = Arc::new(AclGovernor::new(1));
+		let acl_storage = GovernedAclStorage::new(Arc::new(DummyAclStorage::default()), governor.clone());
+		// dummy storage does not support single-use documents
+		assert!(acl_storage.check_and_consume(&Requester::Public(Random.generate().unwrap().public().clone()), &DocumentAddress::from(1)).is_err());
+		assert_eq!(governor.load(), 0);
+	}
+}
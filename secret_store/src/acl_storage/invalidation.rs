@@ -0,0 +1,158 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+use parking_lot::Mutex;
+use ethcore::header::BlockNumber;
+use util::H256;
+use types::all::DocumentAddress;
+use super::CachedAclStorage;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Permissions change, emitted by the ACL checker contract.
+pub struct PermissionChange {
+	/// Number of the block, containing the change.
+	pub block_number: BlockNumber,
+	/// Hash of the block, containing the change.
+	pub block_hash: H256,
+	/// Document, permissions of which have changed.
+	pub document: DocumentAddress,
+}
+
+/// Invalidates cached decisions when permissions of the document change.
+/// Change takes effect only after configured number of confirmations, so that cache is not invalidated
+/// because of the transaction, which is then reorged out. Changes from retracted blocks are dropped:
+/// cached decisions are still valid then. Changes, retracted after they have been applied, only cause
+/// extra checks of the underlying storage.
+pub struct PermissionChangeInvalidator {
+	/// Cache to invalidate.
+	cache: Arc<CachedAclStorage>,
+	/// Number of confirmations, required to apply the change. Zero means changes are applied immediately.
+	confirmations: BlockNumber,
+	/// Changes, waiting for confirmations.
+	pending: Mutex<Vec<PermissionChange>>,
+}
+
+impl PermissionChangeInvalidator {
+	/// Create new invalidator of given cache.
+	pub fn new(cache: Arc<CachedAclStorage>, confirmations: BlockNumber) -> Self {
+		PermissionChangeInvalidator {
+			cache: cache,
+			confirmations: confirmations,
+			pending: Mutex::new(Vec::new()),
+		}
+	}
+
+	/// Get number of changes, waiting for confirmations.
+	pub fn pending_count(&self) -> usize {
+		self.pending.lock().len()
+	}
+
+	/// Process permissions change, seen in the block with given number.
+	pub fn on_permission_changed(&self, change: PermissionChange, best_block_number: BlockNumber) {
+		self.pending.lock().push(change);
+		self.on_new_best_block(best_block_number, &[]);
+	}
+
+	/// Process new best block: drop changes from `retracted` blocks and apply confirmed changes.
+	pub fn on_new_best_block(&self, best_block_number: BlockNumber, retracted: &[H256]) {
+		let mut pending = self.pending.lock();
+		let confirmations = self.confirmations;
+		let (confirmed, unconfirmed): (Vec<_>, Vec<_>) = pending.drain(..)
+			.filter(|change| if retracted.contains(&change.block_hash) {
+				trace!(target: "secretstore", "Permissions change of document {:?} from block {} is retracted", change.document, change.block_number);
+				false
+			} else {
+				true
+			})
+			.partition(|change| change.block_number + confirmations <= best_block_number);
+		*pending = unconfirmed;
+
+		for change in confirmed {
+			trace!(target: "secretstore", "Invalidating cached decisions for document {:?} after permissions change in block {}",
+				change.document, change.block_number);
+			self.cache.invalidate_document(&change.document);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::time::Duration;
+	use util::{Address, H256};
+	use types::all::{DocumentAddress, Requester};
+	use acl_storage::{AclStorage, CachedAclStorage};
+	use acl_storage::tests::DummyAclStorage;
+	use super::{PermissionChange, PermissionChangeInvalidator};
+
+	fn change(block_number: u64, document: u64) -> PermissionChange {
+		PermissionChange {
+			block_number: block_number,
+			block_hash: H256::from(block_number),
+			document: DocumentAddress::from(document),
+		}
+	}
+
+	fn cached_storage() -> Arc<CachedAclStorage> {
+		let cache = Arc::new(CachedAclStorage::new(Arc::new(DummyAclStorage::default()), Duration::from_secs(60)));
+		for document in 1..3 {
+			cache.check(&Requester::Address(Address::from(1)), &DocumentAddress::from(document)).unwrap();
+		}
+		cache
+	}
+
+	#[test]
+	fn change_is_applied_after_confirmations() {
+		let cache = cached_storage();
+		let invalidator = PermissionChangeInvalidator::new(cache.clone(), 2);
+
+		invalidator.on_permission_changed(change(10, 1), 10);
+		invalidator.on_new_best_block(11, &[]);
+		assert_eq!(cache.len(), 2);
+		assert_eq!(invalidator.pending_count(), 1);
+
+		invalidator.on_new_best_block(12, &[]);
+		assert_eq!(cache.len(), 1);
+		assert_eq!(invalidator.pending_count(), 0);
+	}
+
+	#[test]
+	fn change_from_retracted_block_is_dropped() {
+		let cache = cached_storage();
+		let invalidator = PermissionChangeInvalidator::new(cache.clone(), 2);
+
+		invalidator.on_permission_changed(change(10, 1), 10);
+		invalidator.on_permission_changed(change(11, 2), 11);
+		// block 11 is reorged out
+		invalidator.on_new_best_block(11, &[H256::from(11)]);
+		assert_eq!(invalidator.pending_count(), 1);
+
+		invalidator.on_new_best_block(13, &[]);
+		assert_eq!(cache.len(), 1);
+		assert_eq!(cache.check(&Requester::Address(Address::from(1)), &DocumentAddress::from(2)), Ok(true));
+		assert_eq!(cache.len(), 1);
+	}
+
+	#[test]
+	fn change_is_applied_immediately_without_confirmations() {
+		let cache = cached_storage();
+		let invalidator = PermissionChangeInvalidator::new(cache.clone(), 0);
+
+		invalidator.on_permission_changed(change(10, 1), 10);
+		assert_eq!(cache.len(), 1);
+	}
+}
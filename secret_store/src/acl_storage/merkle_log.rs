@@ -0,0 +1,597 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
+use parking_lot::RwLock;
+use util::{Database, H256, Hashable};
+use util::sha3::SHA3_EMPTY;
+use types::all::{Error, Requester};
+use super::AclAuditEvent;
+
+/// Prefix of the leaf hash preimage.
+const LEAF_PREFIX: u8 = 0;
+/// Prefix of the interior node hash preimage.
+const NODE_PREFIX: u8 = 1;
+/// Prefix of the scoped decision leaf hash preimage.
+const SCOPED_LEAF_PREFIX: u8 = 2;
+/// Database key of the number of records.
+const RECORDS_COUNT_KEY: &'static [u8] = b"records_count";
+
+#[derive(Debug, Clone, PartialEq)]
+/// Proof of the decision record inclusion into the audit log.
+pub struct MerkleProof {
+	/// Index of the record in the log.
+	pub index: usize,
+	/// Number of records in the log, when proof has been made.
+	pub records_count: usize,
+	/// Hashes of sibling subtrees, from the leaf to the root.
+	pub path: Vec<H256>,
+}
+
+/// Append-only log of ACL decisions, which is organized as a Merkle tree, so that it is tamper-evident.
+/// Tree is built the same way as in RFC 6962: leaves and interior nodes hashes are domain-separated and
+/// unbalanced trees are split at the largest power of two. Roots of all complete power-of-two subtrees are
+/// kept (and persisted, when log is opened from the database), so that records are appended and the
+/// root is computed in O(log n) and only O(log n) hashes are loaded when the log is opened.
+pub struct MerkleAuditLog {
+	/// Database to persist tree nodes to. None if log is kept in memory.
+	db: Option<Database>,
+	/// Current state of the tree.
+	tree: RwLock<CompactTree>,
+}
+
+/// Compact representation of the tree: roots of the complete subtrees, covering all records.
+struct CompactTree {
+	/// Number of records.
+	len: usize,
+	/// Roots of complete subtrees, covering all records, from the leftmost (largest) one, with their heights.
+	peaks: Vec<(u32, H256)>,
+	/// Roots of all complete subtrees by height, when log is kept in memory. Persistent log reads them from the database.
+	nodes: Vec<Vec<H256>>,
+}
+
+impl MerkleAuditLog {
+	/// Create new in-memory audit log.
+	pub fn in_memory() -> Self {
+		MerkleAuditLog {
+			db: None,
+			tree: RwLock::new(CompactTree::default()),
+		}
+	}
+
+	/// Open persistent audit log at given path. Logs, which only have records hashes persisted,
+	/// are upgraded when opened for the first time.
+	pub fn open(path: &str) -> Result<Self, Error> {
+		let db = Database::open_default(path).map_err(Error::Database)?;
+		let len = match db.get(None, RECORDS_COUNT_KEY).map_err(Error::Database)? {
+			Some(len) => {
+				if len.len() != 8 {
+					return Err(Error::Database("invalid audit log records count".to_owned()));
+				}
+				BigEndian::read_u64(&len) as usize
+			},
+			None => return MerkleAuditLog::upgrade(db),
+		};
+
+		// every set bit of the records count is a complete subtree
+		let mut peaks = Vec::new();
+		let mut offset = 0;
+		for height in (0..64u32).rev() {
+			let size = 1usize.checked_shl(height).unwrap_or(0);
+			if size != 0 && len & size != 0 {
+				peaks.push((height, read_node(&db, height, offset >> height)?));
+				offset += size;
+			}
+		}
+
+		Ok(MerkleAuditLog {
+			db: Some(db),
+			tree: RwLock::new(CompactTree {
+				len: len,
+				peaks: peaks,
+				nodes: Vec::new(),
+			}),
+		})
+	}
+
+	/// Get number of records in the log.
+	pub fn len(&self) -> usize {
+		self.tree.read().len
+	}
+
+	/// Append decision record to the log. Returns index of the record.
+	pub fn append(&self, event: &AclAuditEvent) -> Result<usize, Error> {
+		let mut tree = self.tree.write();
+		let index = tree.len;
+		let (peaks, nodes) = tree.appended(audit_leaf(event));
+		if let Some(ref db) = self.db {
+			let mut batch = db.transaction();
+			for &(height, node_index, ref node) in &nodes {
+				batch.put(None, &node_key(height, node_index), &**node);
+			}
+			let mut len = Vec::with_capacity(8);
+			len.write_u64::<BigEndian>(index as u64 + 1).expect("writing to vec never fails; qed");
+			batch.put(None, RECORDS_COUNT_KEY, &len);
+			db.write(batch).map_err(Error::Database)?;
+		} else {
+			for (height, _, node) in nodes {
+				if tree.nodes.len() == height as usize {
+					tree.nodes.push(Vec::new());
+				}
+				tree.nodes[height as usize].push(node);
+			}
+		}
+
+		tree.len = index + 1;
+		tree.peaks = peaks;
+		Ok(index)
+	}
+
+	/// Get root hash of the log.
+	pub fn audit_root(&self) -> H256 {
+		self.tree.read().root()
+	}
+
+	/// Get proof of inclusion of the record with given index into the current log.
+	pub fn audit_proof(&self, index: usize) -> Result<MerkleProof, Error> {
+		let tree = self.tree.read();
+		if index >= tree.len {
+			return Err(Error::Internal(format!("audit log record {} does not exist", index)));
+		}
+
+		let mut path = Vec::new();
+		self.subtree_path(&tree, index, 0, tree.len, &mut path)?;
+		Ok(MerkleProof {
+			index: index,
+			records_count: tree.len,
+			path: path,
+		})
+	}
+
+	/// Upgrade log, which only has records hashes persisted: persist all complete subtrees and number of records.
+	fn upgrade(db: Database) -> Result<Self, Error> {
+		let mut tree = CompactTree::default();
+		let mut batch = db.transaction();
+		loop {
+			let leaf = match db.get(None, &node_key(0, tree.len)).map_err(Error::Database)? {
+				Some(leaf) => leaf,
+				None => break,
+			};
+			if leaf.len() != 32 {
+				return Err(Error::Database(format!("invalid audit log record {}", tree.len)));
+			}
+
+			let (peaks, nodes) = tree.appended(H256::from_slice(&leaf));
+			for (height, node_index, node) in nodes.into_iter().filter(|&(height, _, _)| height != 0) {
+				batch.put(None, &node_key(height, node_index), &*node);
+			}
+			tree.len += 1;
+			tree.peaks = peaks;
+		}
+
+		if tree.len != 0 {
+			info!(target: "secretstore", "Audit log of {} records is upgraded", tree.len);
+		}
+		let mut len = Vec::with_capacity(8);
+		len.write_u64::<BigEndian>(tree.len as u64).expect("writing to vec never fails; qed");
+		batch.put(None, RECORDS_COUNT_KEY, &len);
+		db.write(batch).map_err(Error::Database)?;
+
+		Ok(MerkleAuditLog {
+			db: Some(db),
+			tree: RwLock::new(tree),
+		})
+	}
+
+	/// Get root of the complete subtree with given height and index.
+	fn node(&self, tree: &CompactTree, height: u32, index: usize) -> Result<H256, Error> {
+		match self.db {
+			Some(ref db) => read_node(db, height, index),
+			None => tree.nodes.get(height as usize)
+				.and_then(|nodes| nodes.get(index))
+				.cloned()
+				.ok_or_else(|| Error::Internal(format!("audit log node {}/{} does not exist", height, index))),
+		}
+	}
+
+	/// Compute root hash of the subtree with `len` leaves, starting from leaf `start`.
+	fn subtree_root(&self, tree: &CompactTree, start: usize, len: usize) -> Result<H256, Error> {
+		if len.is_power_of_two() {
+			let height = len.trailing_zeros();
+			return self.node(tree, height, start >> height);
+		}
+
+		let split = split_point(len);
+		Ok(node_hash(&self.subtree_root(tree, start, split)?, &self.subtree_root(tree, start + split, len - split)?))
+	}
+
+	/// Push hashes of siblings of the leaf with given index to the `path`, from the leaf to the root.
+	fn subtree_path(&self, tree: &CompactTree, index: usize, start: usize, len: usize, path: &mut Vec<H256>) -> Result<(), Error> {
+		if len < 2 {
+			return Ok(());
+		}
+
+		let split = split_point(len);
+		if index < start + split {
+			self.subtree_path(tree, index, start, split, path)?;
+			path.push(self.subtree_root(tree, start + split, len - split)?);
+		} else {
+			self.subtree_path(tree, index, start + split, len - split, path)?;
+			path.push(self.subtree_root(tree, start, split)?);
+		}
+		Ok(())
+	}
+}
+
+impl Default for CompactTree {
+	fn default() -> Self {
+		CompactTree {
+			len: 0,
+			peaks: Vec::new(),
+			nodes: Vec::new(),
+		}
+	}
+}
+
+impl CompactTree {
+	/// Get peaks after the leaf is appended and all complete subtrees, created by the leaf (including the leaf itself),
+	/// as (height, index, root). The tree itself is not changed.
+	fn appended(&self, leaf: H256) -> (Vec<(u32, H256)>, Vec<(u32, usize, H256)>) {
+		let mut peaks = self.peaks.clone();
+		let mut nodes = vec![(0, self.len, leaf.clone())];
+		peaks.push((0, leaf));
+		while peaks.len() > 1 && peaks[peaks.len() - 1].0 == peaks[peaks.len() - 2].0 {
+			let (height, right) = peaks.pop().expect("len > 1; qed");
+			let (_, left) = peaks.pop().expect("len > 1; qed");
+			let node = node_hash(&left, &right);
+			nodes.push((height + 1, self.len >> (height + 1), node.clone()));
+			peaks.push((height + 1, node));
+		}
+		(peaks, nodes)
+	}
+
+	/// Compute root hash of the tree: peaks are folded from the right, which is the same as splitting the tree
+	/// at the largest power of two.
+	fn root(&self) -> H256 {
+		let mut peaks = self.peaks.iter().rev();
+		let mut root = match peaks.next() {
+			Some(&(_, ref peak)) => peak.clone(),
+			None => return SHA3_EMPTY,
+		};
+		for &(_, ref peak) in peaks {
+			root = node_hash(peak, &root);
+		}
+		root
+	}
+}
+
+impl MerkleProof {
+	/// Check that the record with given leaf hash is included into the log with given root.
+	pub fn verify(&self, leaf: &H256, root: &H256) -> bool {
+		if self.index >= self.records_count {
+			return false;
+		}
+
+		let mut node_index = self.index;
+		let mut last_index = self.records_count - 1;
+		let mut hash = leaf.clone();
+		for sibling in &self.path {
+			if last_index == 0 {
+				return false;
+			}
+
+			if node_index % 2 == 1 || node_index == last_index {
+				hash = node_hash(sibling, &hash);
+				// node is the rightmost in its level and has no sibling => it is promoted to upper level
+				while node_index % 2 == 0 && node_index != 0 {
+					node_index >>= 1;
+					last_index >>= 1;
+				}
+			} else {
+				hash = node_hash(&hash, sibling);
+			}
+
+			node_index >>= 1;
+			last_index >>= 1;
+		}
+
+		last_index == 0 && hash == *root
+	}
+}
+
+/// Get hash of the log leaf, representing given decision record.
+/// Records of scoped checks have their own prefix and scope is appended after the document, prefixed with its length.
+/// Block and time of the decision follow, each prefixed with the flag of its presence.
+pub fn audit_leaf(event: &AclAuditEvent) -> H256 {
+	let mut data = vec![if event.scope.is_empty() { LEAF_PREFIX } else { SCOPED_LEAF_PREFIX }];
+	match event.requester {
+		Requester::Public(ref public) => {
+			data.push(0);
+			data.extend_from_slice(&**public);
+		},
+		Requester::Address(ref address) => {
+			data.push(1);
+			data.extend_from_slice(&**address);
+		},
+		Requester::Signature(ref signature) => {
+			data.push(2);
+			data.extend_from_slice(&**signature);
+		},
+	}
+	data.extend_from_slice(&*event.document);
+	if !event.scope.is_empty() {
+		data.write_u32::<BigEndian>(event.scope.len() as u32).expect("writing to vec never fails; qed");
+		data.extend_from_slice(event.scope.as_bytes());
+	}
+	match event.block {
+		Some(ref block) => {
+			data.push(1);
+			data.write_u64::<BigEndian>(block.number).expect("writing to vec never fails; qed");
+			data.extend_from_slice(&*block.hash);
+		},
+		None => data.push(0),
+	}
+	match event.timestamp {
+		Some(timestamp) => {
+			data.push(1);
+			data.write_u64::<BigEndian>(timestamp).expect("writing to vec never fails; qed");
+		},
+		None => data.push(0),
+	}
+	match event.result {
+		Ok(false) => data.push(0),
+		Ok(true) => data.push(1),
+		Err(ref error) => {
+			data.push(2);
+			data.extend_from_slice(error.to_string().as_bytes());
+		},
+	}
+	data.sha3()
+}
+
+/// Database key of the root of complete subtree with given height and index.
+/// Leaves keys are the records indices, so that logs of previous versions could be upgraded.
+fn node_key(height: u32, index: usize) -> Vec<u8> {
+	let mut key = Vec::with_capacity(9);
+	if height != 0 {
+		key.push(height as u8);
+	}
+	key.write_u64::<BigEndian>(index as u64).expect("writing to vec never fails; qed");
+	key
+}
+
+/// Read root of complete subtree with given height and index from the database.
+fn read_node(db: &Database, height: u32, index: usize) -> Result<H256, Error> {
+	match db.get(None, &node_key(height, index)).map_err(Error::Database)? {
+		Some(ref node) if node.len() == 32 => Ok(H256::from_slice(node)),
+		_ => Err(Error::Database(format!("audit log node {}/{} is missing", height, index))),
+	}
+}
+
+/// Get hash of the interior node.
+fn node_hash(left: &H256, right: &H256) -> H256 {
+	let mut data = Vec::with_capacity(1 + 32 + 32);
+	data.push(NODE_PREFIX);
+	data.extend_from_slice(&**left);
+	data.extend_from_slice(&**right);
+	data.sha3()
+}
+
+/// Get size of the left subtree of the tree with given number of leaves: the largest power of two, less than `len`.
+fn split_point(len: usize) -> usize {
+	let mut split = 1;
+	while split << 1 < len {
+		split <<= 1;
+	}
+	split
+}
+
+#[cfg(test)]
+mod tests {
+	use ethkey::{Random, Generator};
+	use devtools::RandomTempPath;
+	use util::{Address, Database, H256};
+	use util::sha3::SHA3_EMPTY;
+	use types::all::{Error, DocumentAddress, Requester};
+	use acl_storage::AclAuditEvent;
+	use super::{MerkleAuditLog, audit_leaf, node_key, node_hash, split_point};
+
+	fn event(document: u64) -> AclAuditEvent {
+		AclAuditEvent {
+			requester: Requester::Address(Address::from(1)),
+			document: DocumentAddress::from(document),
+			scope: String::new(),
+			block: None,
+			timestamp: None,
+			result: if document % 3 == 0 { Err(Error::Internal("failed".into())) } else { Ok(document % 2 == 0) },
+		}
+	}
+
+	/// Compute root hash of the tree with given leaves, as defined in RFC 6962.
+	fn reference_root(leaves: &[H256]) -> H256 {
+		match leaves.len() {
+			0 => SHA3_EMPTY,
+			1 => leaves[0].clone(),
+			len => {
+				let split = split_point(len);
+				node_hash(&reference_root(&leaves[..split]), &reference_root(&leaves[split..]))
+			},
+		}
+	}
+
+	#[test]
+	fn root_is_stable() {
+		let log1 = MerkleAuditLog::in_memory();
+		let log2 = MerkleAuditLog::in_memory();
+		let empty_root = log1.audit_root();
+
+		let mut roots = Vec::new();
+		for document in 0..10u64 {
+			assert_eq!(log1.append(&event(document)), Ok(document as usize));
+			assert_eq!(log1.audit_root(), log1.audit_root());
+			roots.push(log1.audit_root());
+		}
+		for document in 0..10u64 {
+			log2.append(&event(document)).unwrap();
+			assert_eq!(log2.audit_root(), roots[document as usize]);
+		}
+
+		// every record changes the root
+		roots.push(empty_root);
+		roots.sort();
+		roots.dedup();
+		assert_eq!(roots.len(), 11);
+	}
+
+	#[test]
+	fn altered_record_changes_root() {
+		let log1 = MerkleAuditLog::in_memory();
+		let log2 = MerkleAuditLog::in_memory();
+		for document in 0..5u64 {
+			log1.append(&event(document)).unwrap();
+			let mut event = event(document);
+			if document == 2 {
+				event.result = Ok(true);
+			}
+			log2.append(&event).unwrap();
+		}
+
+		assert!(log1.audit_root() != log2.audit_root());
+	}
+
+	#[test]
+	fn scope_is_part_of_record() {
+		let mut decrypt = event(1);
+		decrypt.scope = "decrypt".into();
+		let mut reshare = event(1);
+		reshare.scope = "re-share".into();
+		assert!(audit_leaf(&decrypt) != audit_leaf(&event(1)));
+		assert!(audit_leaf(&decrypt) != audit_leaf(&reshare));
+	}
+
+	#[test]
+	fn proofs_are_verified() {
+		let log = MerkleAuditLog::in_memory();
+		for records_count in 1..18u64 {
+			log.append(&event(records_count - 1)).unwrap();
+			let root = log.audit_root();
+			for document in 0..records_count {
+				let leaf = audit_leaf(&event(document));
+				let proof = log.audit_proof(document as usize).unwrap();
+				assert_eq!(proof.records_count, records_count as usize);
+				assert!(proof.verify(&leaf, &root));
+				// proof of other record or of other root is not verified
+				assert!(!proof.verify(&audit_leaf(&event(document + 1)), &root));
+				assert!(!proof.verify(&leaf, &H256::from(1)));
+			}
+		}
+
+		assert!(log.audit_proof(17).is_err());
+	}
+
+	#[test]
+	fn tampered_proof_is_not_verified() {
+		let log = MerkleAuditLog::in_memory();
+		for document in 0..7u64 {
+			log.append(&event(document)).unwrap();
+		}
+		let root = log.audit_root();
+		let leaf = audit_leaf(&event(4));
+
+		let mut proof = log.audit_proof(4).unwrap();
+		proof.index = 5;
+		assert!(!proof.verify(&leaf, &root));
+
+		let mut proof = log.audit_proof(4).unwrap();
+		proof.path[0] = H256::from(1);
+		assert!(!proof.verify(&leaf, &root));
+
+		let mut proof = log.audit_proof(4).unwrap();
+		proof.path.pop();
+		assert!(!proof.verify(&leaf, &root));
+	}
+
+	#[test]
+	fn persistent_log_survives_restart() {
+		let path = RandomTempPath::create_dir();
+		let log = MerkleAuditLog::open(path.as_str()).unwrap();
+		for document in 0..5u64 {
+			log.append(&event(document)).unwrap();
+		}
+		let root = log.audit_root();
+		drop(log);
+
+		let log = MerkleAuditLog::open(path.as_str()).unwrap();
+		assert_eq!(log.len(), 5);
+		assert_eq!(log.audit_root(), root);
+
+		log.append(&event(5)).unwrap();
+		let proof = log.audit_proof(5).unwrap();
+		assert!(proof.verify(&audit_leaf(&event(5)), &log.audit_root()));
+	}
+
+	#[test]
+	fn root_matches_reference_tree() {
+		let path = RandomTempPath::create_dir();
+		let in_memory = MerkleAuditLog::in_memory();
+		let persistent = MerkleAuditLog::open(path.as_str()).unwrap();
+		let mut leaves = Vec::new();
+		assert_eq!(in_memory.audit_root(), reference_root(&leaves));
+		for document in 0..40u64 {
+			in_memory.append(&event(document)).unwrap();
+			persistent.append(&event(document)).unwrap();
+			leaves.push(audit_leaf(&event(document)));
+			assert_eq!(in_memory.audit_root(), reference_root(&leaves));
+			assert_eq!(persistent.audit_root(), reference_root(&leaves));
+		}
+
+		// proofs of the persistent log are made from the persisted subtrees
+		drop(persistent);
+		let persistent = MerkleAuditLog::open(path.as_str()).unwrap();
+		assert_eq!(persistent.audit_root(), reference_root(&leaves));
+		for index in 0..40 {
+			assert_eq!(persistent.audit_proof(index), in_memory.audit_proof(index));
+		}
+	}
+
+	#[test]
+	fn log_of_records_hashes_is_upgraded() {
+		let path = RandomTempPath::create_dir();
+		let mut leaves = Vec::new();
+		{
+			let db = Database::open_default(path.as_str()).unwrap();
+			let mut batch = db.transaction();
+			for document in 0..11u64 {
+				let leaf = audit_leaf(&event(document));
+				batch.put(None, &node_key(0, document as usize), &*leaf);
+				leaves.push(leaf);
+			}
+			db.write(batch).unwrap();
+		}
+
+		let log = MerkleAuditLog::open(path.as_str()).unwrap();
+		assert_eq!(log.len(), 11);
+		assert_eq!(log.audit_root(), reference_root(&leaves));
+		assert!(log.audit_proof(7).unwrap().verify(&leaves[7], &log.audit_root()));
+		log.append(&event(11)).unwrap();
+		drop(log);
+
+		leaves.push(audit_leaf(&event(11)));
+		let log = MerkleAuditLog::open(path.as_str()).unwrap();
+		assert_eq!(log.len(), 12);
+		assert_eq!(log.audit_root(), reference_root(&leaves));
+	}
+}
@@ -0,0 +1,750 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::net::IpAddr;
+use std::time::Instant;
+use futures::{future, Future};
+use ethcore::client::{Client, BlockChainClient, BlockId};
+use ethcore::header::BlockNumber;
+use native_contracts::Registry;
+use util::{Address, Bytes, H256, Hashable};
+use types::all::{Error, DocumentAddress, Public, Requester};
+
+mod attributes;
+mod audited;
+mod background;
+mod cache_audit;
+mod cached;
+mod clock;
+mod commit;
+mod composite;
+mod decision;
+mod events;
+mod fail_closed;
+mod governor;
+mod invalidation;
+mod merkle_log;
+mod network;
+mod on_chain;
+mod overrides;
+mod policy;
+mod replay;
+mod report;
+mod service;
+mod session_limit;
+mod single_use;
+mod startup;
+mod temporary;
+mod ticket;
+
+pub use self::attributes::{AttributeAclStorage, AttributeAclStorageConfig, AttributeExpression, AttributeResolver, Attributes, Operand};
+pub use self::audited::{AuditedAclStorage, AclAuditEvent, DecisionReceiver};
+pub use self::cache_audit::{CacheAuditor, CacheAuditorConfig, CacheMismatch, MismatchHandler};
+pub use self::cached::{CachedAclStorage, CachedAclStorageConfig, CacheMode, GrantOnlyCacheAclStorage, PrewarmStats};
+pub use self::clock::{Clock, SystemClock};
+pub use self::commit::{CommitAclStorage, GrantToken, AccessOutcome, AccessRecord, AccessHandler};
+pub use self::composite::{CompositeAclStorage, CombinePolicy, CompositeDecision, OnBackendError};
+pub use self::decision::{AclDecision, sign_decision, verify_decision};
+pub use self::events::{AclEvent, AclEventSink, AclEventStream, EventStreamAclStorage, LogEventSink, FileEventSink, ChannelEventSink,
+	AclEventReceiver};
+pub use self::fail_closed::FailClosedAclStorage;
+pub use self::governor::{AclGovernor, AclWork, GovernedAclStorage};
+pub use self::invalidation::{PermissionChange, PermissionChangeInvalidator};
+pub use self::merkle_log::{MerkleAuditLog, MerkleProof, audit_leaf};
+pub use self::network::{NetworkGatedAclStorage, IpNetwork};
+pub use self::on_chain::{OnChainAclStorage, OnChainAclStorageConfig, DocumentAddressScheme, DecisionTransform, CandidateAddresses, namespaced_document};
+pub use self::overrides::{OverrideAclStorage, AdminResolver};
+pub use self::policy::{EffectivePolicy, PolicyEntry};
+pub use self::replay::{AuditReplayer, ReplayRecord, ReplayReport, ReplayMismatch};
+pub use self::report::{AclReport, CacheReport};
+pub use self::service::{AclService, AclRequest};
+pub use self::session_limit::{SessionLimitAclStorage, SessionGuard};
+pub use self::single_use::SingleUseAclStorage;
+pub use self::startup::{StartupGateAclStorage, StartupGateConfig, StartupBehavior, ReadinessProbe};
+pub use self::temporary::{TemporaryGrantAclStorage, TemporaryGrantAclStorageConfig};
+pub use self::ticket::{TicketAclStorage, AccessTicket, sign_ticket};
+
+/// Max number of requestors, returned by `AclStorage::list_requestors`.
+pub const MAX_LISTED_REQUESTORS: usize = 1024;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+/// Additional information about ACL check request.
+pub struct AclContext {
+	/// Network address, the request has came from (if known).
+	pub source: Option<IpAddr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Block, at which ACL decision has been made.
+pub struct DecisionBlock {
+	/// Block number.
+	pub number: BlockNumber,
+	/// Block hash.
+	pub hash: H256,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// ACL check result, which could be cached by the caller.
+pub struct CacheableDecision {
+	/// Is access granted.
+	pub granted: bool,
+	/// Time, after which the decision must be re-checked. None if storage does not limit decision lifetime.
+	pub not_after: Option<Instant>,
+	/// Block, at which the decision has been made. None if storage is not block-based.
+	pub block: Option<DecisionBlock>,
+}
+
+/// ACL storage of Secret Store
+pub trait AclStorage: Send + Sync {
+	/// Check if `requester` can access document with hash `document`
+	fn check(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error>;
+
+	/// Check if requester with given address can access document with hash `document`.
+	/// Callers, checking the same requester against many documents, could derive its address once
+	/// and use this method to avoid deriving the address from the public key on every check.
+	fn check_by_address(&self, address: &Address, document: &DocumentAddress) -> Result<bool, Error> {
+		self.check(&Requester::Address(address.clone()), document)
+	}
+
+	/// Check if `requester` can access document with hash `document`, given additional request context.
+	/// Storages, which do not use the context, ignore it.
+	fn check_with_context(&self, requester: &Requester, document: &DocumentAddress, _context: &AclContext) -> Result<bool, Error> {
+		self.check(requester, document)
+	}
+
+	/// Check if `requester` can access document with hash `document` for given purpose (i.e. "decrypt").
+	/// Empty scope is the same as plain check. Storages, which can't distinguish scopes, fail scoped checks,
+	/// so that grant for one purpose is never used for another: by default, error is returned.
+	fn check_scoped(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<bool, Error> {
+		if !scope.is_empty() {
+			return Err(Error::Internal("scoped checks are not supported by ACL storage".to_owned()));
+		}
+
+		self.check(requester, document)
+	}
+
+	/// Check if `requester` can access document with hash `document` and return the block, at which the decision
+	/// has been made. Storages, which are not block-based, return None.
+	fn check_with_block(&self, requester: &Requester, document: &DocumentAddress) -> Result<(bool, Option<DecisionBlock>), Error> {
+		self.check(requester, document).map(|granted| (granted, None))
+	}
+
+	/// Check if `requester` can access document with hash `document` and return the time, until which the decision
+	/// could be cached by the caller. Storages, which do not limit decision lifetime, return None.
+	fn check_with_expiry(&self, requester: &Requester, document: &DocumentAddress) -> Result<CacheableDecision, Error> {
+		self.check_with_block(requester, document).map(|(granted, block)| CacheableDecision {
+			granted: granted,
+			not_after: None,
+			block: block,
+		})
+	}
+
+	/// Check if `requester` can access document with hash `document` for given purpose and return the time, until which
+	/// the decision could be cached by the caller. Empty scope is the same as plain check with expiry.
+	/// Storages, which limit lifetime of scoped decisions, must override this method.
+	fn check_scoped_with_expiry(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<CacheableDecision, Error> {
+		if scope.is_empty() {
+			return self.check_with_expiry(requester, document);
+		}
+
+		self.check_scoped(requester, document, scope).map(|granted| CacheableDecision {
+			granted: granted,
+			not_after: None,
+			block: None,
+		})
+	}
+
+	/// Check if `requester` can access document with hash `document`, without side effects:
+	/// the check is not cached and is not reported to audit subscribers. Used by health checks and probes.
+	fn check_dry_run(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		self.check(requester, document)
+	}
+
+	/// Check if `requester` can access document with hash `document` for given purpose, without side effects.
+	/// Empty scope is the same as plain dry-run check. Storages, which have side effects of scoped checks,
+	/// must override this method.
+	fn check_scoped_dry_run(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<bool, Error> {
+		if scope.is_empty() {
+			return self.check_dry_run(requester, document);
+		}
+
+		self.check_scoped(requester, document, scope)
+	}
+
+	/// List requestors, which can access document with hash `document`.
+	/// Not every storage is able to enumerate requestors: by default, error is returned.
+	fn list_requestors(&self, _document: &DocumentAddress) -> Result<Vec<Address>, Error> {
+		Err(Error::Internal("requestors enumeration is not supported by ACL storage".to_owned()))
+	}
+
+	/// Get documents, requester with given public key is allowed to access. Used to answer access review requests
+	/// of the requester itself. Not every storage is able to enumerate documents: by default, error is returned.
+	fn effective_policy(&self, _public: &Public) -> Result<EffectivePolicy, Error> {
+		Err(Error::Internal("policy enumeration is not supported by ACL storage".to_owned()))
+	}
+
+	/// Atomically check if `requester` can access document with hash `document` and, if access is granted,
+	/// mark the document as consumed, so that subsequent requests are denied.
+	/// Only storages with writable state support this: on-chain storage would require a transaction to
+	/// change contract state, so it is not available for read-only ACL contracts. By default, error is returned.
+	fn check_and_consume(&self, _requester: &Requester, _document: &DocumentAddress) -> Result<bool, Error> {
+		Err(Error::Internal("single-use documents are not supported by ACL storage".to_owned()))
+	}
+
+	/// Get state of this storage and all underlying storages, for monitoring.
+	fn report(&self) -> AclReport {
+		let mut report = AclReport::default();
+		self.fill_report(&mut report);
+		report
+	}
+
+	/// Fill own part of the state report and pass it to the underlying storages.
+	fn fill_report(&self, _report: &mut AclReport) {
+	}
+
+	#[deprecated(note = "use `check` with `Requester::Public`")]
+	/// Check if requestor with `public` key can access document with hash `document`
+	fn check_public(&self, public: &Public, document: &DocumentAddress) -> Result<bool, Error> {
+		self.check(&Requester::Public(public.clone()), document)
+	}
+
+	/// Check if `requester` can access every document from `documents`, stopping at `deadline`, as seen by `clock`.
+	/// Results of documents, which were not checked before the deadline, are None, so that the caller
+	/// could retry them later.
+	fn check_many_until(&self, requester: &Requester, documents: &[DocumentAddress], deadline: Instant, clock: &Clock) -> Vec<Option<Result<bool, Error>>> {
+		let mut results = Vec::with_capacity(documents.len());
+		for document in documents {
+			if clock.now() >= deadline {
+				break;
+			}
+			results.push(Some(self.check(requester, document)));
+		}
+
+		let checked = results.len();
+		if checked != documents.len() {
+			trace!(target: "secretstore", "ACL checks deadline is reached after {} of {} documents", checked, documents.len());
+			results.resize(documents.len(), None);
+		}
+		results
+	}
+}
+
+/// Check that listed requestors fit into `MAX_LISTED_REQUESTORS`.
+fn limit_listed_requestors(requestors: Vec<Address>) -> Result<Vec<Address>, Error> {
+	if requestors.len() > MAX_LISTED_REQUESTORS {
+		return Err(Error::Internal(format!("too many requestors: {} (max {})", requestors.len(), MAX_LISTED_REQUESTORS)));
+	}
+
+	Ok(requestors)
+}
+
+/// Blockchain client, used by on-chain ACL storage.
+pub trait AclClient: Send + Sync {
+	/// Get address of the contract, registered under given name.
+	/// Returns Ok(None) if there's no such contract and Err if registry could not be read.
+	fn registry_address(&self, name: String) -> Result<Option<Address>, String>;
+	/// Make constant call to the contract.
+	fn call_contract(&self, block: BlockId, address: Address, data: Bytes) -> Result<Bytes, String>;
+	/// Get current best block.
+	fn best_block(&self) -> DecisionBlock;
+}
+
+impl AclClient for Client {
+	fn registry_address(&self, name: String) -> Result<Option<Address>, String> {
+		let registrar = match self.registrar_address() {
+			Some(registrar) => Registry::new(registrar),
+			None => return Ok(None),
+		};
+
+		let do_call = |a, d| future::done(BlockChainClient::call_contract(self, BlockId::Latest, a, d));
+		registrar.get_address(do_call, name.as_bytes().sha3(), "A".to_owned())
+			.wait()
+			.map(|address| if address.is_zero() { None } else { Some(address) })
+	}
+
+	fn call_contract(&self, block: BlockId, address: Address, data: Bytes) -> Result<Bytes, String> {
+		BlockChainClient::call_contract(self, block, address, data)
+	}
+
+	fn best_block(&self) -> DecisionBlock {
+		let info = BlockChainClient::chain_info(self);
+		DecisionBlock {
+			number: info.best_block_number,
+			hash: info.best_block_hash,
+		}
+	}
+}
+
+#[cfg(test)]
+pub mod tests {
+	use std::collections::{HashMap, HashSet};
+	use std::sync::Arc;
+	use std::sync::atomic::{AtomicUsize, Ordering};
+	use std::thread;
+	use std::time::{Duration, Instant};
+	use parking_lot::{Mutex, RwLock};
+	use ethcore::client::BlockId;
+	use ethkey::public_to_address;
+	use util::{Address, Bytes, H256};
+	use types::all::{Error, DocumentAddress, Public, Requester};
+	use super::{AclStorage, AclClient, DecisionBlock, EffectivePolicy, PolicyEntry, Clock, SystemClock};
+	use super::clock::tests::MockClock;
+
+	#[derive(Default, Debug)]
+	/// Dummy ACL storage implementation
+	pub struct DummyAclStorage {
+		prohibited: RwLock<HashMap<Address, HashSet<DocumentAddress>>>,
+		checks: AtomicUsize,
+	}
+
+	impl DummyAclStorage {
+		#[cfg(test)]
+		/// Prohibit given requestor access to given document
+		pub fn prohibit(&self, public: Public, document: DocumentAddress) {
+			self.prohibited.write()
+				.entry(public_to_address(&public))
+				.or_insert_with(Default::default)
+				.insert(document);
+		}
+
+		/// Get number of checks, made by this storage
+		pub fn checks_count(&self) -> usize {
+			self.checks.load(Ordering::SeqCst)
+		}
+	}
+
+	#[derive(Default)]
+	/// ACL storage, which only grants access for explicitly allowed scopes.
+	pub struct ScopedAclStorage {
+		/// Allowed (requester, document, scope).
+		pub allowed: RwLock<HashSet<(Address, DocumentAddress, String)>>,
+		/// Number of checks, made by this storage.
+		pub checks: AtomicUsize,
+	}
+
+	impl ScopedAclStorage {
+		/// Allow requester to access the document for given scope.
+		pub fn allow(&self, requester: Address, document: DocumentAddress, scope: &str) {
+			self.allowed.write().insert((requester, document, scope.to_owned()));
+		}
+	}
+
+	impl AclStorage for ScopedAclStorage {
+		fn check(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+			self.check_scoped(requester, document, "")
+		}
+
+		fn check_scoped(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<bool, Error> {
+			self.checks.fetch_add(1, Ordering::SeqCst);
+			let address = requester.address(document)?;
+			Ok(self.allowed.read().contains(&(address, document.clone(), scope.to_owned())))
+		}
+	}
+
+	impl AclStorage for DummyAclStorage {
+		fn check(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+			self.checks.fetch_add(1, Ordering::SeqCst);
+			let address = requester.address(document)?;
+			Ok(self.prohibited.read()
+				.get(&address)
+				.map(|docs| !docs.contains(document))
+				.unwrap_or(true))
+		}
+
+		fn effective_policy(&self, public: &Public) -> Result<EffectivePolicy, Error> {
+			let mut policy = EffectivePolicy::all_documents();
+			if let Some(documents) = self.prohibited.read().get(&public_to_address(public)) {
+				for document in documents {
+					policy.deny(document);
+				}
+			}
+			Ok(policy)
+		}
+	}
+
+	#[derive(Default, Debug)]
+	/// ACL storage, which only allows access to listed requestors
+	pub struct AllowlistAclStorage {
+		allowed: RwLock<HashMap<DocumentAddress, Vec<Address>>>,
+	}
+
+	impl AllowlistAclStorage {
+		/// Allow given requestor access to given document
+		pub fn allow(&self, requestor: Address, document: DocumentAddress) {
+			self.allowed.write()
+				.entry(document)
+				.or_insert_with(Default::default)
+				.push(requestor);
+		}
+	}
+
+	impl AclStorage for AllowlistAclStorage {
+		fn check(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+			let address = requester.address(document)?;
+			Ok(self.allowed.read()
+				.get(document)
+				.map(|requestors| requestors.contains(&address))
+				.unwrap_or(false))
+		}
+
+		fn list_requestors(&self, document: &DocumentAddress) -> Result<Vec<Address>, Error> {
+			Ok(self.allowed.read().get(document).cloned().unwrap_or_default())
+		}
+
+		fn effective_policy(&self, public: &Public) -> Result<EffectivePolicy, Error> {
+			let address = public_to_address(public);
+			let mut policy = EffectivePolicy::default();
+			for (document, requestors) in self.allowed.read().iter() {
+				if requestors.contains(&address) {
+					policy.allow(PolicyEntry {
+						document: document.clone().into(),
+						expires_in_secs: None,
+						reason: "allowlist".into(),
+					});
+				}
+			}
+			Ok(policy)
+		}
+	}
+
+	/// Dummy blockchain client for on-chain ACL storage tests
+	pub struct DummyAclClient {
+		/// Registered ACL contract address.
+		pub contract_address: Mutex<Option<Address>>,
+		/// Result of every contract call.
+		pub call_result: Mutex<Result<Bytes, String>>,
+		/// Blocks and data of all contract calls.
+		pub calls: Mutex<Vec<(BlockId, Address, Bytes)>>,
+		/// Error of every registry lookup, if set.
+		pub registry_error: Mutex<Option<String>>,
+		/// Number of registry lookups.
+		pub registry_lookups: AtomicUsize,
+		/// Delay of every registry lookup, if set.
+		pub registry_delay: Mutex<Option<Duration>>,
+		/// Delay of every contract call, if set.
+		pub call_delay: Mutex<Option<Duration>>,
+		/// Current best block.
+		pub best_block: Mutex<DecisionBlock>,
+	}
+
+	impl DummyAclClient {
+		/// Create client with given contract registered, granting access on every call
+		pub fn new(contract_address: Option<Address>) -> Self {
+			DummyAclClient {
+				contract_address: Mutex::new(contract_address),
+				call_result: Mutex::new(Ok(encode_bool(true))),
+				calls: Mutex::new(Vec::new()),
+				registry_error: Mutex::new(None),
+				registry_lookups: AtomicUsize::new(0),
+				registry_delay: Mutex::new(None),
+				call_delay: Mutex::new(None),
+				best_block: Mutex::new(DecisionBlock {
+					number: 1,
+					hash: H256::from(1),
+				}),
+			}
+		}
+
+		/// Get number of registry lookups
+		pub fn registry_lookups_count(&self) -> usize {
+			self.registry_lookups.load(Ordering::SeqCst)
+		}
+	}
+
+	/// ACL storage, which grants access after given delay
+	pub struct SlowAclStorage {
+		/// Delay of every check.
+		pub delay: Duration,
+	}
+
+	impl AclStorage for SlowAclStorage {
+		fn check(&self, _requester: &Requester, _document: &DocumentAddress) -> Result<bool, Error> {
+			thread::sleep(self.delay);
+			Ok(true)
+		}
+	}
+
+	impl AclClient for DummyAclClient {
+		fn registry_address(&self, _name: String) -> Result<Option<Address>, String> {
+			self.registry_lookups.fetch_add(1, Ordering::SeqCst);
+			if let Some(delay) = *self.registry_delay.lock() {
+				thread::sleep(delay);
+			}
+			match self.registry_error.lock().clone() {
+				Some(error) => Err(error),
+				None => Ok(self.contract_address.lock().clone()),
+			}
+		}
+
+		fn call_contract(&self, block: BlockId, address: Address, data: Bytes) -> Result<Bytes, String> {
+			self.calls.lock().push((block, address, data));
+			let call_delay = self.call_delay.lock().clone();
+			if let Some(call_delay) = call_delay {
+				thread::sleep(call_delay);
+			}
+			self.call_result.lock().clone()
+		}
+
+		fn best_block(&self) -> DecisionBlock {
+			self.best_block.lock().clone()
+		}
+	}
+
+	#[test]
+	#[allow(deprecated)]
+	fn public_check_is_forwarded_to_requester_check() {
+		use ethkey::{Random, Generator};
+
+		let acl_storage = DummyAclStorage::default();
+		let public = Random.generate().unwrap().public().clone();
+		acl_storage.prohibit(public.clone(), DocumentAddress::from(1));
+
+		assert_eq!(acl_storage.check_public(&public, &DocumentAddress::from(1)), Ok(false));
+		assert_eq!(acl_storage.check_public(&public, &DocumentAddress::from(2)), Ok(true));
+	}
+
+	#[test]
+	fn address_check_is_forwarded_to_requester_check() {
+		use ethkey::{Random, Generator};
+
+		let acl_storage = DummyAclStorage::default();
+		let public = Random.generate().unwrap().public().clone();
+		acl_storage.prohibit(public.clone(), DocumentAddress::from(1));
+
+		assert_eq!(acl_storage.check_by_address(&public_to_address(&public), &DocumentAddress::from(1)), Ok(false));
+		assert_eq!(acl_storage.check_by_address(&public_to_address(&public), &DocumentAddress::from(2)), Ok(true));
+	}
+
+	#[test]
+	fn batch_check_returns_partial_results_after_deadline() {
+		use ethkey::{Random, Generator};
+
+		let acl_storage = SlowAclStorage { delay: Duration::from_millis(20) };
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+		let documents: Vec<_> = (0..10u64).map(DocumentAddress::from).collect();
+
+		let results = acl_storage.check_many_until(&requester, &documents, Instant::now() + Duration::from_millis(50), &SystemClock);
+		assert_eq!(results.len(), documents.len());
+		assert_eq!(results[0], Some(Ok(true)));
+		assert_eq!(results[9], None);
+		// all checked documents go before pending documents
+		let checked = results.iter().take_while(|r| r.is_some()).count();
+		assert!(results[checked..].iter().all(|r| r.is_none()));
+
+		// nothing is checked when deadline has already passed
+		assert!(acl_storage.check_many_until(&requester, &documents, Instant::now(), &SystemClock).iter().all(|r| r.is_none()));
+	}
+
+	#[test]
+	fn decision_block_is_forwarded_by_wrapping_storages() {
+		use std::time::Duration;
+		use key_storage::tests::DummyKeyStorage;
+		use super::{OnChainAclStorage, AuditedAclStorage, CompositeAclStorage, CombinePolicy, OverrideAclStorage,
+			TemporaryGrantAclStorage, SessionLimitAclStorage, CommitAclStorage, SingleUseAclStorage, FailClosedAclStorage,
+			StartupGateAclStorage, StartupGateConfig};
+
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(1))));
+		let block = DecisionBlock { number: 5, hash: H256::from(5) };
+		*client.best_block.lock() = block.clone();
+		let inner: Arc<AclStorage> = Arc::new(OnChainAclStorage::new(client));
+
+		let wrappers: Vec<Arc<AclStorage>> = vec![
+			Arc::new(AuditedAclStorage::new(inner.clone())),
+			Arc::new(CompositeAclStorage::new(vec![inner.clone(), inner.clone()], CombinePolicy::All)),
+			Arc::new(OverrideAclStorage::new(inner.clone(), Arc::new(DummyKeyStorage::default()), Arc::new(|| Ok(None)))),
+			Arc::new(TemporaryGrantAclStorage::new(inner.clone())),
+			Arc::new(SessionLimitAclStorage::new(inner.clone(), 1)),
+			Arc::new(CommitAclStorage::new(inner.clone(), Duration::from_secs(60), Arc::new(|_| ()))),
+			Arc::new(SingleUseAclStorage::new(inner.clone())),
+			Arc::new(FailClosedAclStorage::new(inner.clone(), Duration::from_secs(60))),
+			Arc::new(StartupGateAclStorage::new(inner.clone(), StartupGateConfig { window: Some(Duration::from_secs(0)), ..Default::default() },
+				Arc::new(|| Ok(())))),
+		];
+
+		let requester = Requester::Address(Address::from(2));
+		for wrapper in wrappers {
+			assert_eq!(wrapper.check_with_block(&requester, &DocumentAddress::from(1)), Ok((true, Some(block.clone()))));
+		}
+	}
+
+	#[test]
+	fn decision_expiry_is_forwarded_by_wrapping_storages() {
+		use std::time::Duration;
+		use key_storage::tests::DummyKeyStorage;
+		use super::{AuditedAclStorage, CompositeAclStorage, CombinePolicy, OverrideAclStorage, TemporaryGrantAclStorage,
+			SessionLimitAclStorage, CommitAclStorage, SingleUseAclStorage, FailClosedAclStorage, StartupGateAclStorage,
+			StartupGateConfig, EventStreamAclStorage, AclEventStream};
+
+		let clock = Arc::new(MockClock::default());
+		let temporary = Arc::new(TemporaryGrantAclStorage::with_clock(Arc::new(DummyAclStorage::default()), clock.clone()));
+		temporary.grant(Address::from(2), DocumentAddress::from(1), Duration::from_secs(10));
+		let inner: Arc<AclStorage> = temporary;
+
+		let wrappers: Vec<Arc<AclStorage>> = vec![
+			Arc::new(AuditedAclStorage::new(inner.clone())),
+			Arc::new(CompositeAclStorage::new(vec![inner.clone(), inner.clone()], CombinePolicy::All)),
+			Arc::new(OverrideAclStorage::new(inner.clone(), Arc::new(DummyKeyStorage::default()), Arc::new(|| Ok(None)))),
+			Arc::new(SessionLimitAclStorage::new(inner.clone(), 1)),
+			Arc::new(CommitAclStorage::new(inner.clone(), Duration::from_secs(60), Arc::new(|_| ()))),
+			Arc::new(SingleUseAclStorage::new(inner.clone())),
+			Arc::new(FailClosedAclStorage::new(inner.clone(), Duration::from_secs(60))),
+			Arc::new(StartupGateAclStorage::new(inner.clone(), StartupGateConfig { window: Some(Duration::from_secs(0)), ..Default::default() },
+				Arc::new(|| Ok(())))),
+			Arc::new(EventStreamAclStorage::new(inner.clone(), Arc::new(AclEventStream::new()))),
+		];
+
+		let requester = Requester::Address(Address::from(2));
+		let not_after = Some(clock.now() + Duration::from_secs(10));
+		for wrapper in wrappers {
+			let decision = wrapper.check_with_expiry(&requester, &DocumentAddress::from(1)).unwrap();
+			assert!(decision.granted);
+			assert_eq!(decision.not_after, not_after);
+			let decision = wrapper.check_scoped_with_expiry(&requester, &DocumentAddress::from(1), "decrypt").unwrap();
+			assert!(decision.granted);
+			assert_eq!(decision.not_after, not_after);
+		}
+	}
+
+	#[test]
+	fn dry_run_is_forwarded_by_wrapping_storages() {
+		use super::{CachedAclStorage, SessionLimitAclStorage, CommitAclStorage, SingleUseAclStorage};
+
+		let cache = Arc::new(CachedAclStorage::new(Arc::new(DummyAclStorage::default()), Duration::from_secs(60)));
+		let inner: Arc<AclStorage> = cache.clone();
+
+		let wrappers: Vec<Arc<AclStorage>> = vec![
+			Arc::new(SessionLimitAclStorage::new(inner.clone(), 1)),
+			Arc::new(CommitAclStorage::new(inner.clone(), Duration::from_secs(60), Arc::new(|_| ()))),
+			Arc::new(SingleUseAclStorage::new(inner.clone())),
+		];
+
+		let requester = Requester::Address(Address::from(2));
+		for wrapper in wrappers {
+			assert_eq!(wrapper.check_dry_run(&requester, &DocumentAddress::from(1)), Ok(true));
+			assert_eq!(wrapper.check_scoped_dry_run(&requester, &DocumentAddress::from(1), ""), Ok(true));
+		}
+		// dry-run checks have reached the cache without side effects
+		assert_eq!(cache.len(), 0);
+	}
+
+	#[test]
+	fn consumption_is_forwarded_by_wrapping_storages() {
+		use key_storage::tests::DummyKeyStorage;
+		use super::{AuditedAclStorage, CachedAclStorage, GrantOnlyCacheAclStorage, CompositeAclStorage, CombinePolicy,
+			OverrideAclStorage, TemporaryGrantAclStorage, SessionLimitAclStorage, CommitAclStorage, SingleUseAclStorage,
+			FailClosedAclStorage, StartupGateAclStorage, StartupGateConfig, EventStreamAclStorage, AclEventStream,
+			AclGovernor, GovernedAclStorage};
+
+		fn single_use() -> Arc<AclStorage> {
+			Arc::new(SingleUseAclStorage::new(Arc::new(DummyAclStorage::default())))
+		}
+
+		let wrappers: Vec<Arc<AclStorage>> = vec![
+			Arc::new(AuditedAclStorage::new(single_use())),
+			Arc::new(CachedAclStorage::new(single_use(), Duration::from_secs(60))),
+			Arc::new(GrantOnlyCacheAclStorage::new(single_use(), Duration::from_secs(60))),
+			Arc::new(CompositeAclStorage::new(vec![single_use()], CombinePolicy::All)),
+			Arc::new(OverrideAclStorage::new(single_use(), Arc::new(DummyKeyStorage::default()), Arc::new(|| Ok(None)))),
+			Arc::new(TemporaryGrantAclStorage::new(single_use())),
+			Arc::new(SessionLimitAclStorage::new(single_use(), 1)),
+			Arc::new(CommitAclStorage::new(single_use(), Duration::from_secs(60), Arc::new(|_| ()))),
+			Arc::new(FailClosedAclStorage::new(single_use(), Duration::from_secs(60))),
+			Arc::new(StartupGateAclStorage::new(single_use(), StartupGateConfig { window: Some(Duration::from_secs(0)), ..Default::default() },
+				Arc::new(|| Ok(())))),
+			Arc::new(EventStreamAclStorage::new(single_use(), Arc::new(AclEventStream::new()))),
+			Arc::new(GovernedAclStorage::new(single_use(), Arc::new(AclGovernor::new(1)))),
+		];
+
+		let requester = Requester::Address(Address::from(2));
+		for wrapper in wrappers {
+			// decision, made before consumption, is not served after it
+			assert_eq!(wrapper.check(&requester, &DocumentAddress::from(1)), Ok(true));
+			assert_eq!(wrapper.check_and_consume(&requester, &DocumentAddress::from(1)), Ok(true));
+			assert_eq!(wrapper.check_and_consume(&requester, &DocumentAddress::from(1)), Ok(false));
+			assert_eq!(wrapper.check(&requester, &DocumentAddress::from(1)), Ok(false));
+		}
+	}
+
+	#[test]
+	fn scope_is_forwarded_by_wrapping_storages() {
+		use key_storage::tests::DummyKeyStorage;
+		use super::{CompositeAclStorage, CombinePolicy, OverrideAclStorage, TemporaryGrantAclStorage, SingleUseAclStorage,
+			NetworkGatedAclStorage, TicketAclStorage};
+		use ethkey::{Random, Generator};
+
+		let inner = Arc::new(ScopedAclStorage::default());
+		inner.allow(Address::from(2), DocumentAddress::from(1), "decrypt");
+		let inner: Arc<AclStorage> = inner;
+
+		let wrappers: Vec<Arc<AclStorage>> = vec![
+			Arc::new(CompositeAclStorage::new(vec![inner.clone(), inner.clone()], CombinePolicy::All)),
+			Arc::new(OverrideAclStorage::new(inner.clone(), Arc::new(DummyKeyStorage::default()), Arc::new(|| Ok(None)))),
+			Arc::new(TemporaryGrantAclStorage::new(inner.clone())),
+			Arc::new(SingleUseAclStorage::new(inner.clone())),
+		];
+
+		let requester = Requester::Address(Address::from(2));
+		for wrapper in wrappers {
+			assert_eq!(wrapper.check_scoped(&requester, &DocumentAddress::from(1), "decrypt"), Ok(true));
+			assert_eq!(wrapper.check_scoped(&requester, &DocumentAddress::from(1), "re-share"), Ok(false));
+			assert_eq!(wrapper.check_scoped_dry_run(&requester, &DocumentAddress::from(1), "decrypt"), Ok(true));
+			assert_eq!(wrapper.check_scoped_dry_run(&requester, &DocumentAddress::from(1), "re-share"), Ok(false));
+		}
+
+		// storages, which require more than the scope, deny scoped checks
+		let denying: Vec<Arc<AclStorage>> = vec![
+			Arc::new(NetworkGatedAclStorage::new(inner.clone(), vec!["0.0.0.0/0".parse().unwrap()])),
+			Arc::new(TicketAclStorage::new(Random.generate().unwrap().public().clone(), Some(inner.clone()))),
+		];
+		for storage in denying {
+			assert_eq!(storage.check_scoped(&requester, &DocumentAddress::from(1), "decrypt"), Ok(false));
+		}
+	}
+
+	#[test]
+	fn batch_check_deadline_is_checked_against_given_clock() {
+		use ethkey::{Random, Generator};
+
+		struct ClockAdvancingAclStorage {
+			clock: Arc<MockClock>,
+		}
+
+		impl AclStorage for ClockAdvancingAclStorage {
+			fn check(&self, _requester: &Requester, _document: &DocumentAddress) -> Result<bool, Error> {
+				self.clock.advance(Duration::from_millis(20));
+				Ok(true)
+			}
+		}
+
+		let clock = Arc::new(MockClock::default());
+		let acl_storage = ClockAdvancingAclStorage { clock: clock.clone() };
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+		let documents: Vec<_> = (0..10u64).map(DocumentAddress::from).collect();
+
+		// checks are started at 0ms, 20ms and 40ms
+		let results = acl_storage.check_many_until(&requester, &documents, clock.now() + Duration::from_millis(50), &*clock);
+		assert_eq!(results.iter().filter(|r| r.is_some()).count(), 3);
+		assert!(results[..3].iter().all(|r| r == &Some(Ok(true))));
+		assert!(results[3..].iter().all(|r| r.is_none()));
+	}
+
+	/// ABI-encode single bool value
+	pub fn encode_bool(value: bool) -> Bytes {
+		let mut encoded = vec![0; 32];
+		encoded[31] = if value { 1 } else { 0 };
+		encoded
+	}
+}
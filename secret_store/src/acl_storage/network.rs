@@ -0,0 +1,286 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+use std::str::FromStr;
+use std::net::IpAddr;
+use types::all::{Error, DocumentAddress, Address, Public, Requester};
+use super::{AclStorage, AclContext, AclReport, CacheableDecision, EffectivePolicy};
+
+#[derive(Debug, Clone, PartialEq)]
+/// Range of network addresses in CIDR notation.
+pub struct IpNetwork {
+	/// Network address.
+	address: IpAddr,
+	/// Number of leading bits of the network address, shared by all addresses in range.
+	prefix: u8,
+}
+
+/// ACL storage, which only allows access from given source networks.
+/// Source address is taken from the check context, so checks without populated context are denied.
+/// Requests from outside of allowed networks are denied without consulting the underlying storage.
+pub struct NetworkGatedAclStorage {
+	/// Underlying ACL storage.
+	inner: Arc<AclStorage>,
+	/// Allowed source networks.
+	allowed_networks: Vec<IpNetwork>,
+}
+
+impl IpNetwork {
+	/// Create new network. Returns error if prefix is too large for given address.
+	pub fn new(address: IpAddr, prefix: u8) -> Result<Self, Error> {
+		let max_prefix = match address {
+			IpAddr::V4(_) => 32,
+			IpAddr::V6(_) => 128,
+		};
+		if prefix > max_prefix {
+			return Err(Error::Internal(format!("invalid network prefix {} of address {}", prefix, address)));
+		}
+
+		Ok(IpNetwork {
+			address: address,
+			prefix: prefix,
+		})
+	}
+
+	/// Check if address belongs to this network.
+	pub fn contains(&self, address: &IpAddr) -> bool {
+		match (&self.address, address) {
+			(&IpAddr::V4(ref network), &IpAddr::V4(ref address)) => prefix_matches(&network.octets(), &address.octets(), self.prefix),
+			(&IpAddr::V6(ref network), &IpAddr::V6(ref address)) => prefix_matches(&network.octets(), &address.octets(), self.prefix),
+			_ => false,
+		}
+	}
+}
+
+impl FromStr for IpNetwork {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Error> {
+		let mut parts = s.splitn(2, '/');
+		let address = parts.next().unwrap_or("");
+		let address: IpAddr = address.parse().map_err(|_| Error::Internal(format!("invalid network address {}", s)))?;
+		let prefix = match parts.next() {
+			Some(prefix) => prefix.parse().map_err(|_| Error::Internal(format!("invalid network prefix {}", s)))?,
+			None => match address {
+				IpAddr::V4(_) => 32,
+				IpAddr::V6(_) => 128,
+			},
+		};
+
+		IpNetwork::new(address, prefix)
+	}
+}
+
+impl NetworkGatedAclStorage {
+	/// Create new network-gated ACL storage on top of `inner`.
+	pub fn new(inner: Arc<AclStorage>, allowed_networks: Vec<IpNetwork>) -> Self {
+		NetworkGatedAclStorage {
+			inner: inner,
+			allowed_networks: allowed_networks,
+		}
+	}
+
+	/// Check if request from given source is allowed.
+	fn is_source_allowed(&self, context: &AclContext) -> bool {
+		match context.source {
+			Some(ref source) => self.allowed_networks.iter().any(|network| network.contains(source)),
+			None => false,
+		}
+	}
+}
+
+impl AclStorage for NetworkGatedAclStorage {
+	fn check(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		self.check_with_context(requester, document, &AclContext::default())
+	}
+
+	fn check_with_context(&self, requester: &Requester, document: &DocumentAddress, context: &AclContext) -> Result<bool, Error> {
+		if !self.is_source_allowed(context) {
+			trace!(target: "secretstore", "Access to document {:?} from {:?} is denied by network ACL", document, context.source);
+			return Ok(false);
+		}
+
+		self.inner.check_with_context(requester, document, context)
+	}
+
+	/// Scoped checks carry no request context, so they are made as if request source is unknown.
+	fn check_scoped(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<bool, Error> {
+		if !self.is_source_allowed(&AclContext::default()) {
+			trace!(target: "secretstore", "Access to document {:?} from unknown source is denied by network ACL", document);
+			return Ok(false);
+		}
+
+		self.inner.check_scoped(requester, document, scope)
+	}
+
+	/// Checks with expiry carry no request context, so they are made as if request source is unknown.
+	fn check_with_expiry(&self, requester: &Requester, document: &DocumentAddress) -> Result<CacheableDecision, Error> {
+		if !self.is_source_allowed(&AclContext::default()) {
+			trace!(target: "secretstore", "Access to document {:?} from unknown source is denied by network ACL", document);
+			return Ok(CacheableDecision {
+				granted: false,
+				not_after: None,
+				block: None,
+			});
+		}
+
+		self.inner.check_with_expiry(requester, document)
+	}
+
+	fn check_scoped_with_expiry(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<CacheableDecision, Error> {
+		if !self.is_source_allowed(&AclContext::default()) {
+			trace!(target: "secretstore", "Access to document {:?} from unknown source is denied by network ACL", document);
+			return Ok(CacheableDecision {
+				granted: false,
+				not_after: None,
+				block: None,
+			});
+		}
+
+		self.inner.check_scoped_with_expiry(requester, document, scope)
+	}
+
+	/// Consumption carries no request context, so it is made as if request source is unknown.
+	fn check_and_consume(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		if !self.is_source_allowed(&AclContext::default()) {
+			trace!(target: "secretstore", "Access to document {:?} from unknown source is denied by network ACL", document);
+			return Ok(false);
+		}
+
+		self.inner.check_and_consume(requester, document)
+	}
+
+	fn list_requestors(&self, document: &DocumentAddress) -> Result<Vec<Address>, Error> {
+		self.inner.list_requestors(document)
+	}
+
+	/// Policy is reported regardless of the network, requests are made from.
+	fn effective_policy(&self, public: &Public) -> Result<EffectivePolicy, Error> {
+		self.inner.effective_policy(public)
+	}
+
+	fn fill_report(&self, report: &mut AclReport) {
+		self.inner.fill_report(report)
+	}
+}
+
+/// Check if first `prefix` bits of both addresses are the same.
+fn prefix_matches(network: &[u8], address: &[u8], prefix: u8) -> bool {
+	let full_bytes = (prefix / 8) as usize;
+	if network[..full_bytes] != address[..full_bytes] {
+		return false;
+	}
+
+	let rest_bits = prefix % 8;
+	if rest_bits == 0 {
+		return true;
+	}
+
+	let mask = 0xffu8 << (8 - rest_bits);
+	network[full_bytes] & mask == address[full_bytes] & mask
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::time::Duration;
+	use ethkey::{Random, Generator};
+	use types::all::{DocumentAddress, Requester};
+	use acl_storage::{AclStorage, AclContext, CachedAclStorage, CompositeAclStorage, CombinePolicy};
+	use acl_storage::tests::DummyAclStorage;
+	use super::{IpNetwork, NetworkGatedAclStorage};
+
+	fn context(source: &str) -> AclContext {
+		AclContext {
+			source: Some(source.parse().unwrap()),
+		}
+	}
+
+	#[test]
+	fn networks_are_parsed() {
+		let network: IpNetwork = "10.0.0.0/8".parse().unwrap();
+		assert!(network.contains(&"10.1.2.3".parse().unwrap()));
+		assert!(!network.contains(&"11.1.2.3".parse().unwrap()));
+		assert!(!network.contains(&"::1".parse().unwrap()));
+
+		let network: IpNetwork = "192.168.1.128/25".parse().unwrap();
+		assert!(network.contains(&"192.168.1.200".parse().unwrap()));
+		assert!(!network.contains(&"192.168.1.100".parse().unwrap()));
+
+		let network: IpNetwork = "fd00::/8".parse().unwrap();
+		assert!(network.contains(&"fd12::1".parse().unwrap()));
+
+		let network: IpNetwork = "127.0.0.1".parse().unwrap();
+		assert!(network.contains(&"127.0.0.1".parse().unwrap()));
+		assert!(!network.contains(&"127.0.0.2".parse().unwrap()));
+
+		assert!("10.0.0.0/33".parse::<IpNetwork>().is_err());
+		assert!("10.0.0/8".parse::<IpNetwork>().is_err());
+	}
+
+	#[test]
+	fn requests_from_outside_of_allowed_networks_are_denied() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let acl_storage = NetworkGatedAclStorage::new(inner.clone(), vec!["10.0.0.0/8".parse().unwrap()]);
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+		let document = DocumentAddress::from(1);
+
+		assert_eq!(acl_storage.check_with_context(&requester, &document, &context("10.0.0.1")), Ok(true));
+		assert_eq!(acl_storage.check_with_context(&requester, &document, &context("192.168.0.1")), Ok(false));
+		assert_eq!(inner.checks_count(), 1);
+	}
+
+	#[test]
+	fn requests_without_context_are_denied() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let acl_storage = NetworkGatedAclStorage::new(inner.clone(), vec!["0.0.0.0/0".parse().unwrap()]);
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(false));
+		assert_eq!(acl_storage.check_with_context(&requester, &DocumentAddress::from(1), &context("1.2.3.4")), Ok(true));
+		assert_eq!(inner.checks_count(), 1);
+	}
+
+	#[test]
+	fn network_gate_is_applied_behind_cache() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let gated = Arc::new(NetworkGatedAclStorage::new(inner.clone(), vec!["10.0.0.0/8".parse().unwrap()]));
+		let acl_storage = CachedAclStorage::new(gated, Duration::from_secs(60));
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+		let document = DocumentAddress::from(1);
+
+		assert_eq!(acl_storage.check_with_context(&requester, &document, &context("10.0.0.1")), Ok(true));
+		// grant for one source is not reused for another
+		assert_eq!(acl_storage.check_with_context(&requester, &document, &context("192.168.0.1")), Ok(false));
+		assert_eq!(acl_storage.check_with_context(&requester, &document, &context("10.0.0.1")), Ok(true));
+		assert_eq!(inner.checks_count(), 2);
+	}
+
+	#[test]
+	fn network_gate_is_applied_behind_composite() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let gated = Arc::new(NetworkGatedAclStorage::new(inner.clone(), vec!["10.0.0.0/8".parse().unwrap()]));
+		let acl_storage = CompositeAclStorage::new(vec![gated, Arc::new(DummyAclStorage::default())], CombinePolicy::All);
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+		let document = DocumentAddress::from(1);
+
+		assert_eq!(acl_storage.check_with_context(&requester, &document, &context("10.0.0.1")), Ok(true));
+		assert_eq!(acl_storage.check_with_context(&requester, &document, &context("192.168.0.1")), Ok(false));
+		assert_eq!(acl_storage.check(&requester, &document), Ok(false));
+		assert_eq!(inner.checks_count(), 1);
+	}
+}
@@ -0,0 +1,1258 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::fmt;
+use std::sync::Arc;
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+use futures::{future, Future};
+use byteorder::{BigEndian, WriteBytesExt};
+use parking_lot::Mutex;
+use ethcore::client::BlockId;
+use native_contracts::SecretStoreAclStorage;
+use util::{Bytes, H256, Hashable};
+use types::all::{Error, AclError, DocumentAddress, Address, Public, Requester};
+use super::{AclStorage, AclClient, AclReport, DecisionBlock, Clock, SystemClock};
+use super::background::BackgroundCalls;
+
+const ACL_CHECKER_CONTRACT_REGISTRY_NAME: &'static str = "secretstore_acl_checker";
+/// Signature of the ACL checker contract function, checking requester permissions.
+const CHECK_PERMISSIONS_SIGNATURE: &'static [u8] = b"checkPermissions(address,bytes32)";
+/// Signature of the ACL checker contract function, checking requester permissions for given purpose.
+const CHECK_PERMISSIONS_SCOPED_SIGNATURE: &'static [u8] = b"checkPermissionsScoped(address,bytes32,bytes32)";
+/// Size of ABI-encoded word.
+const ABI_WORD_SIZE: usize = 32;
+/// Default time, during which missing registry entry is not re-queried.
+const DEFAULT_REGISTRY_MISS_TTL_SECS: u64 = 10;
+/// Default time, during which registry is not re-queried after failed lookup.
+const DEFAULT_RESOLUTION_FAILURE_BACKOFF_MS: u64 = 1000;
+/// Default max number of candidate addresses, checked in addition to the canonical requester address.
+const DEFAULT_MAX_CANDIDATE_ADDRESSES: usize = 4;
+/// Default max size of the permissions check function output.
+const DEFAULT_MAX_OUTPUT_SIZE: usize = 64 * 1024;
+/// Default max number of outstanding timed contract calls.
+const DEFAULT_MAX_PENDING_CALLS: usize = 16;
+/// Domain separator of namespaced document addresses.
+const NAMESPACED_DOCUMENT_DOMAIN: &'static [u8] = b"secretstore_document";
+/// Version of the configuration fingerprint preimage. Must be changed when preimage format changes.
+const CONFIG_FINGERPRINT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Scheme, used to derive document address from the document name.
+pub enum DocumentAddressScheme {
+	/// Document address is keccak256 of the document name.
+	Keccak256,
+}
+
+/// Transform of the decision, returned by the ACL checker contract.
+pub type DecisionTransform = Arc<Fn(bool) -> bool + Send + Sync>;
+
+/// Derivation of the candidate addresses of the requester public key.
+pub type CandidateAddresses = Arc<Fn(&Public) -> Vec<Address> + Send + Sync>;
+
+#[derive(Clone)]
+/// On-chain ACL storage configuration.
+pub struct OnChainAclStorageConfig {
+	/// When set, callers must pass document names, which are hashed by the storage itself
+	/// using this scheme. Raw document addresses are rejected.
+	pub document_address_scheme: Option<DocumentAddressScheme>,
+	/// Time, during which the registry is not re-queried after contract has been found missing.
+	/// Zero duration means that the registry is queried on every check until the contract is found.
+	pub registry_miss_ttl: Duration,
+	/// Time, during which the registry is not re-queried after failed lookup.
+	/// Checks during this time fail with the error of the last lookup.
+	pub resolution_failure_backoff: Duration,
+	/// Index of the permissions check function output, holding the decision. Contracts may return
+	/// additional outputs (i.e. `(bool, uint, string)`), which are ignored. The decision output must be
+	/// preceded by single-word (or dynamic) outputs only.
+	pub decision_output_index: usize,
+	/// When set, contract output must be exactly a single bool: extra outputs and trailing bytes are rejected.
+	/// This catches contract interface drift. `decision_output_index` must be zero in this mode.
+	pub strict_decode: bool,
+	/// Transform, applied to the decoded decision. This is an interop shim for contracts with non-standard
+	/// decision convention (i.e. returning `true` when access is denied). None means that the decision is used as is.
+	pub decision_transform: Option<DecisionTransform>,
+	/// Derivation of additional addresses of the requester, i.e. addresses of previous derivation schemes during migration.
+	/// When set and requester public key is known, the contract is asked for the canonical address and then for every
+	/// candidate address, until access is granted. None means that only the canonical address is checked.
+	pub candidate_addresses: Option<CandidateAddresses>,
+	/// Max number of candidate addresses, checked in addition to the canonical address. Extra candidates are ignored,
+	/// so that single check makes bounded number of contract calls.
+	pub max_candidate_addresses: usize,
+	/// Addresses of trusted ACL checker contracts. When registry points to other contract, checks are failing with
+	/// `AclError::UntrustedContract`, so that compromised registry can't redirect checks. Empty means that registry is trusted.
+	pub trusted_contracts: Vec<Address>,
+	/// Max size of the permissions check function output. Larger outputs are rejected with `AclError::ResponseTooLarge`
+	/// before they are decoded. None means that output size is not limited.
+	pub max_output_size: Option<usize>,
+	/// Max duration of the permissions check contract call. Slower calls are failed, while the call itself
+	/// completes in background. None means that calls are not timed out.
+	pub call_timeout: Option<Duration>,
+	/// Max number of outstanding timed contract calls, including calls, which have timed out, but are still running.
+	/// When the limit is reached, checks are failing with `AclError::Overloaded`. Only used when `call_timeout` is set.
+	pub max_pending_calls: usize,
+	/// When set, requester public key is normalized and validated before the address is derived,
+	/// so that keys, which are not valid curve points, are rejected with `AclError::InvalidRequestor`.
+	pub normalize_requester: bool,
+}
+
+/// On-chain ACL storage implementation.
+pub struct OnChainAclStorage {
+	/// Blockchain client.
+	client: Arc<AclClient>,
+	/// Storage configuration.
+	config: OnChainAclStorageConfig,
+	/// Time source.
+	clock: Arc<Clock>,
+	/// On-chain contract. Lock is only held during resolution, contract calls are made without it.
+	contract: Mutex<Option<Arc<CachedContract>>>,
+	/// Last registry lookup, which has not resolved the contract.
+	last_failed_resolution: Mutex<Option<FailedResolution>>,
+	/// Outstanding timed contract calls.
+	calls: BackgroundCalls,
+}
+
+/// Registry lookup, which has not resolved the contract.
+struct FailedResolution {
+	/// Time of the lookup.
+	time: Instant,
+	/// Lookup error. None if registry has no contract entry.
+	error: Option<String>,
+	/// True if registry points to the contract, which is not trusted.
+	untrusted: bool,
+}
+
+/// Resolved on-chain contract with cached properties. Properties are dropped when contract is re-resolved.
+/// Checks hold their own reference to the contract, so that re-resolution does not affect in-flight checks.
+struct CachedContract {
+	/// Contract.
+	contract: SecretStoreAclStorage,
+	/// Contract administrator, if already read.
+	admin: Mutex<Option<Option<Address>>>,
+}
+
+impl Default for OnChainAclStorageConfig {
+	fn default() -> Self {
+		OnChainAclStorageConfig {
+			document_address_scheme: None,
+			registry_miss_ttl: Duration::from_secs(DEFAULT_REGISTRY_MISS_TTL_SECS),
+			resolution_failure_backoff: Duration::from_millis(DEFAULT_RESOLUTION_FAILURE_BACKOFF_MS),
+			decision_output_index: 0,
+			strict_decode: false,
+			decision_transform: None,
+			candidate_addresses: None,
+			max_candidate_addresses: DEFAULT_MAX_CANDIDATE_ADDRESSES,
+			trusted_contracts: Vec::new(),
+			max_output_size: Some(DEFAULT_MAX_OUTPUT_SIZE),
+			call_timeout: None,
+			max_pending_calls: DEFAULT_MAX_PENDING_CALLS,
+			normalize_requester: true,
+		}
+	}
+}
+
+impl fmt::Debug for OnChainAclStorageConfig {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_struct("OnChainAclStorageConfig")
+			.field("document_address_scheme", &self.document_address_scheme)
+			.field("registry_miss_ttl", &self.registry_miss_ttl)
+			.field("resolution_failure_backoff", &self.resolution_failure_backoff)
+			.field("decision_output_index", &self.decision_output_index)
+			.field("strict_decode", &self.strict_decode)
+			.field("decision_transform", &self.decision_transform.is_some())
+			.field("candidate_addresses", &self.candidate_addresses.is_some())
+			.field("max_candidate_addresses", &self.max_candidate_addresses)
+			.field("trusted_contracts", &self.trusted_contracts)
+			.field("max_output_size", &self.max_output_size)
+			.field("call_timeout", &self.call_timeout)
+			.field("max_pending_calls", &self.max_pending_calls)
+			.field("normalize_requester", &self.normalize_requester)
+			.finish()
+	}
+}
+
+impl DocumentAddressScheme {
+	/// Compute canonical address of the document with given name.
+	pub fn document_address(&self, name: &[u8]) -> DocumentAddress {
+		match *self {
+			DocumentAddressScheme::Keccak256 => name.sha3(),
+		}
+	}
+}
+
+/// Derive address of the document `name` of application `app_id`, so that documents of different applications
+/// never collide. Address is `keccak256("secretstore_document" ++ u32_be(len(app_id)) ++ app_id ++ name)`,
+/// where `len(app_id)` is the length of UTF-8 encoded `app_id` in bytes.
+pub fn namespaced_document(app_id: &str, name: &str) -> DocumentAddress {
+	let mut preimage = Vec::with_capacity(NAMESPACED_DOCUMENT_DOMAIN.len() + 4 + app_id.len() + name.len());
+	preimage.extend_from_slice(NAMESPACED_DOCUMENT_DOMAIN);
+	preimage.write_u32::<BigEndian>(app_id.len() as u32).expect("writing to vec never fails; qed");
+	preimage.extend_from_slice(app_id.as_bytes());
+	preimage.extend_from_slice(name.as_bytes());
+	preimage.sha3()
+}
+
+impl OnChainAclStorage {
+	pub fn new(client: Arc<AclClient>) -> Self {
+		OnChainAclStorage::with_config(client, Default::default())
+	}
+
+	pub fn with_config(client: Arc<AclClient>, config: OnChainAclStorageConfig) -> Self {
+		OnChainAclStorage::with_clock(client, config, Arc::new(SystemClock))
+	}
+
+	pub fn with_clock(client: Arc<AclClient>, config: OnChainAclStorageConfig, clock: Arc<Clock>) -> Self {
+		assert!(!config.strict_decode || config.decision_output_index == 0, "strict decoding requires decision to be the only output");
+		if config.decision_transform.is_some() {
+			warn!(target: "secretstore", "ACL checker contract decisions are transformed before use");
+		}
+
+		OnChainAclStorage {
+			client: client,
+			calls: BackgroundCalls::new(config.max_pending_calls),
+			config: config,
+			clock: clock,
+			contract: Mutex::new(None),
+			last_failed_resolution: Mutex::new(None),
+		}
+	}
+
+	/// Check if `requester` can access document with given name.
+	/// Document address is derived from the name using configured scheme.
+	pub fn check_document_name(&self, requester: &Requester, name: &[u8]) -> Result<bool, Error> {
+		match self.config.document_address_scheme {
+			Some(ref scheme) => self.check_document_address(requester, &scheme.document_address(name), None, BlockId::Latest),
+			None => Err(Error::Internal("document address scheme is not configured".to_owned())),
+		}
+	}
+
+	/// Get address of the ACL contract owner (administrator).
+	/// Returns None if contract has no `owner()` view.
+	pub fn admin(&self) -> Result<Option<Address>, Error> {
+		let contract = match self.current_contract()? {
+			Some(contract) => contract,
+			None => return Err(Error::Internal("ACL checker contract is not configured".to_owned())),
+		};
+
+		let mut cached_admin = contract.admin.lock();
+		if let Some(ref admin) = *cached_admin {
+			return Ok(admin.clone());
+		}
+
+		// None if call has not been made
+		let call_failed = Cell::new(None);
+		let do_call = |a, d| {
+			let result = self.client.call_contract(BlockId::Latest, a, d);
+			call_failed.set(Some(result.is_err()));
+			future::done(result)
+		};
+		let admin = match contract.contract.owner(do_call).wait() {
+			Ok(admin) => Some(admin),
+			Err(err) => match call_failed.get() {
+				None => return Err(Error::Internal(format!("owner() call has not been made: {}", err))),
+				Some(true) => return Err(AclError::Call(err).into()),
+				// contract has returned something, which is not an address => there's no owner() view
+				Some(false) => None,
+			},
+		};
+
+		info!(target: "secretstore", "ACL checker contract is administered by {:?}", admin);
+		*cached_admin = Some(admin.clone());
+		Ok(admin)
+	}
+
+	/// Re-resolve the contract from the registry, i.e. after it has been replaced. Checks, which are already
+	/// in flight, complete using the previous contract. If lookup fails, the previous contract is kept.
+	pub fn refresh_contract(&self) -> Result<(), Error> {
+		let mut contract = self.contract.lock();
+		*self.last_failed_resolution.lock() = None;
+		let mut refreshed = None;
+		self.resolve_contract(&mut refreshed)?;
+		*contract = refreshed;
+		Ok(())
+	}
+
+	/// Check if `requester` can access document with hash `document` at given block.
+	/// With `BlockId::Pending`, the check is made against the pending state, so that requester could verify that
+	/// just-submitted grant transaction will take effect. Pending state is the local view of the transactions queue,
+	/// so it may differ across nodes.
+	pub fn check_at(&self, requester: &Requester, document: &DocumentAddress, block: BlockId) -> Result<bool, Error> {
+		self.ensure_raw_document_addresses_accepted()?;
+		self.check_document_address(requester, document, None, block)
+	}
+
+	/// Check if `requester` can access document with hash `document` for given purpose at given block.
+	/// Empty scope is the same as plain check.
+	pub fn check_scoped_at(&self, requester: &Requester, document: &DocumentAddress, scope: &str, block: BlockId) -> Result<bool, Error> {
+		if scope.is_empty() {
+			return self.check_at(requester, document, block);
+		}
+
+		self.ensure_raw_document_addresses_accepted()?;
+		self.check_document_address(requester, document, Some(&scope.as_bytes().sha3()), block)
+	}
+
+	/// Get stable fingerprint of the effective configuration: resolved contract address, contract ABI, registry name
+	/// and every setting, affecting decisions. Nodes with identical configuration have identical fingerprints, so
+	/// fingerprints could be compared across the fleet. Contract is not resolved here: unresolved contract is
+	/// fingerprinted as zero address. Only presence of the decision transform and candidate addresses derivation is
+	/// fingerprinted, since functions could not be hashed.
+	pub fn config_fingerprint(&self) -> H256 {
+		let contract_address = self.contract.lock().as_ref().map(|contract| contract.contract.address.clone());
+		config_fingerprint(&self.config, &contract_address.unwrap_or_default())
+	}
+
+	fn ensure_raw_document_addresses_accepted(&self) -> Result<(), Error> {
+		if self.config.document_address_scheme.is_some() {
+			return Err(Error::Internal("raw document addresses are not accepted: document name is required".to_owned()));
+		}
+
+		Ok(())
+	}
+
+	/// Check if `requester` can access document with hash `document` at given block. When `scope` (keccak256 of
+	/// the check purpose) is given, the contract is asked for permissions of this purpose only.
+	fn check_document_address(&self, requester: &Requester, document: &DocumentAddress, scope: Option<&H256>, block: BlockId) -> Result<bool, Error> {
+		let addresses = self.requester_addresses(requester, document)?;
+		if let Some(contract) = self.current_contract()? {
+			for address in addresses {
+				if self.check_address(&contract.contract.address, &address, document, scope, block)? {
+					return Ok(true);
+				}
+			}
+			Ok(false)
+		} else {
+			Err(Error::Internal("ACL checker contract is not configured".to_owned()))
+		}
+	}
+
+	/// Get addresses of the requester, which are checked by the contract: canonical address goes first,
+	/// followed by configured candidate addresses.
+	fn requester_addresses(&self, requester: &Requester, document: &DocumentAddress) -> Result<Vec<Address>, Error> {
+		let requester = if self.config.normalize_requester {
+			requester.normalized()?.resolve(document)?
+		} else {
+			requester.resolve(document)?
+		};
+		let mut addresses = vec![requester.address.clone()];
+		let candidates = match (self.config.candidate_addresses.as_ref(), requester.public.as_ref()) {
+			(Some(candidate_addresses), Some(public)) => candidate_addresses(public),
+			_ => return Ok(addresses),
+		};
+
+		if candidates.len() > self.config.max_candidate_addresses {
+			warn!(target: "secretstore", "{} candidate addresses are derived for the requester, only first {} are checked",
+				candidates.len(), self.config.max_candidate_addresses);
+		}
+		for candidate in candidates.into_iter().take(self.config.max_candidate_addresses) {
+			if !addresses.contains(&candidate) {
+				addresses.push(candidate);
+			}
+		}
+		Ok(addresses)
+	}
+
+	/// Ask the contract if `address` can access the `document`.
+	fn check_address(&self, contract: &Address, address: &Address, document: &DocumentAddress, scope: Option<&H256>, block: BlockId) -> Result<bool, Error> {
+		let call = match scope {
+			Some(scope) => encode_check_permissions_scoped(address, document, scope),
+			None => encode_check_permissions(address, document),
+		};
+		let output = self.call_contract(block, contract, call)?;
+		let granted = if self.config.strict_decode {
+			decode_strict_check_permissions(&output)?
+		} else {
+			decode_check_permissions(&output, self.config.decision_output_index)?
+		};
+		match self.config.decision_transform {
+			Some(ref transform) => {
+				let transformed = transform(granted);
+				trace!(target: "secretstore", "ACL checker contract decision {} is transformed to {}", granted, transformed);
+				Ok(transformed)
+			},
+			None => Ok(granted),
+		}
+	}
+
+	/// Call the contract, enforcing configured output size and call duration limits.
+	fn call_contract(&self, block: BlockId, contract: &Address, data: Bytes) -> Result<Bytes, Error> {
+		let output = match self.config.call_timeout {
+			Some(call_timeout) => {
+				let client = self.client.clone();
+				let contract = contract.clone();
+				match self.calls.call(call_timeout, move || client.call_contract(block, contract, data))? {
+					Some(output) => output,
+					None => Err("contract call has timed out".to_owned()),
+				}
+			},
+			None => self.client.call_contract(block, contract.clone(), data),
+		}.map_err(AclError::Call)?;
+
+		match self.config.max_output_size {
+			Some(max_output_size) if output.len() > max_output_size => {
+				warn!(target: "secretstore", "ACL checker contract has returned {} bytes, while at most {} bytes are accepted", output.len(), max_output_size);
+				Err(AclError::ResponseTooLarge.into())
+			},
+			_ => Ok(output),
+		}
+	}
+
+	/// Get resolved contract, resolving it if required. The contract lock is released before return.
+	fn current_contract(&self) -> Result<Option<Arc<CachedContract>>, Error> {
+		let mut contract = self.contract.lock();
+		self.resolve_contract(&mut *contract)?;
+		Ok(contract.clone())
+	}
+
+	/// Resolve contract address from the registry, if it is not yet resolved.
+	/// Missing contract is not re-queried until configured TTL passes. Failed lookup is not retried
+	/// until configured backoff passes. Since resolution happens under the contract lock, concurrent
+	/// checks share a single lookup.
+	fn resolve_contract(&self, contract: &mut Option<Arc<CachedContract>>) -> Result<(), Error> {
+		if contract.is_some() {
+			return Ok(());
+		}
+
+		let mut last_failed_resolution = self.last_failed_resolution.lock();
+		let now = self.clock.now();
+		if let Some(ref failed_resolution) = *last_failed_resolution {
+			match failed_resolution.error {
+				None if now < failed_resolution.time + self.config.registry_miss_ttl => return Ok(()),
+				Some(_) if failed_resolution.untrusted && now < failed_resolution.time + self.config.resolution_failure_backoff =>
+					return Err(AclError::UntrustedContract.into()),
+				Some(ref error) if now < failed_resolution.time + self.config.resolution_failure_backoff =>
+					return Err(Error::Internal(format!("ACL checker contract resolution has failed: {}", error))),
+				_ => (),
+			}
+		}
+
+		match self.client.registry_address(ACL_CHECKER_CONTRACT_REGISTRY_NAME.to_owned()) {
+			Ok(Some(contract_addr)) => {
+				if !self.config.trusted_contracts.is_empty() && !self.config.trusted_contracts.contains(&contract_addr) {
+					warn!(target: "secretstore", "Registry points to untrusted ACL checker contract {}", contract_addr);
+					*last_failed_resolution = Some(FailedResolution {
+						time: now,
+						error: Some(format!("registry points to untrusted contract {}", contract_addr)),
+						untrusted: true,
+					});
+					return Err(AclError::UntrustedContract.into());
+				}
+
+				trace!(target: "secretstore", "Configuring for ACL checker contract from {}", contract_addr);
+
+				*contract = Some(Arc::new(CachedContract {
+					contract: SecretStoreAclStorage::new(contract_addr),
+					admin: Mutex::new(None),
+				}));
+				*last_failed_resolution = None;
+				Ok(())
+			},
+			Ok(None) => {
+				*last_failed_resolution = Some(FailedResolution {
+					time: now,
+					error: None,
+					untrusted: false,
+				});
+				Ok(())
+			},
+			Err(error) => {
+				warn!(target: "secretstore", "Failed to resolve ACL checker contract: {}", error);
+				*last_failed_resolution = Some(FailedResolution {
+					time: now,
+					error: Some(error.clone()),
+					untrusted: false,
+				});
+				Err(Error::Internal(format!("ACL checker contract resolution has failed: {}", error)))
+			},
+		}
+	}
+}
+
+/// Encode call of the permissions check function. The call is encoded here rather than by `ethabi`,
+/// so that the calldata does not depend on the `ethabi` version. Any change to the produced calldata
+/// is caught by `check_permissions_calldata_is_pinned` test.
+fn encode_check_permissions(address: &Address, document: &DocumentAddress) -> Bytes {
+	let mut data = Vec::with_capacity(4 + 2 * ABI_WORD_SIZE);
+	data.extend_from_slice(&CHECK_PERMISSIONS_SIGNATURE.sha3()[..4]);
+	data.extend_from_slice(&[0; ABI_WORD_SIZE - 20]);
+	data.extend_from_slice(&**address);
+	data.extend_from_slice(&**document);
+	data
+}
+
+/// Encode call of the scoped permissions check function. See `encode_check_permissions`.
+fn encode_check_permissions_scoped(address: &Address, document: &DocumentAddress, scope: &H256) -> Bytes {
+	let mut data = Vec::with_capacity(4 + 3 * ABI_WORD_SIZE);
+	data.extend_from_slice(&CHECK_PERMISSIONS_SCOPED_SIGNATURE.sha3()[..4]);
+	data.extend_from_slice(&[0; ABI_WORD_SIZE - 20]);
+	data.extend_from_slice(&**address);
+	data.extend_from_slice(&**document);
+	data.extend_from_slice(&**scope);
+	data
+}
+
+/// Compute fingerprint of the configuration with given resolved contract address.
+fn config_fingerprint(config: &OnChainAclStorageConfig, contract_address: &Address) -> H256 {
+	fn write_bytes(data: &mut Vec<u8>, bytes: &[u8]) {
+		data.write_u32::<BigEndian>(bytes.len() as u32).expect("writing to vec never fails; qed");
+		data.extend_from_slice(bytes);
+	}
+	fn write_duration(data: &mut Vec<u8>, duration: &Duration) {
+		data.write_u64::<BigEndian>(duration.as_secs()).expect("writing to vec never fails; qed");
+		data.write_u32::<BigEndian>(duration.subsec_nanos()).expect("writing to vec never fails; qed");
+	}
+	fn write_usize(data: &mut Vec<u8>, value: usize) {
+		data.write_u64::<BigEndian>(value as u64).expect("writing to vec never fails; qed");
+	}
+
+	let mut data = Vec::new();
+	data.write_u32::<BigEndian>(CONFIG_FINGERPRINT_VERSION).expect("writing to vec never fails; qed");
+	data.extend_from_slice(&**contract_address);
+	write_bytes(&mut data, ACL_CHECKER_CONTRACT_REGISTRY_NAME.as_bytes());
+	write_bytes(&mut data, CHECK_PERMISSIONS_SIGNATURE);
+	write_bytes(&mut data, CHECK_PERMISSIONS_SCOPED_SIGNATURE);
+	data.push(match config.document_address_scheme {
+		None => 0,
+		Some(DocumentAddressScheme::Keccak256) => 1,
+	});
+	write_duration(&mut data, &config.registry_miss_ttl);
+	write_duration(&mut data, &config.resolution_failure_backoff);
+	write_usize(&mut data, config.decision_output_index);
+	data.push(config.strict_decode as u8);
+	data.push(config.decision_transform.is_some() as u8);
+	data.push(config.candidate_addresses.is_some() as u8);
+	write_usize(&mut data, config.max_candidate_addresses);
+	// order of trusted contracts does not affect decisions
+	let mut trusted_contracts = config.trusted_contracts.clone();
+	trusted_contracts.sort();
+	trusted_contracts.dedup();
+	write_usize(&mut data, trusted_contracts.len());
+	for trusted_contract in &trusted_contracts {
+		data.extend_from_slice(&**trusted_contract);
+	}
+	match config.max_output_size {
+		Some(max_output_size) => {
+			data.push(1);
+			write_usize(&mut data, max_output_size);
+		},
+		None => data.push(0),
+	}
+	match config.call_timeout {
+		Some(ref call_timeout) => {
+			data.push(1);
+			write_duration(&mut data, call_timeout);
+		},
+		None => data.push(0),
+	}
+	data.push(config.normalize_requester as u8);
+	data.sha3()
+}
+
+/// Decode decision from the output of the permissions check function.
+/// Decision is read from the `index`-th output, which must be a strictly-encoded bool.
+fn decode_check_permissions(output: &[u8], index: usize) -> Result<bool, Error> {
+	let begin = index * ABI_WORD_SIZE;
+	if output.len() < begin + ABI_WORD_SIZE {
+		return Err(AclError::Decode(format!("ACL checker contract has returned {} bytes, while decision is expected at output {}", output.len(), index)).into());
+	}
+
+	let word = &output[begin..begin + ABI_WORD_SIZE];
+	if word[..ABI_WORD_SIZE - 1].iter().any(|b| *b != 0) || word[ABI_WORD_SIZE - 1] > 1 {
+		return Err(AclError::Decode(format!("ACL checker contract has returned invalid bool at output {}", index)).into());
+	}
+
+	Ok(word[ABI_WORD_SIZE - 1] == 1)
+}
+
+/// Decode decision from the output of the permissions check function, which must be exactly a single bool.
+fn decode_strict_check_permissions(output: &[u8]) -> Result<bool, Error> {
+	if output.len() != ABI_WORD_SIZE {
+		return Err(AclError::Decode(format!("ACL checker contract has returned {} bytes, while single bool is expected", output.len())).into());
+	}
+
+	decode_check_permissions(output, 0)
+}
+
+/// The contract has no enumeration view, so `AclStorage::list_requestors` is not supported.
+impl AclStorage for OnChainAclStorage {
+	fn check(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		self.check_at(requester, document, BlockId::Latest)
+	}
+
+	fn check_scoped(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<bool, Error> {
+		self.check_scoped_at(requester, document, scope, BlockId::Latest)
+	}
+
+	fn check_with_block(&self, requester: &Requester, document: &DocumentAddress) -> Result<(bool, Option<DecisionBlock>), Error> {
+		let block = self.client.best_block();
+		let granted = self.check_at(requester, document, BlockId::Hash(block.hash.clone()))?;
+		Ok((granted, Some(block)))
+	}
+
+	fn fill_report(&self, report: &mut AclReport) {
+		report.contract_address = self.contract.lock().as_ref().map(|contract| contract.contract.address.clone().into());
+		report.contract_resolution_error = self.last_failed_resolution.lock().as_ref().and_then(|resolution| resolution.error.clone());
+		report.config_fingerprint = Some(self.config_fingerprint().into());
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::sync::atomic::{AtomicBool, Ordering};
+	use std::thread;
+	use std::cell::RefCell;
+	use std::time::{Duration, Instant};
+	use futures::{future, Future};
+	use parking_lot::Mutex;
+	use rustc_serialize::hex::ToHex;
+	use ethkey::{Random, Generator, public_to_address};
+	use ethcore::client::BlockId;
+	use native_contracts::SecretStoreAclStorage;
+	use util::{Address, Bytes, H256, Hashable};
+	use types::all::{Error, AclError, DocumentAddress, Public, Requester};
+	use acl_storage::{AclStorage, AclClient, AclReport, DecisionBlock};
+	use acl_storage::tests::{DummyAclClient, encode_bool};
+	use acl_storage::clock::tests::MockClock;
+	use super::{OnChainAclStorage, OnChainAclStorageConfig, DocumentAddressScheme, namespaced_document,
+		encode_check_permissions, encode_check_permissions_scoped, decode_check_permissions, decode_strict_check_permissions};
+
+	fn golden_address() -> Address {
+		"0123456789abcdef0123456789abcdef01234567".parse().unwrap()
+	}
+
+	fn golden_document() -> DocumentAddress {
+		"00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff".parse().unwrap()
+	}
+
+	#[test]
+	fn check_permissions_calldata_is_pinned() {
+		// this is the wire format of deployed ACL contracts: it must never change
+		assert_eq!(encode_check_permissions(&golden_address(), &golden_document()).to_hex(), concat!(
+			"b36a9a7c",
+			"0000000000000000000000000123456789abcdef0123456789abcdef01234567",
+			"00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff"));
+	}
+
+	#[test]
+	fn check_permissions_scoped_calldata_is_pinned() {
+		assert_eq!(encode_check_permissions_scoped(&golden_address(), &golden_document(), &b"decrypt".sha3()).to_hex(), concat!(
+			"0b687984",
+			"0000000000000000000000000123456789abcdef0123456789abcdef01234567",
+			"00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff",
+			"d81e391d4cbec3cd1f61ccd5e8a6a38d2324dce27b9ab7bd305d6cc6e28e8262"));
+	}
+
+	#[test]
+	fn scoped_check_calls_scoped_function() {
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(1))));
+		let acl_storage = OnChainAclStorage::new(client.clone());
+		let requester = Requester::Address(golden_address());
+
+		assert_eq!(acl_storage.check_scoped(&requester, &golden_document(), "decrypt"), Ok(true));
+		assert_eq!(acl_storage.check_scoped(&requester, &golden_document(), ""), Ok(true));
+		let calls = client.calls.lock();
+		assert_eq!(calls[0].2, encode_check_permissions_scoped(&golden_address(), &golden_document(), &b"decrypt".sha3()));
+		// empty scope is the plain check
+		assert_eq!(calls[1].2, encode_check_permissions(&golden_address(), &golden_document()));
+	}
+
+	#[test]
+	fn check_permissions_calldata_matches_ethabi_encoding() {
+		let calldata = RefCell::new(Vec::new());
+		let contract = SecretStoreAclStorage::new(Address::from(1));
+		let granted = contract.check_permissions(|_, data| {
+			*calldata.borrow_mut() = data;
+			future::ok::<_, String>(encode_bool(true))
+		}, golden_address(), golden_document()).wait();
+
+		assert_eq!(granted, Ok(true));
+		assert_eq!(*calldata.borrow(), encode_check_permissions(&golden_address(), &golden_document()));
+	}
+
+	#[test]
+	fn raw_document_address_is_accepted_by_default() {
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(1))));
+		let acl_storage = OnChainAclStorage::new(client.clone());
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+		assert!(acl_storage.check_document_name(&requester, b"document").is_err());
+	}
+
+	#[test]
+	fn raw_document_address_is_rejected_when_scheme_is_configured() {
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(1))));
+		let acl_storage = OnChainAclStorage::with_config(client.clone(), OnChainAclStorageConfig {
+			document_address_scheme: Some(DocumentAddressScheme::Keccak256),
+			..Default::default()
+		});
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+
+		assert!(acl_storage.check(&requester, &DocumentAddress::from(1)).is_err());
+		assert!(client.calls.lock().is_empty());
+
+		assert_eq!(acl_storage.check_document_name(&requester, b"document"), Ok(true));
+		let calls = client.calls.lock();
+		assert_eq!(calls.len(), 1);
+		// checkPermissions(address, bytes32): selector + address word + document word
+		assert_eq!(&calls[0].2[36..68], &*b"document".sha3());
+	}
+
+	fn encode_address(address: Address) -> Vec<u8> {
+		let mut encoded = vec![0; 32];
+		encoded[12..].copy_from_slice(&*address);
+		encoded
+	}
+
+	#[test]
+	fn admin_is_read_once() {
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(1))));
+		*client.call_result.lock() = Ok(encode_address(Address::from(2)));
+		let acl_storage = OnChainAclStorage::new(client.clone());
+
+		assert_eq!(acl_storage.admin(), Ok(Some(Address::from(2))));
+		assert_eq!(acl_storage.admin(), Ok(Some(Address::from(2))));
+		assert_eq!(client.calls.lock().len(), 1);
+	}
+
+	#[test]
+	fn admin_is_none_when_contract_has_no_owner_view() {
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(1))));
+		*client.call_result.lock() = Ok(Vec::new());
+		let acl_storage = OnChainAclStorage::new(client.clone());
+		assert_eq!(acl_storage.admin(), Ok(None));
+
+		// but call errors are reported
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(1))));
+		*client.call_result.lock() = Err("call failed".into());
+		let acl_storage = OnChainAclStorage::new(client.clone());
+		assert!(acl_storage.admin().is_err());
+		*client.call_result.lock() = Ok(encode_address(Address::from(2)));
+		assert_eq!(acl_storage.admin(), Ok(Some(Address::from(2))));
+	}
+
+	#[test]
+	fn missing_contract_is_not_requeried_within_ttl() {
+		let client = Arc::new(DummyAclClient::new(None));
+		let clock = Arc::new(MockClock::default());
+		let acl_storage = OnChainAclStorage::with_clock(client.clone(), OnChainAclStorageConfig {
+			registry_miss_ttl: Duration::from_secs(10),
+			..Default::default()
+		}, clock.clone());
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+
+		assert!(acl_storage.check(&requester, &DocumentAddress::from(1)).is_err());
+		assert!(acl_storage.check(&requester, &DocumentAddress::from(1)).is_err());
+		assert_eq!(client.registry_lookups_count(), 1);
+
+		// contract is deployed later and picked up when TTL passes
+		*client.contract_address.lock() = Some(Address::from(1));
+		clock.advance(Duration::from_secs(9));
+		assert!(acl_storage.check(&requester, &DocumentAddress::from(1)).is_err());
+		clock.advance(Duration::from_secs(1));
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+		assert_eq!(client.registry_lookups_count(), 2);
+	}
+
+	#[test]
+	fn concurrent_checks_share_failed_resolution() {
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(1))));
+		*client.registry_error.lock() = Some("registry is unavailable".into());
+		let clock = Arc::new(MockClock::default());
+		let acl_storage = Arc::new(OnChainAclStorage::with_clock(client.clone(), OnChainAclStorageConfig {
+			resolution_failure_backoff: Duration::from_secs(1),
+			..Default::default()
+		}, clock.clone()));
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+
+		let handles: Vec<_> = (0..8).map(|_| {
+			let acl_storage = acl_storage.clone();
+			let requester = requester.clone();
+			thread::spawn(move || acl_storage.check(&requester, &DocumentAddress::from(1)))
+		}).collect();
+		for handle in handles {
+			assert!(handle.join().unwrap().is_err());
+		}
+		assert_eq!(client.registry_lookups_count(), 1);
+
+		// registry recovers and is re-queried after backoff
+		*client.registry_error.lock() = None;
+		clock.advance(Duration::from_secs(1));
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+		assert_eq!(client.registry_lookups_count(), 2);
+	}
+
+	#[test]
+	fn concurrent_first_checks_share_contract_resolution() {
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(1))));
+		*client.registry_delay.lock() = Some(Duration::from_millis(50));
+		let acl_storage = Arc::new(OnChainAclStorage::new(client.clone()));
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+
+		let handles: Vec<_> = (0..8u64).map(|i| {
+			let acl_storage = acl_storage.clone();
+			let requester = requester.clone();
+			thread::spawn(move || acl_storage.check(&requester, &DocumentAddress::from(i)))
+		}).collect();
+		for handle in handles {
+			assert_eq!(handle.join().unwrap(), Ok(true));
+		}
+		assert_eq!(client.registry_lookups_count(), 1);
+		assert_eq!(client.calls.lock().len(), 8);
+	}
+
+	#[test]
+	fn contract_is_refreshed_while_check_is_in_flight() {
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(1))));
+		*client.call_delay.lock() = Some(Duration::from_millis(300));
+		let acl_storage = Arc::new(OnChainAclStorage::new(client.clone()));
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+
+		let is_completed = Arc::new(AtomicBool::new(false));
+		let handle = {
+			let (acl_storage, requester, is_completed) = (acl_storage.clone(), requester.clone(), is_completed.clone());
+			thread::spawn(move || {
+				let result = acl_storage.check(&requester, &DocumentAddress::from(1));
+				is_completed.store(true, Ordering::SeqCst);
+				result
+			})
+		};
+		while client.calls.lock().is_empty() {
+			thread::sleep(Duration::from_millis(1));
+		}
+
+		// contract is replaced, refresh is not blocked by the slow check
+		*client.call_delay.lock() = None;
+		*client.contract_address.lock() = Some(Address::from(2));
+		assert_eq!(acl_storage.refresh_contract(), Ok(()));
+		assert!(!is_completed.load(Ordering::SeqCst));
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(2)), Ok(true));
+
+		// in-flight check has completed using the old contract
+		assert_eq!(handle.join().unwrap(), Ok(true));
+		let calls = client.calls.lock();
+		assert_eq!(calls.len(), 2);
+		assert_eq!(calls[0].1, Address::from(1));
+		assert_eq!(calls[1].1, Address::from(2));
+	}
+
+	#[test]
+	fn failed_refresh_keeps_previous_contract() {
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(1))));
+		let acl_storage = OnChainAclStorage::new(client.clone());
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+
+		*client.registry_error.lock() = Some("registry is unavailable".into());
+		assert!(acl_storage.refresh_contract().is_err());
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+		assert_eq!(client.calls.lock().last().unwrap().1, Address::from(1));
+	}
+
+	#[test]
+	fn only_trusted_contract_is_called() {
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(1))));
+		let acl_storage = OnChainAclStorage::with_config(client.clone(), OnChainAclStorageConfig {
+			trusted_contracts: vec![Address::from(1), Address::from(2)],
+			..Default::default()
+		});
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+
+		// registry is redirected to untrusted contract => previous contract is kept
+		*client.contract_address.lock() = Some(Address::from(3));
+		assert_eq!(acl_storage.refresh_contract(), Err(Error::Acl(AclError::UntrustedContract)));
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+		assert!(client.calls.lock().iter().all(|call| call.1 == Address::from(1)));
+	}
+
+	#[test]
+	fn oversized_output_is_rejected() {
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(1))));
+		let acl_storage = OnChainAclStorage::with_config(client.clone(), OnChainAclStorageConfig {
+			max_output_size: Some(64),
+			..Default::default()
+		});
+		let requester = Requester::Address(Address::from(2));
+
+		// (bool, bytes32) still fits
+		let mut output = encode_bool(true);
+		output.extend_from_slice(&[0; 32]);
+		*client.call_result.lock() = Ok(output.clone());
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+
+		output.push(0);
+		*client.call_result.lock() = Ok(output);
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Err(Error::Acl(AclError::ResponseTooLarge)));
+	}
+
+	#[test]
+	fn slow_call_is_timed_out() {
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(1))));
+		let acl_storage = OnChainAclStorage::with_config(client.clone(), OnChainAclStorageConfig {
+			call_timeout: Some(Duration::from_millis(20)),
+			..Default::default()
+		});
+		let requester = Requester::Address(Address::from(2));
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+
+		*client.call_delay.lock() = Some(Duration::from_secs(1));
+		let started_at = Instant::now();
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Err(Error::Acl(AclError::Call("contract call has timed out".into()))));
+		assert!(started_at.elapsed() < Duration::from_secs(1));
+	}
+
+	#[test]
+	fn timed_out_calls_are_limited() {
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(1))));
+		let acl_storage = OnChainAclStorage::with_config(client.clone(), OnChainAclStorageConfig {
+			call_timeout: Some(Duration::from_millis(10)),
+			max_pending_calls: 2,
+			..Default::default()
+		});
+		let requester = Requester::Address(Address::from(2));
+
+		*client.call_delay.lock() = Some(Duration::from_millis(500));
+		for _ in 0..2 {
+			assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Err(Error::Acl(AclError::Call("contract call has timed out".into()))));
+		}
+		// timed out calls are still running
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Err(Error::Acl(AclError::Overloaded)));
+
+		*client.call_delay.lock() = None;
+		let started_at = Instant::now();
+		while acl_storage.check(&requester, &DocumentAddress::from(1)) != Ok(true) {
+			assert!(started_at.elapsed() < Duration::from_secs(5));
+			thread::sleep(Duration::from_millis(10));
+		}
+	}
+
+	#[test]
+	fn untrusted_contract_is_not_called() {
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(3))));
+		let acl_storage = OnChainAclStorage::with_config(client.clone(), OnChainAclStorageConfig {
+			trusted_contracts: vec![Address::from(1)],
+			..Default::default()
+		});
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Err(Error::Acl(AclError::UntrustedContract)));
+		assert!(client.calls.lock().is_empty());
+	}
+
+	#[test]
+	fn untrusted_contract_resolution_is_backed_off() {
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(3))));
+		let clock = Arc::new(MockClock::default());
+		let acl_storage = OnChainAclStorage::with_clock(client.clone(), OnChainAclStorageConfig {
+			trusted_contracts: vec![Address::from(1)],
+			resolution_failure_backoff: Duration::from_secs(30),
+			..Default::default()
+		}, clock.clone());
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Err(Error::Acl(AclError::UntrustedContract)));
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Err(Error::Acl(AclError::UntrustedContract)));
+		assert_eq!(client.registry_lookups_count(), 1);
+
+		let mut report = AclReport::default();
+		acl_storage.fill_report(&mut report);
+		assert!(report.contract_resolution_error.is_some());
+
+		// registry is re-queried when backoff passes
+		*client.contract_address.lock() = Some(Address::from(1));
+		clock.advance(Duration::from_secs(30));
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+		assert_eq!(client.registry_lookups_count(), 2);
+	}
+
+	#[test]
+	fn config_fingerprint_is_stable() {
+		let config = || OnChainAclStorageConfig {
+			trusted_contracts: vec![Address::from(1), Address::from(2)],
+			call_timeout: Some(Duration::from_secs(5)),
+			..Default::default()
+		};
+		let acl_storage1 = OnChainAclStorage::with_config(Arc::new(DummyAclClient::new(Some(Address::from(1)))), config());
+		let acl_storage2 = OnChainAclStorage::with_config(Arc::new(DummyAclClient::new(Some(Address::from(1)))), config());
+		assert_eq!(acl_storage1.config_fingerprint(), acl_storage2.config_fingerprint());
+		assert_eq!(acl_storage1.config_fingerprint(), acl_storage1.config_fingerprint());
+
+		// order of trusted contracts does not matter
+		let acl_storage3 = OnChainAclStorage::with_config(Arc::new(DummyAclClient::new(Some(Address::from(1)))), OnChainAclStorageConfig {
+			trusted_contracts: vec![Address::from(2), Address::from(1)],
+			..config()
+		});
+		assert_eq!(acl_storage1.config_fingerprint(), acl_storage3.config_fingerprint());
+
+		// changed setting changes fingerprint
+		let acl_storage4 = OnChainAclStorage::with_config(Arc::new(DummyAclClient::new(Some(Address::from(1)))), OnChainAclStorageConfig {
+			strict_decode: true,
+			..config()
+		});
+		assert!(acl_storage1.config_fingerprint() != acl_storage4.config_fingerprint());
+		let acl_storage5 = OnChainAclStorage::with_config(Arc::new(DummyAclClient::new(Some(Address::from(1)))), OnChainAclStorageConfig {
+			call_timeout: None,
+			..config()
+		});
+		assert!(acl_storage1.config_fingerprint() != acl_storage5.config_fingerprint());
+
+		// resolved contract changes fingerprint
+		let fingerprint = acl_storage1.config_fingerprint();
+		acl_storage1.check(&Requester::Address(Address::from(3)), &DocumentAddress::from(1)).unwrap();
+		assert!(acl_storage1.config_fingerprint() != fingerprint);
+		assert_eq!(acl_storage1.report().config_fingerprint.map(|fingerprint| (*fingerprint).clone()), Some(acl_storage1.config_fingerprint()));
+	}
+
+	#[test]
+	fn invalid_requester_is_rejected() {
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(1))));
+		*client.call_result.lock() = Ok(encode_bool(true));
+		let acl_storage = OnChainAclStorage::new(client.clone());
+		let requester = Requester::Public(Public::default());
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Err(Error::Acl(AclError::InvalidRequestor)));
+		assert!(client.calls.lock().is_empty());
+
+		// normalization could be disabled
+		let acl_storage = OnChainAclStorage::with_config(client.clone(), OnChainAclStorageConfig {
+			normalize_requester: false,
+			..Default::default()
+		});
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+	}
+
+	fn encode_uint(value: u8) -> Vec<u8> {
+		let mut encoded = vec![0; 32];
+		encoded[31] = value;
+		encoded
+	}
+
+	#[test]
+	fn decision_is_read_from_configured_output() {
+		// (bool, uint, string) = (true, 5, "ok")
+		let mut output = encode_bool(true);
+		output.extend(encode_uint(5));
+		output.extend(encode_uint(96));
+		output.extend(encode_uint(2));
+		let mut string = vec![0; 32];
+		string[..2].copy_from_slice(b"ok");
+		output.extend(string);
+
+		assert_eq!(decode_check_permissions(&output, 0), Ok(true));
+		// uint(5) is not a bool
+		assert!(decode_check_permissions(&output, 1).is_err());
+		// there are 5 words in the output
+		assert!(decode_check_permissions(&output, 5).is_err());
+		// (uint, bool) = (2, false)
+		let mut output = encode_uint(2);
+		output.extend(encode_bool(false));
+		assert_eq!(decode_check_permissions(&output, 1), Ok(false));
+		// empty output
+		assert!(decode_check_permissions(&[], 0).is_err());
+	}
+
+	#[test]
+	fn trailing_outputs_are_ignored() {
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(1))));
+		let mut output = encode_bool(false);
+		output.extend(encode_bool(true));
+		*client.call_result.lock() = Ok(output);
+		let acl_storage = OnChainAclStorage::new(client.clone());
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(false));
+		let acl_storage = OnChainAclStorage::with_config(client.clone(), OnChainAclStorageConfig {
+			decision_output_index: 1,
+			..Default::default()
+		});
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+	}
+
+	#[test]
+	fn pending_block_is_forwarded_to_client() {
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(1))));
+		let acl_storage = OnChainAclStorage::new(client.clone());
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+
+		assert_eq!(acl_storage.check_at(&requester, &DocumentAddress::from(1), BlockId::Pending), Ok(true));
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+		let calls = client.calls.lock();
+		assert_eq!(calls[0].0, BlockId::Pending);
+		assert_eq!(calls[1].0, BlockId::Latest);
+	}
+
+	#[test]
+	fn call_and_decode_failures_are_distinguished() {
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(1))));
+		let acl_storage = OnChainAclStorage::new(client.clone());
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+
+		*client.call_result.lock() = Err("call failed".into());
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Err(Error::Acl(AclError::Call("call failed".into()))));
+		assert_eq!(acl_storage.admin(), Err(Error::Acl(AclError::Call("call failed".into()))));
+
+		*client.call_result.lock() = Ok(Vec::new());
+		match acl_storage.check(&requester, &DocumentAddress::from(1)) {
+			Err(Error::Acl(AclError::Decode(_))) => (),
+			result => panic!("unexpected check result: {:?}", result),
+		}
+		*client.call_result.lock() = Ok(encode_uint(2));
+		match acl_storage.check(&requester, &DocumentAddress::from(2)) {
+			Err(Error::Acl(AclError::Decode(_))) => (),
+			result => panic!("unexpected check result: {:?}", result),
+		}
+	}
+
+	#[test]
+	fn decision_transform_is_applied() {
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(1))));
+		let acl_storage = OnChainAclStorage::with_config(client.clone(), OnChainAclStorageConfig {
+			decision_transform: Some(Arc::new(|granted: bool| !granted)),
+			..Default::default()
+		});
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(false));
+		*client.call_result.lock() = Ok(encode_bool(false));
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+
+		// decode errors are not transformed
+		*client.call_result.lock() = Ok(Vec::new());
+		assert!(acl_storage.check(&requester, &DocumentAddress::from(1)).is_err());
+	}
+
+	#[test]
+	fn namespaced_document_test_vectors() {
+		assert_eq!(namespaced_document("app", "document"), "9171b0094e38da2b307598014afbc4359a36175ddf4600b8ed45f2de3c18fc6f".parse::<DocumentAddress>().unwrap());
+		assert_eq!(namespaced_document("", ""), "0c11d245091693c0bd3f7939f5d753e1c89e1b40d8fba56130781b85b06a77b7".parse::<DocumentAddress>().unwrap());
+		// concatenation of app id and name is the same, but addresses differ
+		assert_eq!(namespaced_document("ap", "pdocument"), "2cfa67a408c6c6e03cea4c57bc82cb10e0d300ab99fab0a44ae6c841f617fbcb".parse::<DocumentAddress>().unwrap());
+	}
+
+	#[test]
+	fn strict_decoding_rejects_non_single_bool_outputs() {
+		let is_decode_error = |result: Result<bool, Error>| match result {
+			Err(Error::Acl(AclError::Decode(_))) => true,
+			_ => false,
+		};
+
+		assert_eq!(decode_strict_check_permissions(&encode_bool(true)), Ok(true));
+		assert_eq!(decode_strict_check_permissions(&encode_bool(false)), Ok(false));
+		// extra outputs
+		let mut output = encode_bool(true);
+		output.extend(encode_uint(5));
+		assert!(is_decode_error(decode_strict_check_permissions(&output)));
+		// trailing bytes
+		let mut output = encode_bool(true);
+		output.push(0);
+		assert!(is_decode_error(decode_strict_check_permissions(&output)));
+		// no outputs
+		assert!(is_decode_error(decode_strict_check_permissions(&[])));
+		// non-bool output
+		assert!(is_decode_error(decode_strict_check_permissions(&encode_uint(2))));
+	}
+
+	#[test]
+	fn strict_decoding_is_opt_in() {
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(1))));
+		let mut output = encode_bool(true);
+		output.push(0);
+		*client.call_result.lock() = Ok(output);
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+
+		let acl_storage = OnChainAclStorage::new(client.clone());
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+
+		let acl_storage = OnChainAclStorage::with_config(client.clone(), OnChainAclStorageConfig {
+			strict_decode: true,
+			..Default::default()
+		});
+		assert!(acl_storage.check(&requester, &DocumentAddress::from(1)).is_err());
+	}
+
+	/// Client of the contract, which grants access to listed addresses only.
+	struct AllowlistAclClient {
+		allowed: Vec<Address>,
+		checked: Mutex<Vec<Address>>,
+	}
+
+	impl AclClient for AllowlistAclClient {
+		fn registry_address(&self, _name: String) -> Result<Option<Address>, String> {
+			Ok(Some(Address::from(1)))
+		}
+
+		fn call_contract(&self, _block: BlockId, _address: Address, data: Bytes) -> Result<Bytes, String> {
+			// requester address is the first argument
+			let requester = Address::from_slice(&data[16..36]);
+			self.checked.lock().push(requester.clone());
+			Ok(encode_bool(self.allowed.contains(&requester)))
+		}
+
+		fn best_block(&self) -> DecisionBlock {
+			DecisionBlock {
+				number: 1,
+				hash: H256::from(1),
+			}
+		}
+	}
+
+	#[test]
+	fn grant_of_any_candidate_address_is_grant() {
+		let public = Random.generate().unwrap().public().clone();
+		let requester = Requester::Public(public.clone());
+		let config = OnChainAclStorageConfig {
+			candidate_addresses: Some(Arc::new(|_public: &Public| vec![Address::from(10), Address::from(11)])),
+			..Default::default()
+		};
+
+		// only the last candidate is allowed
+		let client = Arc::new(AllowlistAclClient { allowed: vec![Address::from(11)], checked: Mutex::new(Vec::new()) });
+		let acl_storage = OnChainAclStorage::with_config(client.clone(), config.clone());
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+		assert_eq!(*client.checked.lock(), vec![public_to_address(&public), Address::from(10), Address::from(11)]);
+
+		// checks stop at the first grant
+		let client = Arc::new(AllowlistAclClient { allowed: vec![public_to_address(&public)], checked: Mutex::new(Vec::new()) });
+		let acl_storage = OnChainAclStorage::with_config(client.clone(), config.clone());
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+		assert_eq!(client.checked.lock().len(), 1);
+
+		// nothing is allowed
+		let client = Arc::new(AllowlistAclClient { allowed: Vec::new(), checked: Mutex::new(Vec::new()) });
+		let acl_storage = OnChainAclStorage::with_config(client.clone(), config.clone());
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(false));
+		assert_eq!(client.checked.lock().len(), 3);
+
+		// candidates are not derived when public key is unknown
+		client.checked.lock().clear();
+		assert_eq!(acl_storage.check(&Requester::Address(Address::from(12)), &DocumentAddress::from(1)), Ok(false));
+		assert_eq!(*client.checked.lock(), vec![Address::from(12)]);
+	}
+
+	#[test]
+	fn number_of_candidate_addresses_is_bounded() {
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+		let client = Arc::new(AllowlistAclClient { allowed: vec![Address::from(11)], checked: Mutex::new(Vec::new()) });
+		let acl_storage = OnChainAclStorage::with_config(client.clone(), OnChainAclStorageConfig {
+			candidate_addresses: Some(Arc::new(|_public: &Public| vec![Address::from(10), Address::from(11)])),
+			max_candidate_addresses: 1,
+			..Default::default()
+		});
+
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(false));
+		assert_eq!(client.checked.lock().len(), 2);
+	}
+}
@@ -0,0 +1,332 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+use parking_lot::Mutex;
+use key_storage::{KeyStorage, MAX_ALLOWED_REQUESTORS};
+use types::all::{Error, DocumentAddress, Address, Public, Requester};
+use super::{AclStorage, AclContext, AclReport, DecisionBlock, CacheableDecision, EffectivePolicy, limit_listed_requestors};
+
+/// Resolver of the administrator, who is allowed to edit document overrides (i.e. `OnChainAclStorage::admin`).
+/// None means that there's no administrator and overrides could only be set when document key is generated.
+/// Note that edits are node-local, see `OverrideAclStorage::set_local_allowed_requestors`.
+pub type AdminResolver = Arc<Fn() -> Result<Option<Address>, Error> + Send + Sync>;
+
+/// ACL storage, which allows access to requestors, listed in the document key share metadata.
+/// The underlying storage is only consulted when requestor is not in this list.
+/// Overrides are not bound to a scope, so listed requestors are allowed to access the document for any purpose.
+pub struct OverrideAclStorage {
+	/// Underlying ACL storage.
+	inner: Arc<AclStorage>,
+	/// Key storage, holding per-document overrides.
+	key_storage: Arc<KeyStorage>,
+	/// Administrator resolver.
+	admin: AdminResolver,
+	/// Lock, serializing read-modify-write updates of key shares.
+	updates: Mutex<()>,
+}
+
+impl OverrideAclStorage {
+	/// Create new overriding ACL storage. Overrides could be edited by the administrator, returned by `admin`.
+	pub fn new(inner: Arc<AclStorage>, key_storage: Arc<KeyStorage>, admin: AdminResolver) -> Self {
+		OverrideAclStorage {
+			inner: inner,
+			key_storage: key_storage,
+			admin: admin,
+			updates: Mutex::new(()),
+		}
+	}
+
+	/// Replace list of requestors, which are allowed to access the document in addition to the underlying storage.
+	/// Only the administrator could edit the list: `admin` must be authenticated by the caller (i.e. passed as
+	/// `Requester::Signature` of the document). Otherwise `Error::AccessDenied` is returned.
+	///
+	/// The edit is node-local: only the key share of this node is updated, while other key servers keep their lists,
+	/// so decisions for the document differ between nodes until the same edit is made on every share holder.
+	/// That's why edits are not exposed through the key server API. The list, shared by all nodes, is only set when
+	/// document key is generated (see `KeyServer::generate_document_key_with_allowed_requestors`).
+	pub fn set_local_allowed_requestors(&self, admin: &Requester, document: &DocumentAddress, requestors: Vec<Address>) -> Result<(), Error> {
+		self.update_local_allowed_requestors(admin, document, |allowed_requestors| *allowed_requestors = requestors)
+	}
+
+	/// Add requestor to the list of requestors, which are allowed to access the document. See `set_local_allowed_requestors`.
+	pub fn add_local_allowed_requestor(&self, admin: &Requester, document: &DocumentAddress, requestor: Address) -> Result<(), Error> {
+		self.update_local_allowed_requestors(admin, document, |allowed_requestors| if !allowed_requestors.contains(&requestor) {
+			allowed_requestors.push(requestor);
+		})
+	}
+
+	/// Remove requestor from the list of requestors, which are allowed to access the document. See `set_local_allowed_requestors`.
+	pub fn remove_local_allowed_requestor(&self, admin: &Requester, document: &DocumentAddress, requestor: &Address) -> Result<(), Error> {
+		self.update_local_allowed_requestors(admin, document, |allowed_requestors| allowed_requestors.retain(|allowed| allowed != requestor))
+	}
+
+	fn update_local_allowed_requestors<F: FnOnce(&mut Vec<Address>)>(&self, admin: &Requester, document: &DocumentAddress, update: F) -> Result<(), Error> {
+		let admin = admin.address(document)?;
+		match (self.admin)()? {
+			Some(ref expected_admin) if *expected_admin == admin => (),
+			_ => {
+				warn!(target: "secretstore", "{:?} is not allowed to edit ACL overrides of document {:?}", admin, document);
+				return Err(Error::AccessDenied);
+			},
+		}
+
+		let _update = self.updates.lock();
+		let mut key_share = self.key_storage.get(document)?;
+		update(&mut key_share.allowed_requestors);
+		if key_share.allowed_requestors.len() > MAX_ALLOWED_REQUESTORS {
+			return Err(Error::Internal(format!("too many allowed requestors: {} (max {})", key_share.allowed_requestors.len(), MAX_ALLOWED_REQUESTORS)));
+		}
+
+		info!(target: "secretstore", "Allowing {:?} to access document {:?}", key_share.allowed_requestors, document);
+		self.key_storage.insert(document.clone(), key_share)
+	}
+
+	/// Get list of requestors, which are allowed to access the document in addition to the underlying storage.
+	pub fn allowed_requestors(&self, document: &DocumentAddress) -> Result<Vec<Address>, Error> {
+		self.key_storage.get(document).map(|key_share| key_share.allowed_requestors)
+	}
+
+	/// Return `granted` if access is granted by override and ask the underlying storage with `inner_check` otherwise.
+	fn check_overridden<T, F>(&self, requester: &Requester, document: &DocumentAddress, granted: T, inner_check: F) -> Result<T, Error>
+		where F: FnOnce(&Requester) -> Result<T, Error> {
+		let requester = requester.resolve(document)?;
+		match self.key_storage.get(document) {
+			Ok(key_share) => {
+				if key_share.allowed_requestors.contains(&requester.address) {
+					trace!(target: "secretstore", "Access to document {:?} is granted by document ACL override", document);
+					return Ok(granted);
+				}
+			},
+			Err(Error::DocumentNotFound) => (),
+			Err(err) => warn!(target: "secretstore", "Failed to read ACL overrides of document {:?}: {}", document, err),
+		}
+
+		inner_check(&requester.into())
+	}
+}
+
+impl AclStorage for OverrideAclStorage {
+	fn check(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		self.check_overridden(requester, document, true, |requester| self.inner.check(requester, document))
+	}
+
+	fn check_with_context(&self, requester: &Requester, document: &DocumentAddress, context: &AclContext) -> Result<bool, Error> {
+		self.check_overridden(requester, document, true, |requester| self.inner.check_with_context(requester, document, context))
+	}
+
+	fn check_with_block(&self, requester: &Requester, document: &DocumentAddress) -> Result<(bool, Option<DecisionBlock>), Error> {
+		self.check_overridden(requester, document, (true, None), |requester| self.inner.check_with_block(requester, document))
+	}
+
+	fn check_with_expiry(&self, requester: &Requester, document: &DocumentAddress) -> Result<CacheableDecision, Error> {
+		let overridden = CacheableDecision {
+			granted: true,
+			not_after: None,
+			block: None,
+		};
+		self.check_overridden(requester, document, overridden, |requester| self.inner.check_with_expiry(requester, document))
+	}
+
+	fn check_scoped_with_expiry(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<CacheableDecision, Error> {
+		let overridden = CacheableDecision {
+			granted: true,
+			not_after: None,
+			block: None,
+		};
+		self.check_overridden(requester, document, overridden, |requester| self.inner.check_scoped_with_expiry(requester, document, scope))
+	}
+
+	fn check_scoped(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<bool, Error> {
+		self.check_overridden(requester, document, true, |requester| self.inner.check_scoped(requester, document, scope))
+	}
+
+	fn check_dry_run(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		self.check_overridden(requester, document, true, |requester| self.inner.check_dry_run(requester, document))
+	}
+
+	fn check_scoped_dry_run(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<bool, Error> {
+		self.check_overridden(requester, document, true, |requester| self.inner.check_scoped_dry_run(requester, document, scope))
+	}
+
+	/// Requestors, allowed by override, access the document without consuming it.
+	fn check_and_consume(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		self.check_overridden(requester, document, true, |requester| self.inner.check_and_consume(requester, document))
+	}
+
+	fn list_requestors(&self, document: &DocumentAddress) -> Result<Vec<Address>, Error> {
+		let mut requestors = self.inner.list_requestors(document)?;
+		match self.key_storage.get(document) {
+			Ok(key_share) => for requestor in key_share.allowed_requestors {
+				if !requestors.contains(&requestor) {
+					requestors.push(requestor);
+				}
+			},
+			Err(Error::DocumentNotFound) => (),
+			Err(err) => return Err(err),
+		}
+
+		limit_listed_requestors(requestors)
+	}
+
+	/// Overrides are stored in document key shares, which could not be enumerated, so only the underlying storage policy is reported.
+	fn effective_policy(&self, public: &Public) -> Result<EffectivePolicy, Error> {
+		self.inner.effective_policy(public)
+	}
+
+	fn fill_report(&self, report: &mut AclReport) {
+		self.inner.fill_report(report)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::thread;
+	use ethkey::{self, Random, Generator, KeyPair};
+	use key_storage::{KeyStorage, DocumentKeyShare, MAX_ALLOWED_REQUESTORS};
+	use key_storage::tests::DummyKeyStorage;
+	use types::all::{Error, DocumentAddress, Requester};
+	use acl_storage::AclStorage;
+	use acl_storage::tests::{DummyAclStorage, AllowlistAclStorage};
+	use super::OverrideAclStorage;
+
+	fn insert_key_share(key_storage: &DummyKeyStorage, document: &DocumentAddress) {
+		key_storage.insert(document.clone(), DocumentKeyShare {
+			threshold: 0,
+			id_numbers: Default::default(),
+			secret_share: Random.generate().unwrap().secret().clone(),
+			common_point: Random.generate().unwrap().public().clone(),
+			encrypted_point: Random.generate().unwrap().public().clone(),
+			allowed_requestors: Vec::new(),
+		}).unwrap();
+	}
+
+	fn admin_requester(admin: &KeyPair, document: &DocumentAddress) -> Requester {
+		Requester::Signature(ethkey::sign(admin.secret(), document).unwrap())
+	}
+
+	fn prepare_storage<T: AclStorage + 'static>(inner: Arc<T>, document: &DocumentAddress) -> (KeyPair, OverrideAclStorage) {
+		let key_storage = Arc::new(DummyKeyStorage::default());
+		insert_key_share(&key_storage, document);
+		let admin = Random.generate().unwrap();
+		let admin_address = admin.address();
+		(admin, OverrideAclStorage::new(inner, key_storage, Arc::new(move || Ok(Some(admin_address.clone())))))
+	}
+
+	#[test]
+	fn access_is_granted_by_override() {
+		let document = DocumentAddress::from(1);
+		let requestor: KeyPair = Random.generate().unwrap();
+		let inner = Arc::new(DummyAclStorage::default());
+		let (admin, acl_storage) = prepare_storage(inner.clone(), &document);
+		inner.prohibit(requestor.public().clone(), document.clone());
+		assert_eq!(acl_storage.check(&Requester::Public(requestor.public().clone()), &document), Ok(false));
+
+		acl_storage.set_local_allowed_requestors(&admin_requester(&admin, &document), &document, vec![requestor.address()]).unwrap();
+		assert_eq!(acl_storage.allowed_requestors(&document), Ok(vec![requestor.address()]));
+		assert_eq!(acl_storage.check(&Requester::Public(requestor.public().clone()), &document), Ok(true));
+		assert_eq!(inner.checks_count(), 1);
+	}
+
+	#[test]
+	fn underlying_storage_is_consulted_when_override_does_not_match() {
+		let document = DocumentAddress::from(1);
+		let requestor: KeyPair = Random.generate().unwrap();
+		let inner = Arc::new(DummyAclStorage::default());
+		let (admin, acl_storage) = prepare_storage(inner.clone(), &document);
+		acl_storage.set_local_allowed_requestors(&admin_requester(&admin, &document), &document, vec![Random.generate().unwrap().address()]).unwrap();
+		inner.prohibit(requestor.public().clone(), document.clone());
+
+		assert_eq!(acl_storage.check(&Requester::Public(requestor.public().clone()), &document), Ok(false));
+		assert_eq!(acl_storage.check(&Requester::Public(requestor.public().clone()), &DocumentAddress::from(2)), Ok(true));
+		assert_eq!(inner.checks_count(), 2);
+	}
+
+	#[test]
+	fn overridden_requestors_are_listed() {
+		let document = DocumentAddress::from(1);
+		let inner = Arc::new(AllowlistAclStorage::default());
+		let (admin, acl_storage) = prepare_storage(inner.clone(), &document);
+		let (requestor1, requestor2) = (Random.generate().unwrap().address(), Random.generate().unwrap().address());
+		inner.allow(requestor1.clone(), document.clone());
+
+		acl_storage.set_local_allowed_requestors(&admin_requester(&admin, &document), &document, vec![requestor1.clone(), requestor2.clone()]).unwrap();
+		assert_eq!(acl_storage.list_requestors(&document), Ok(vec![requestor1, requestor2]));
+		assert_eq!(acl_storage.list_requestors(&DocumentAddress::from(2)), Ok(vec![]));
+
+		// enumeration is only possible when underlying storage supports it
+		let (_, acl_storage) = prepare_storage(Arc::new(DummyAclStorage::default()), &document);
+		assert!(acl_storage.list_requestors(&document).is_err());
+	}
+
+	#[test]
+	fn overrides_list_is_capped() {
+		let document = DocumentAddress::from(1);
+		let (admin, acl_storage) = prepare_storage(Arc::new(DummyAclStorage::default()), &document);
+		let requestors: Vec<_> = (0..MAX_ALLOWED_REQUESTORS + 1).map(|_| Random.generate().unwrap().address()).collect();
+
+		assert!(acl_storage.set_local_allowed_requestors(&admin_requester(&admin, &document), &document, requestors).is_err());
+		assert_eq!(acl_storage.allowed_requestors(&document), Ok(Vec::new()));
+	}
+
+	#[test]
+	fn only_admin_could_edit_overrides() {
+		let document = DocumentAddress::from(1);
+		let (_, acl_storage) = prepare_storage(Arc::new(DummyAclStorage::default()), &document);
+		let requestor = Random.generate().unwrap();
+
+		// requestor could not allow itself
+		assert_eq!(acl_storage.add_local_allowed_requestor(&admin_requester(&requestor, &document), &document, requestor.address()),
+			Err(Error::AccessDenied));
+		assert_eq!(acl_storage.allowed_requestors(&document), Ok(Vec::new()));
+
+		// nobody is admin when administrator is unknown
+		let key_storage = Arc::new(DummyKeyStorage::default());
+		insert_key_share(&key_storage, &document);
+		let acl_storage = OverrideAclStorage::new(Arc::new(DummyAclStorage::default()), key_storage, Arc::new(|| Ok(None)));
+		assert_eq!(acl_storage.add_local_allowed_requestor(&admin_requester(&requestor, &document), &document, requestor.address()),
+			Err(Error::AccessDenied));
+	}
+
+	#[test]
+	fn concurrent_updates_are_not_lost() {
+		let document = DocumentAddress::from(1);
+		let (admin, acl_storage) = prepare_storage(Arc::new(DummyAclStorage::default()), &document);
+		let acl_storage = Arc::new(acl_storage);
+		let admin = admin_requester(&admin, &document);
+		let requestors: Vec<_> = (0..8).map(|_| Random.generate().unwrap().address()).collect();
+
+		let handles: Vec<_> = requestors.iter().cloned().map(|requestor| {
+			let acl_storage = acl_storage.clone();
+			let admin = admin.clone();
+			let document = document.clone();
+			thread::spawn(move || acl_storage.add_local_allowed_requestor(&admin, &document, requestor).unwrap())
+		}).collect();
+		for handle in handles {
+			handle.join().unwrap();
+		}
+
+		let mut allowed_requestors = acl_storage.allowed_requestors(&document).unwrap();
+		let mut requestors = requestors;
+		allowed_requestors.sort();
+		requestors.sort();
+		assert_eq!(allowed_requestors, requestors);
+
+		acl_storage.remove_local_allowed_requestor(&admin, &document, &requestors[0]).unwrap();
+		assert!(!acl_storage.allowed_requestors(&document).unwrap().contains(&requestors[0]));
+	}
+}
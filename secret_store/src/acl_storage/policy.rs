@@ -0,0 +1,180 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use serialization::SerializableH256;
+use types::all::DocumentAddress;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+/// Documents, which requester is allowed to access, as reported to the requester itself.
+/// Every storage layer adjusts the policy of the underlying storage.
+pub struct EffectivePolicy {
+	/// Requester is allowed to access every document, except of `denied`.
+	pub all_documents: bool,
+	/// Documents, requester is explicitly allowed to access.
+	pub documents: Vec<PolicyEntry>,
+	/// Documents, requester is denied to access. Only listed when `all_documents` is set.
+	pub denied: Vec<SerializableH256>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+/// Document, requester is allowed to access.
+pub struct PolicyEntry {
+	/// Document address.
+	pub document: SerializableH256,
+	/// Number of seconds, after which access expires. None if access does not expire.
+	pub expires_in_secs: Option<u64>,
+	/// Why access is granted.
+	pub reason: String,
+}
+
+impl EffectivePolicy {
+	/// Policy, allowing access to every document.
+	pub fn all_documents() -> Self {
+		EffectivePolicy {
+			all_documents: true,
+			..Default::default()
+		}
+	}
+
+	/// Is access to the document allowed by this policy.
+	pub fn allows(&self, document: &DocumentAddress) -> bool {
+		if self.all_documents {
+			!self.denied.iter().any(|denied| **denied == *document)
+		} else {
+			self.entry(document).is_some()
+		}
+	}
+
+	/// Get entry of explicitly allowed document.
+	pub fn entry(&self, document: &DocumentAddress) -> Option<&PolicyEntry> {
+		self.documents.iter().find(|entry| *entry.document == *document)
+	}
+
+	/// Allow access to the document. Existing entry of the document is replaced.
+	pub fn allow(&mut self, entry: PolicyEntry) {
+		self.denied.retain(|denied| **denied != *entry.document);
+		self.documents.retain(|existing| *existing.document != *entry.document);
+		self.documents.push(entry);
+	}
+
+	/// Deny access to the document.
+	pub fn deny(&mut self, document: &DocumentAddress) {
+		self.documents.retain(|entry| *entry.document != *document);
+		if self.all_documents && !self.denied.iter().any(|denied| **denied == *document) {
+			self.denied.push(document.clone().into());
+		}
+	}
+
+	/// Combine policies, so that document is allowed if it is allowed by any of them.
+	pub fn union(policies: Vec<EffectivePolicy>) -> EffectivePolicy {
+		let mut combined = EffectivePolicy {
+			all_documents: policies.iter().any(|policy| policy.all_documents),
+			..Default::default()
+		};
+		for policy in &policies {
+			for entry in &policy.documents {
+				if combined.entry(&entry.document).is_none() {
+					combined.documents.push(entry.clone());
+				}
+			}
+		}
+		if combined.all_documents {
+			for policy in &policies {
+				for denied in &policy.denied {
+					if !policies.iter().any(|policy| policy.allows(denied)) {
+						combined.deny(denied);
+					}
+				}
+			}
+		}
+		combined
+	}
+
+	/// Combine policies, so that document is allowed only if it is allowed by all of them.
+	pub fn intersection(policies: Vec<EffectivePolicy>) -> EffectivePolicy {
+		let mut combined = EffectivePolicy {
+			all_documents: policies.iter().all(|policy| policy.all_documents),
+			..Default::default()
+		};
+		for policy in &policies {
+			for entry in &policy.documents {
+				if combined.entry(&entry.document).is_none() && policies.iter().all(|policy| policy.allows(&entry.document)) {
+					combined.documents.push(entry.clone());
+				}
+			}
+		}
+		if combined.all_documents {
+			for policy in &policies {
+				for denied in &policy.denied {
+					combined.deny(denied);
+				}
+			}
+		}
+		combined
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json;
+	use types::all::DocumentAddress;
+	use super::{EffectivePolicy, PolicyEntry};
+
+	fn entry(document: u64) -> PolicyEntry {
+		PolicyEntry {
+			document: DocumentAddress::from(document).into(),
+			expires_in_secs: None,
+			reason: "test".into(),
+		}
+	}
+
+	fn policy(documents: &[u64]) -> EffectivePolicy {
+		EffectivePolicy {
+			all_documents: false,
+			documents: documents.iter().map(|document| entry(*document)).collect(),
+			denied: Vec::new(),
+		}
+	}
+
+	fn wildcard_policy(denied: &[u64]) -> EffectivePolicy {
+		let mut policy = EffectivePolicy::all_documents();
+		for document in denied {
+			policy.deny(&DocumentAddress::from(*document));
+		}
+		policy
+	}
+
+	fn allowed(policy: &EffectivePolicy) -> Vec<u64> {
+		(1..6u64).filter(|document| policy.allows(&DocumentAddress::from(*document))).collect()
+	}
+
+	#[test]
+	fn policies_are_combined() {
+		assert_eq!(allowed(&EffectivePolicy::union(vec![policy(&[1, 2]), policy(&[2, 3])])), vec![1, 2, 3]);
+		assert_eq!(allowed(&EffectivePolicy::intersection(vec![policy(&[1, 2]), policy(&[2, 3])])), vec![2]);
+		assert_eq!(allowed(&EffectivePolicy::union(vec![wildcard_policy(&[1, 2]), policy(&[2])])), vec![2, 3, 4, 5]);
+		assert_eq!(allowed(&EffectivePolicy::intersection(vec![wildcard_policy(&[1, 2]), policy(&[2, 3])])), vec![3]);
+		assert_eq!(allowed(&EffectivePolicy::intersection(vec![wildcard_policy(&[1]), wildcard_policy(&[2])])), vec![3, 4, 5]);
+		assert_eq!(allowed(&EffectivePolicy::union(vec![wildcard_policy(&[1, 2]), wildcard_policy(&[2, 3])])), vec![1, 3, 4, 5]);
+	}
+
+	#[test]
+	fn policy_is_serialized() {
+		let mut policy = policy(&[1]);
+		policy.documents[0].expires_in_secs = Some(60);
+		assert_eq!(serde_json::to_string(&policy).unwrap(), r#"{"all_documents":false,"documents":[{"document":"0000000000000000000000000000000000000000000000000000000000000001","expires_in_secs":60,"reason":"test"}],"denied":[]}"#);
+	}
+}
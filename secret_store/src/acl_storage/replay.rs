@@ -0,0 +1,229 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use ethcore::client::BlockId;
+use types::all::Error;
+use super::{AclAuditEvent, MerkleAuditLog, OnChainAclStorage, audit_leaf};
+
+#[derive(Debug, Clone, PartialEq)]
+/// Decision record, replayed against the chain state.
+pub struct ReplayRecord {
+	/// Index of the record in the audit log.
+	pub index: usize,
+	/// Recorded decision.
+	pub event: AclAuditEvent,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Recorded decision, which differs from the decision, made by replay.
+pub struct ReplayMismatch {
+	/// Index of the record in the audit log.
+	pub index: usize,
+	/// Recorded result of the check.
+	pub recorded: Result<bool, Error>,
+	/// Replayed decision.
+	pub replayed: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+/// Result of the audit log replay.
+pub struct ReplayReport {
+	/// Indices of records, which match replayed decisions.
+	pub matched: Vec<usize>,
+	/// Records, which differ from replayed decisions.
+	pub mismatched: Vec<ReplayMismatch>,
+	/// Indices of records, which are not included into the audit log (i.e. have been altered). They are not replayed.
+	pub unverified: Vec<usize>,
+	/// Records, which could not be replayed, with replay error.
+	pub failed: Vec<(usize, Error)>,
+}
+
+/// Re-evaluates decisions from the audit log against the on-chain state at the logged blocks, so that
+/// correctness of past decisions could be checked after incident. Chain client must keep the state of
+/// the recorded blocks (i.e. be an archive node).
+pub struct AuditReplayer {
+	/// Audit log, records are verified against.
+	audit_log: Arc<MerkleAuditLog>,
+	/// On-chain ACL storage, decisions are replayed by.
+	storage: Arc<OnChainAclStorage>,
+	/// Min interval between replayed checks, so that replay does not overload the chain client.
+	min_interval: Duration,
+}
+
+impl AuditReplayer {
+	/// Create new replayer, making at most one check per `min_interval`.
+	pub fn new(audit_log: Arc<MerkleAuditLog>, storage: Arc<OnChainAclStorage>, min_interval: Duration) -> Self {
+		AuditReplayer {
+			audit_log: audit_log,
+			storage: storage,
+			min_interval: min_interval,
+		}
+	}
+
+	/// Replay given records. Only records, which are included into the audit log, are replayed. Since block
+	/// is the part of the logged record, decisions are only replayed at the blocks, they have been made at.
+	pub fn replay(&self, records: &[ReplayRecord]) -> ReplayReport {
+		let root = self.audit_log.audit_root();
+		let mut report = ReplayReport::default();
+		let mut is_first_check = true;
+		for record in records {
+			let is_logged = self.audit_log.audit_proof(record.index)
+				.map(|proof| proof.verify(&audit_leaf(&record.event), &root))
+				.unwrap_or(false);
+			if !is_logged {
+				warn!(target: "secretstore", "ACL decision record {} is not included into the audit log", record.index);
+				report.unverified.push(record.index);
+				continue;
+			}
+
+			let event = &record.event;
+			let block = match event.block {
+				Some(ref block) => block.hash.clone(),
+				None => {
+					report.failed.push((record.index, Error::Internal("decision block is not recorded".to_owned())));
+					continue;
+				},
+			};
+
+			if !is_first_check {
+				thread::sleep(self.min_interval);
+			}
+			is_first_check = false;
+
+			match self.storage.check_scoped_at(&event.requester, &event.document, &event.scope, BlockId::Hash(block)) {
+				Ok(replayed) if event.result == Ok(replayed) => report.matched.push(record.index),
+				Ok(replayed) => {
+					warn!(target: "secretstore", "ACL decision record {} for document {:?} is {:?}, while replayed decision is {}",
+						record.index, event.document, event.result, replayed);
+					report.mismatched.push(ReplayMismatch {
+						index: record.index,
+						recorded: event.result.clone(),
+						replayed: replayed,
+					});
+				},
+				Err(error) => report.failed.push((record.index, error)),
+			}
+		}
+
+		report
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::time::{Duration, Instant};
+	use ethcore::client::BlockId;
+	use util::{Address, H256};
+	use types::all::{Error, AclError, DocumentAddress, Requester};
+	use acl_storage::{AclAuditEvent, MerkleAuditLog, OnChainAclStorage, DecisionBlock};
+	use acl_storage::tests::DummyAclClient;
+	use super::{AuditReplayer, ReplayRecord, ReplayMismatch, ReplayReport};
+
+	fn record(index: usize, result: Result<bool, Error>) -> ReplayRecord {
+		ReplayRecord {
+			index: index,
+			event: AclAuditEvent {
+				requester: Requester::Address(Address::from(2)),
+				document: DocumentAddress::from(index as u64),
+				scope: String::new(),
+				block: Some(DecisionBlock { number: index as u64 + 10, hash: H256::from(index as u64 + 10) }),
+				timestamp: Some(1500000000),
+				result: result,
+			},
+		}
+	}
+
+	fn synthetic_log(records: &[ReplayRecord]) -> Arc<MerkleAuditLog> {
+		let audit_log = Arc::new(MerkleAuditLog::in_memory());
+		for record in records {
+			assert_eq!(audit_log.append(&record.event), Ok(record.index));
+		}
+		audit_log
+	}
+
+	#[test]
+	fn decisions_are_replayed_at_recorded_blocks() {
+		// contract grants access to everyone
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(1))));
+		let records = vec![record(0, Ok(true)), record(1, Ok(false)), record(2, Err(Error::Internal("failed".into()))), record(3, Ok(false))];
+		let audit_log = synthetic_log(&records);
+		let replayer = AuditReplayer::new(audit_log, Arc::new(OnChainAclStorage::new(client.clone())), Duration::from_millis(0));
+
+		// record 3 is altered after it has been logged
+		let mut replayed = records.clone();
+		replayed[3].event.result = Ok(true);
+		assert_eq!(replayer.replay(&replayed), ReplayReport {
+			matched: vec![0],
+			mismatched: vec![ReplayMismatch {
+				index: 1,
+				recorded: Ok(false),
+				replayed: true,
+			}, ReplayMismatch {
+				index: 2,
+				recorded: Err(Error::Internal("failed".into())),
+				replayed: true,
+			}],
+			unverified: vec![3],
+			failed: vec![],
+		});
+		let blocks: Vec<_> = client.calls.lock().iter().map(|call| call.0).collect();
+		assert_eq!(blocks, vec![BlockId::Hash(H256::from(10)), BlockId::Hash(H256::from(11)), BlockId::Hash(H256::from(12))]);
+	}
+
+	#[test]
+	fn failed_replays_are_reported() {
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(1))));
+		*client.call_result.lock() = Err("state is pruned".into());
+		let records = vec![record(0, Ok(true))];
+		let replayer = AuditReplayer::new(synthetic_log(&records), Arc::new(OnChainAclStorage::new(client)), Duration::from_millis(0));
+
+		assert_eq!(replayer.replay(&records).failed, vec![(0, Error::Acl(AclError::Call("state is pruned".into())))]);
+	}
+
+	#[test]
+	fn decisions_are_only_replayed_at_logged_blocks() {
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(1))));
+		let mut records = vec![record(0, Ok(true)), record(1, Ok(true))];
+		records[1].event.block = None;
+		let replayer = AuditReplayer::new(synthetic_log(&records), Arc::new(OnChainAclStorage::new(client.clone())), Duration::from_millis(0));
+
+		// block of the record is changed => record is not the logged one
+		let mut replayed = records.clone();
+		replayed[0].event.block = Some(DecisionBlock { number: 20, hash: H256::from(20) });
+		assert_eq!(replayer.replay(&replayed), ReplayReport {
+			matched: vec![],
+			mismatched: vec![],
+			unverified: vec![0],
+			failed: vec![(1, Error::Internal("decision block is not recorded".into()))],
+		});
+		assert!(client.calls.lock().is_empty());
+	}
+
+	#[test]
+	fn replay_rate_is_bounded() {
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(1))));
+		let records = vec![record(0, Ok(true)), record(1, Ok(true)), record(2, Ok(true))];
+		let replayer = AuditReplayer::new(synthetic_log(&records), Arc::new(OnChainAclStorage::new(client)), Duration::from_millis(20));
+
+		let started_at = Instant::now();
+		assert_eq!(replayer.replay(&records).matched, vec![0, 1, 2]);
+		assert!(started_at.elapsed() >= Duration::from_millis(40));
+	}
+}
@@ -0,0 +1,93 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use ethcore::header::BlockNumber;
+use serialization::{SerializableAddress, SerializableH256};
+
+#[derive(Debug, Clone, Default, Serialize)]
+/// State of the (composed) ACL storage, reported to monitoring tools.
+/// Every storage layer fills its own part of the report.
+pub struct AclReport {
+	/// Reports of all caching layers.
+	pub caches: Vec<CacheReport>,
+	/// Number of audit subscribers.
+	pub audit_subscribers: usize,
+	/// Number of active temporary grants.
+	pub temporary_grants: usize,
+	/// Number of consumed single-use documents.
+	pub consumed_documents: usize,
+	/// Address of the resolved ACL checker contract.
+	pub contract_address: Option<SerializableAddress>,
+	/// Error of the last failed contract resolution.
+	pub contract_resolution_error: Option<String>,
+	/// Number of outstanding checks, registered with the node-wide governor.
+	pub outstanding_checks: Option<usize>,
+	/// Fingerprint of the on-chain storage configuration.
+	pub config_fingerprint: Option<SerializableH256>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+/// State of the caching layer.
+pub struct CacheReport {
+	/// Is caching currently enabled.
+	pub caching_enabled: bool,
+	/// Number of cached decisions.
+	pub entries: usize,
+	/// Number of cache shards.
+	pub shards: usize,
+	/// Number of the oldest block, at which cached decision has been made.
+	pub oldest_block: Option<BlockNumber>,
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::time::Duration;
+	use serde_json;
+	use ethkey::{Random, Generator};
+	use util::Address;
+	use types::all::{DocumentAddress, Requester};
+	use acl_storage::{AclStorage, OnChainAclStorage, CachedAclStorage, AuditedAclStorage};
+	use acl_storage::tests::DummyAclClient;
+	use super::CacheReport;
+
+	#[test]
+	fn report_reflects_storage_state() {
+		let client = Arc::new(DummyAclClient::new(Some(Address::from(1))));
+		let on_chain = Arc::new(OnChainAclStorage::new(client));
+		let cached = Arc::new(CachedAclStorage::new(on_chain, Duration::from_secs(60)));
+		let acl_storage = AuditedAclStorage::new(cached.clone());
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+
+		let report = acl_storage.report();
+		assert_eq!(report.caches, vec![CacheReport { caching_enabled: true, entries: 0, shards: 16, oldest_block: None }]);
+		assert!(report.contract_address.is_none());
+
+		let _receiver = acl_storage.subscribe_decisions();
+		acl_storage.check(&requester, &DocumentAddress::from(1)).unwrap();
+		acl_storage.check(&requester, &DocumentAddress::from(2)).unwrap();
+
+		let report = acl_storage.report();
+		assert_eq!(report.caches, vec![CacheReport { caching_enabled: true, entries: 2, shards: 16, oldest_block: Some(1) }]);
+		assert_eq!(report.audit_subscribers, 1);
+		assert_eq!(report.contract_address.as_ref().map(|address| (**address).clone()), Some(Address::from(1)));
+		let report = serde_json::to_string(&report).unwrap();
+		assert!(report.contains(r#""contract_address":"0000000000000000000000000000000000000001""#));
+
+		cached.set_caching_enabled(false);
+		assert_eq!(acl_storage.report().caches, vec![CacheReport { caching_enabled: false, entries: 0, shards: 16, oldest_block: None }]);
+	}
+}
@@ -0,0 +1,157 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+use futures_cpupool::{CpuPool, CpuFuture};
+use tokio_service::Service;
+use types::all::{Error, DocumentAddress, Requester};
+use super::AclStorage;
+
+#[derive(Debug, Clone, PartialEq)]
+/// ACL check request, served by `AclService`.
+pub struct AclRequest {
+	/// Requester of the document.
+	pub requester: Requester,
+	/// Checked document.
+	pub document: DocumentAddress,
+}
+
+/// Adapter, which serves ACL checks of the storage as `tokio_service::Service`.
+/// Checks could block (e.g. on contract calls), so they are executed on the given cpu pool.
+///
+/// Adapter does nothing but the check. Timeouts, rate or concurrency limits and logging are
+/// added by wrapping it in middleware services: every middleware is a `Service` which holds the
+/// inner service and forwards `call` to it, e.g. `Timeout::new(ConcurrencyLimit::new(AclService::new(..)))`.
+/// The outermost middleware sees the request first.
+pub struct AclService {
+	/// ACL storage, checks are forwarded to.
+	storage: Arc<AclStorage>,
+	/// Pool, checks are executed on.
+	pool: CpuPool,
+}
+
+impl AclService {
+	/// Create new service, which executes checks of the `storage` on the `pool`.
+	pub fn new(storage: Arc<AclStorage>, pool: CpuPool) -> Self {
+		AclService {
+			storage: storage,
+			pool: pool,
+		}
+	}
+}
+
+impl Service for AclService {
+	type Request = AclRequest;
+	type Response = bool;
+	type Error = Error;
+	type Future = CpuFuture<bool, Error>;
+
+	fn call(&self, request: AclRequest) -> Self::Future {
+		let storage = self.storage.clone();
+		self.pool.spawn_fn(move || storage.check(&request.requester, &request.document))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::thread;
+	use std::time::Duration;
+	use futures::Future;
+	use futures_cpupool::CpuPool;
+	use tokio_core::reactor::{Core, Handle, Timeout};
+	use tokio_service::Service;
+	use ethkey::{Random, Generator};
+	use types::all::{Error, DocumentAddress, Requester};
+	use acl_storage::AclStorage;
+	use acl_storage::tests::DummyAclStorage;
+	use super::{AclService, AclRequest};
+
+	/// ACL storage, which takes a while to respond.
+	struct SlowAclStorage(Duration);
+
+	impl AclStorage for SlowAclStorage {
+		fn check(&self, _requester: &Requester, _document: &DocumentAddress) -> Result<bool, Error> {
+			thread::sleep(self.0);
+			Ok(true)
+		}
+	}
+
+	/// Middleware, which fails requests, not served within timeout.
+	struct TimeoutService<S> {
+		inner: S,
+		timeout: Duration,
+		handle: Handle,
+	}
+
+	impl<S> Service for TimeoutService<S> where S: Service<Error = Error>, S::Future: 'static, S::Response: 'static {
+		type Request = S::Request;
+		type Response = S::Response;
+		type Error = Error;
+		type Future = Box<Future<Item = S::Response, Error = Error>>;
+
+		fn call(&self, request: S::Request) -> Self::Future {
+			let timeout = Timeout::new(self.timeout, &self.handle).expect("failed to create timeout")
+				.then(|_| Err::<S::Response, Error>(Error::Internal("ACL check has timed out".into())));
+			Box::new(self.inner.call(request)
+				.select(timeout)
+				.map(|(response, _)| response)
+				.map_err(|(error, _)| error))
+		}
+	}
+
+	fn request() -> AclRequest {
+		AclRequest {
+			requester: Requester::Public(Random.generate().unwrap().public().clone()),
+			document: DocumentAddress::from(1),
+		}
+	}
+
+	#[test]
+	fn check_is_served() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let service = AclService::new(inner.clone(), CpuPool::new(1));
+		let public = Random.generate().unwrap().public().clone();
+		let request = AclRequest {
+			requester: Requester::Public(public.clone()),
+			document: DocumentAddress::from(1),
+		};
+		assert_eq!(service.call(request.clone()).wait(), Ok(true));
+
+		inner.prohibit(public, DocumentAddress::from(1));
+		assert_eq!(service.call(request).wait(), Ok(false));
+	}
+
+	#[test]
+	fn timeout_middleware_is_applied() {
+		let mut core = Core::new().unwrap();
+		let pool = CpuPool::new(1);
+
+		let service = TimeoutService {
+			inner: AclService::new(Arc::new(SlowAclStorage(Duration::from_millis(500))), pool.clone()),
+			timeout: Duration::from_millis(20),
+			handle: core.handle(),
+		};
+		assert_eq!(core.run(service.call(request())), Err(Error::Internal("ACL check has timed out".into())));
+
+		let service = TimeoutService {
+			inner: AclService::new(Arc::new(SlowAclStorage(Duration::from_millis(0))), pool),
+			timeout: Duration::from_millis(5000),
+			handle: core.handle(),
+		};
+		assert_eq!(core.run(service.call(request())), Ok(true));
+	}
+}
@@ -0,0 +1,233 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+use std::collections::HashMap;
+use parking_lot::Mutex;
+use types::all::{Error, AclError, DocumentAddress, Address, Public, Requester};
+use super::{AclStorage, AclContext, AclReport, DecisionBlock, CacheableDecision, EffectivePolicy};
+
+/// Number of active sessions of every document.
+type Sessions = Arc<Mutex<HashMap<DocumentAddress, usize>>>;
+
+/// ACL storage, which limits number of concurrent sessions, accessing the same document.
+/// Session slot is acquired with `acquire` and is released when returned guard is dropped.
+/// When document has max number of active sessions, checks are failing with `AclError::TooManyConcurrent`.
+pub struct SessionLimitAclStorage {
+	/// Underlying ACL storage.
+	inner: Arc<AclStorage>,
+	/// Max number of concurrent sessions per document.
+	max_sessions: usize,
+	/// Active sessions.
+	sessions: Sessions,
+}
+
+/// Active session slot. Slot is released when guard is dropped.
+pub struct SessionGuard {
+	/// Active sessions.
+	sessions: Sessions,
+	/// Document, accessed by the session.
+	document: DocumentAddress,
+}
+
+impl SessionLimitAclStorage {
+	/// Create new session-limiting ACL storage on top of `inner`.
+	pub fn new(inner: Arc<AclStorage>, max_sessions: usize) -> Self {
+		SessionLimitAclStorage {
+			inner: inner,
+			max_sessions: max_sessions,
+			sessions: Arc::new(Mutex::new(HashMap::new())),
+		}
+	}
+
+	/// Check if `requester` can access document with hash `document` and acquire session slot.
+	/// Returns `Error::AccessDenied` if access is denied by the underlying storage.
+	pub fn acquire(&self, requester: &Requester, document: &DocumentAddress) -> Result<SessionGuard, Error> {
+		self.ensure_slot_available(document)?;
+		if !self.inner.check(requester, document)? {
+			return Err(Error::AccessDenied);
+		}
+
+		// slot could be acquired by other session while we were waiting for the underlying storage
+		let mut sessions = self.sessions.lock();
+		let active_sessions = sessions.entry(document.clone()).or_insert(0);
+		if *active_sessions >= self.max_sessions {
+			return Err(AclError::TooManyConcurrent.into());
+		}
+
+		*active_sessions += 1;
+		Ok(SessionGuard {
+			sessions: self.sessions.clone(),
+			document: document.clone(),
+		})
+	}
+
+	/// Get number of active sessions of the document.
+	pub fn active_sessions(&self, document: &DocumentAddress) -> usize {
+		self.sessions.lock().get(document).cloned().unwrap_or(0)
+	}
+
+	fn ensure_slot_available(&self, document: &DocumentAddress) -> Result<(), Error> {
+		if self.active_sessions(document) >= self.max_sessions {
+			trace!(target: "secretstore", "Access to document {:?} is denied: too many concurrent sessions", document);
+			return Err(AclError::TooManyConcurrent.into());
+		}
+
+		Ok(())
+	}
+}
+
+impl Drop for SessionGuard {
+	fn drop(&mut self) {
+		let mut sessions = self.sessions.lock();
+		let is_last = match sessions.get_mut(&self.document) {
+			Some(active_sessions) => {
+				*active_sessions -= 1;
+				*active_sessions == 0
+			},
+			None => false,
+		};
+		if is_last {
+			sessions.remove(&self.document);
+		}
+	}
+}
+
+impl AclStorage for SessionLimitAclStorage {
+	fn check(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		self.ensure_slot_available(document)?;
+		self.inner.check(requester, document)
+	}
+
+	fn check_with_context(&self, requester: &Requester, document: &DocumentAddress, context: &AclContext) -> Result<bool, Error> {
+		self.ensure_slot_available(document)?;
+		self.inner.check_with_context(requester, document, context)
+	}
+
+	fn check_with_block(&self, requester: &Requester, document: &DocumentAddress) -> Result<(bool, Option<DecisionBlock>), Error> {
+		self.ensure_slot_available(document)?;
+		self.inner.check_with_block(requester, document)
+	}
+
+	fn check_with_expiry(&self, requester: &Requester, document: &DocumentAddress) -> Result<CacheableDecision, Error> {
+		self.ensure_slot_available(document)?;
+		self.inner.check_with_expiry(requester, document)
+	}
+
+	fn check_scoped_with_expiry(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<CacheableDecision, Error> {
+		self.ensure_slot_available(document)?;
+		self.inner.check_scoped_with_expiry(requester, document, scope)
+	}
+
+	fn check_scoped(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<bool, Error> {
+		self.ensure_slot_available(document)?;
+		self.inner.check_scoped(requester, document, scope)
+	}
+
+	/// Dry-run checks do not acquire session slots, so they are not limited.
+	fn check_dry_run(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		self.inner.check_dry_run(requester, document)
+	}
+
+	fn check_scoped_dry_run(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<bool, Error> {
+		self.inner.check_scoped_dry_run(requester, document, scope)
+	}
+
+	fn check_and_consume(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		self.ensure_slot_available(document)?;
+		self.inner.check_and_consume(requester, document)
+	}
+
+	fn list_requestors(&self, document: &DocumentAddress) -> Result<Vec<Address>, Error> {
+		self.inner.list_requestors(document)
+	}
+
+	fn effective_policy(&self, public: &Public) -> Result<EffectivePolicy, Error> {
+		self.inner.effective_policy(public)
+	}
+
+	fn fill_report(&self, report: &mut AclReport) {
+		self.inner.fill_report(report)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::{Arc, Barrier};
+	use std::thread;
+	use ethkey::{Random, Generator};
+	use types::all::{Error, AclError, DocumentAddress, Requester};
+	use acl_storage::AclStorage;
+	use acl_storage::tests::DummyAclStorage;
+	use super::SessionLimitAclStorage;
+
+	#[test]
+	fn sessions_are_limited_per_document() {
+		let acl_storage = SessionLimitAclStorage::new(Arc::new(DummyAclStorage::default()), 2);
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+		let document = DocumentAddress::from(1);
+
+		let session1 = acl_storage.acquire(&requester, &document).unwrap();
+		let _session2 = acl_storage.acquire(&requester, &document).unwrap();
+		assert_eq!(acl_storage.active_sessions(&document), 2);
+		assert_eq!(acl_storage.acquire(&requester, &document).err(), Some(Error::Acl(AclError::TooManyConcurrent)));
+		assert_eq!(acl_storage.check(&requester, &document), Err(Error::Acl(AclError::TooManyConcurrent)));
+		// probes are not limited
+		assert_eq!(acl_storage.check_dry_run(&requester, &document), Ok(true));
+		assert_eq!(acl_storage.check_scoped_dry_run(&requester, &document, ""), Ok(true));
+		// other documents are unaffected
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(2)), Ok(true));
+
+		drop(session1);
+		assert_eq!(acl_storage.active_sessions(&document), 1);
+		assert!(acl_storage.acquire(&requester, &document).is_ok());
+	}
+
+	#[test]
+	fn denied_request_does_not_acquire_session() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let acl_storage = SessionLimitAclStorage::new(inner.clone(), 1);
+		let public = Random.generate().unwrap().public().clone();
+		let document = DocumentAddress::from(1);
+		inner.prohibit(public.clone(), document.clone());
+
+		assert_eq!(acl_storage.acquire(&Requester::Public(public), &document).err(), Some(Error::AccessDenied));
+		assert_eq!(acl_storage.active_sessions(&document), 0);
+	}
+
+	#[test]
+	fn concurrent_sessions_are_limited() {
+		let acl_storage = Arc::new(SessionLimitAclStorage::new(Arc::new(DummyAclStorage::default()), 3));
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+		let barrier = Arc::new(Barrier::new(8));
+
+		let handles: Vec<_> = (0..8).map(|_| {
+			let acl_storage = acl_storage.clone();
+			let requester = requester.clone();
+			let barrier = barrier.clone();
+			thread::spawn(move || {
+				let session = acl_storage.acquire(&requester, &DocumentAddress::from(1));
+				// sessions are held until every thread has tried to acquire the slot
+				barrier.wait();
+				session.is_ok()
+			})
+		}).collect();
+		let acquired = handles.into_iter().map(|handle| handle.join().unwrap()).filter(|acquired| *acquired).count();
+
+		assert_eq!(acquired, 3);
+		assert_eq!(acl_storage.active_sessions(&DocumentAddress::from(1)), 0);
+	}
+}
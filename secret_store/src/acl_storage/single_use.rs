@@ -0,0 +1,219 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+use std::collections::HashSet;
+use parking_lot::Mutex;
+use types::all::{Error, DocumentAddress, Public, Requester};
+use super::{AclStorage, AclContext, AclReport, DecisionBlock, CacheableDecision, EffectivePolicy};
+
+/// ACL storage for single-use documents: once access to the document is consumed,
+/// it is denied to everyone. Consumed documents are only kept in memory.
+pub struct SingleUseAclStorage {
+	/// Underlying ACL storage.
+	inner: Arc<AclStorage>,
+	/// Consumed documents.
+	consumed: Mutex<HashSet<DocumentAddress>>,
+}
+
+impl SingleUseAclStorage {
+	/// Create new single-use ACL storage on top of `inner`.
+	pub fn new(inner: Arc<AclStorage>) -> Self {
+		SingleUseAclStorage {
+			inner: inner,
+			consumed: Mutex::new(HashSet::new()),
+		}
+	}
+
+	/// Is access to the document already consumed.
+	pub fn is_consumed(&self, document: &DocumentAddress) -> bool {
+		self.consumed.lock().contains(document)
+	}
+}
+
+impl AclStorage for SingleUseAclStorage {
+	fn check(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		if self.is_consumed(document) {
+			return Ok(false);
+		}
+
+		self.inner.check(requester, document)
+	}
+
+	fn check_with_context(&self, requester: &Requester, document: &DocumentAddress, context: &AclContext) -> Result<bool, Error> {
+		if self.is_consumed(document) {
+			return Ok(false);
+		}
+
+		self.inner.check_with_context(requester, document, context)
+	}
+
+	fn check_with_block(&self, requester: &Requester, document: &DocumentAddress) -> Result<(bool, Option<DecisionBlock>), Error> {
+		if self.is_consumed(document) {
+			return Ok((false, None));
+		}
+
+		self.inner.check_with_block(requester, document)
+	}
+
+	fn check_with_expiry(&self, requester: &Requester, document: &DocumentAddress) -> Result<CacheableDecision, Error> {
+		if self.is_consumed(document) {
+			return Ok(CacheableDecision {
+				granted: false,
+				not_after: None,
+				block: None,
+			});
+		}
+
+		self.inner.check_with_expiry(requester, document)
+	}
+
+	fn check_scoped_with_expiry(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<CacheableDecision, Error> {
+		if self.is_consumed(document) {
+			return Ok(CacheableDecision {
+				granted: false,
+				not_after: None,
+				block: None,
+			});
+		}
+
+		self.inner.check_scoped_with_expiry(requester, document, scope)
+	}
+
+	fn check_scoped(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<bool, Error> {
+		if self.is_consumed(document) {
+			return Ok(false);
+		}
+
+		self.inner.check_scoped(requester, document, scope)
+	}
+
+	fn check_dry_run(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		if self.is_consumed(document) {
+			return Ok(false);
+		}
+
+		self.inner.check_dry_run(requester, document)
+	}
+
+	fn check_scoped_dry_run(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<bool, Error> {
+		if self.is_consumed(document) {
+			return Ok(false);
+		}
+
+		self.inner.check_scoped_dry_run(requester, document, scope)
+	}
+
+	fn check_and_consume(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		// lock is held during the check, so that only one of concurrent requesters could consume the document
+		let mut consumed = self.consumed.lock();
+		if consumed.contains(document) {
+			return Ok(false);
+		}
+
+		let granted = self.inner.check(requester, document)?;
+		if granted {
+			trace!(target: "secretstore", "Access to single-use document {:?} is consumed", document);
+			consumed.insert(document.clone());
+		}
+		Ok(granted)
+	}
+
+	fn effective_policy(&self, public: &Public) -> Result<EffectivePolicy, Error> {
+		let mut policy = self.inner.effective_policy(public)?;
+		for document in self.consumed.lock().iter() {
+			policy.deny(document);
+		}
+		Ok(policy)
+	}
+
+	fn fill_report(&self, report: &mut AclReport) {
+		report.consumed_documents += self.consumed.lock().len();
+		self.inner.fill_report(report)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use ethkey::{Random, Generator};
+	use types::all::{DocumentAddress, Requester};
+	use acl_storage::AclStorage;
+	use acl_storage::tests::{DummyAclStorage, AllowlistAclStorage};
+	use super::SingleUseAclStorage;
+
+	#[test]
+	fn single_use_document_is_accessed_once() {
+		let acl_storage = SingleUseAclStorage::new(Arc::new(DummyAclStorage::default()));
+		let requester1 = Requester::Public(Random.generate().unwrap().public().clone());
+		let requester2 = Requester::Public(Random.generate().unwrap().public().clone());
+		let document = DocumentAddress::from(1);
+
+		assert_eq!(acl_storage.check(&requester1, &document), Ok(true));
+		assert!(!acl_storage.is_consumed(&document));
+
+		assert_eq!(acl_storage.check_and_consume(&requester1, &document), Ok(true));
+		assert!(acl_storage.is_consumed(&document));
+		assert_eq!(acl_storage.check_and_consume(&requester1, &document), Ok(false));
+		assert_eq!(acl_storage.check_and_consume(&requester2, &document), Ok(false));
+		assert_eq!(acl_storage.check(&requester2, &document), Ok(false));
+		assert_eq!(acl_storage.check_dry_run(&requester2, &document), Ok(false));
+
+		// other documents are unaffected
+		assert_eq!(acl_storage.check_and_consume(&requester2, &DocumentAddress::from(2)), Ok(true));
+	}
+
+	#[test]
+	fn denied_request_does_not_consume_document() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let acl_storage = SingleUseAclStorage::new(inner.clone());
+		let public = Random.generate().unwrap().public().clone();
+		let document = DocumentAddress::from(1);
+		inner.prohibit(public.clone(), document.clone());
+
+		assert_eq!(acl_storage.check_and_consume(&Requester::Public(public), &document), Ok(false));
+		assert!(!acl_storage.is_consumed(&document));
+	}
+
+	#[test]
+	fn consuming_is_not_supported_by_default() {
+		let acl_storage = DummyAclStorage::default();
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+		assert!(acl_storage.check_and_consume(&requester, &DocumentAddress::from(1)).is_err());
+	}
+
+	#[test]
+	fn consumed_documents_are_removed_from_policy() {
+		let inner = Arc::new(AllowlistAclStorage::default());
+		let acl_storage = SingleUseAclStorage::new(inner.clone());
+		let key_pair = Random.generate().unwrap();
+		inner.allow(key_pair.address(), DocumentAddress::from(1));
+		inner.allow(key_pair.address(), DocumentAddress::from(2));
+		assert_eq!(acl_storage.effective_policy(key_pair.public()).unwrap().documents.len(), 2);
+
+		acl_storage.check_and_consume(&Requester::Public(key_pair.public().clone()), &DocumentAddress::from(1)).unwrap();
+		let policy = acl_storage.effective_policy(key_pair.public()).unwrap();
+		assert!(!policy.allows(&DocumentAddress::from(1)));
+		assert!(policy.allows(&DocumentAddress::from(2)));
+
+		// wildcard policy lists consumed documents as denied
+		let acl_storage = SingleUseAclStorage::new(Arc::new(DummyAclStorage::default()));
+		acl_storage.check_and_consume(&Requester::Public(key_pair.public().clone()), &DocumentAddress::from(1)).unwrap();
+		let policy = acl_storage.effective_policy(key_pair.public()).unwrap();
+		assert!(policy.all_documents);
+		assert_eq!(policy.denied, vec![DocumentAddress::from(1).into()]);
+	}
+}
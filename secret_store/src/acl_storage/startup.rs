@@ -0,0 +1,280 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use types::all::{Error, AclError, DocumentAddress, Address, Public, Requester};
+use super::{AclStorage, AclContext, AclReport, DecisionBlock, CacheableDecision, EffectivePolicy, Clock, SystemClock};
+
+/// Default max duration of the startup window.
+const DEFAULT_STARTUP_WINDOW_SECS: u64 = 300;
+
+/// Readiness probe of the node, i.e. checking that client is synced and that cache is warm.
+pub type ReadinessProbe = Arc<Fn() -> Result<(), Error> + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Result of checks, made during the startup window.
+pub enum StartupBehavior {
+	/// Checks are failing with `AclError::ServiceStarting`.
+	Reject,
+	/// Access is denied.
+	Deny,
+}
+
+#[derive(Debug, Clone)]
+/// Startup gate configuration.
+pub struct StartupGateConfig {
+	/// Max duration of the startup window. When it elapses, checks are served even if the node has not
+	/// become ready. None means that checks are not served until the node is ready.
+	pub window: Option<Duration>,
+	/// Result of checks during the startup window.
+	pub behavior: StartupBehavior,
+}
+
+/// ACL storage, which does not serve checks of the underlying storage right after node start, until
+/// `await_ready` succeeds or startup window elapses. This way decisions are not served before the client
+/// is synced and the cache is warm.
+pub struct StartupGateAclStorage {
+	/// Underlying ACL storage.
+	inner: Arc<AclStorage>,
+	/// Gate configuration.
+	config: StartupGateConfig,
+	/// Readiness probe.
+	probe: ReadinessProbe,
+	/// Time source.
+	clock: Arc<Clock>,
+	/// Time of the node start.
+	started: Instant,
+	/// True if startup window is over.
+	ready: AtomicBool,
+}
+
+impl Default for StartupGateConfig {
+	fn default() -> Self {
+		StartupGateConfig {
+			window: Some(Duration::from_secs(DEFAULT_STARTUP_WINDOW_SECS)),
+			behavior: StartupBehavior::Reject,
+		}
+	}
+}
+
+impl StartupGateAclStorage {
+	/// Create new startup gate on top of `inner`. Startup window begins now.
+	pub fn new(inner: Arc<AclStorage>, config: StartupGateConfig, probe: ReadinessProbe) -> Self {
+		StartupGateAclStorage::with_clock(inner, config, probe, Arc::new(SystemClock))
+	}
+
+	/// Create new startup gate, which uses given clock to track the startup window.
+	pub fn with_clock(inner: Arc<AclStorage>, config: StartupGateConfig, probe: ReadinessProbe, clock: Arc<Clock>) -> Self {
+		StartupGateAclStorage {
+			inner: inner,
+			config: config,
+			probe: probe,
+			started: clock.now(),
+			clock: clock,
+			ready: AtomicBool::new(false),
+		}
+	}
+
+	/// Run readiness probe and end the startup window if node is ready. Returns probe error otherwise.
+	pub fn await_ready(&self) -> Result<(), Error> {
+		if self.ready.load(Ordering::SeqCst) {
+			return Ok(());
+		}
+
+		(self.probe)()?;
+		if !self.ready.swap(true, Ordering::SeqCst) {
+			info!(target: "secretstore", "Node is ready, ACL checks are served");
+		}
+		Ok(())
+	}
+
+	/// Check if startup window is over.
+	pub fn is_ready(&self) -> bool {
+		if self.ready.load(Ordering::SeqCst) {
+			return true;
+		}
+
+		match self.config.window {
+			Some(window) if self.clock.now() >= self.started + window => {
+				if !self.ready.swap(true, Ordering::SeqCst) {
+					warn!(target: "secretstore", "Node has not become ready in {}s, ACL checks are served", window.as_secs());
+				}
+				true
+			},
+			_ => false,
+		}
+	}
+
+	/// Fail if startup window is not over.
+	fn ensure_ready(&self) -> Result<(), Error> {
+		if !self.is_ready() {
+			return Err(AclError::ServiceStarting.into());
+		}
+
+		Ok(())
+	}
+
+	/// Make the check if startup window is over. Otherwise the check is rejected or `denied` is returned.
+	fn gated<T, F: FnOnce() -> Result<T, Error>>(&self, denied: T, check: F) -> Result<T, Error> {
+		if self.is_ready() {
+			return check();
+		}
+
+		trace!(target: "secretstore", "ACL check is made during startup window");
+		match self.config.behavior {
+			StartupBehavior::Reject => Err(AclError::ServiceStarting.into()),
+			StartupBehavior::Deny => Ok(denied),
+		}
+	}
+
+	/// Denial of the check, made during the startup window. Denial expires immediately, so that it is not cached by the caller.
+	fn denied_with_expiry(&self) -> CacheableDecision {
+		CacheableDecision {
+			granted: false,
+			not_after: Some(self.clock.now()),
+			block: None,
+		}
+	}
+}
+
+impl AclStorage for StartupGateAclStorage {
+	fn check(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		self.gated(false, || self.inner.check(requester, document))
+	}
+
+	fn check_with_context(&self, requester: &Requester, document: &DocumentAddress, context: &AclContext) -> Result<bool, Error> {
+		self.gated(false, || self.inner.check_with_context(requester, document, context))
+	}
+
+	fn check_with_block(&self, requester: &Requester, document: &DocumentAddress) -> Result<(bool, Option<DecisionBlock>), Error> {
+		self.gated((false, None), || self.inner.check_with_block(requester, document))
+	}
+
+	/// Denials, made during the startup window, expire immediately, so that they are not cached by the caller.
+	fn check_with_expiry(&self, requester: &Requester, document: &DocumentAddress) -> Result<CacheableDecision, Error> {
+		self.gated(self.denied_with_expiry(), || self.inner.check_with_expiry(requester, document))
+	}
+
+	fn check_scoped_with_expiry(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<CacheableDecision, Error> {
+		self.gated(self.denied_with_expiry(), || self.inner.check_scoped_with_expiry(requester, document, scope))
+	}
+
+	fn check_scoped(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<bool, Error> {
+		self.gated(false, || self.inner.check_scoped(requester, document, scope))
+	}
+
+	fn check_dry_run(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		self.gated(false, || self.inner.check_dry_run(requester, document))
+	}
+
+	fn check_scoped_dry_run(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<bool, Error> {
+		self.gated(false, || self.inner.check_scoped_dry_run(requester, document, scope))
+	}
+
+	fn check_and_consume(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		self.gated(false, || self.inner.check_and_consume(requester, document))
+	}
+
+	fn list_requestors(&self, document: &DocumentAddress) -> Result<Vec<Address>, Error> {
+		self.ensure_ready()?;
+		self.inner.list_requestors(document)
+	}
+
+	fn effective_policy(&self, public: &Public) -> Result<EffectivePolicy, Error> {
+		self.ensure_ready()?;
+		self.inner.effective_policy(public)
+	}
+
+	fn fill_report(&self, report: &mut AclReport) {
+		self.inner.fill_report(report)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::time::Duration;
+	use parking_lot::Mutex;
+	use ethkey::{Random, Generator};
+	use types::all::{Error, AclError, DocumentAddress, Requester};
+	use acl_storage::AclStorage;
+	use acl_storage::tests::DummyAclStorage;
+	use acl_storage::clock::tests::MockClock;
+	use super::{StartupGateAclStorage, StartupGateConfig, StartupBehavior};
+
+	fn acl_storage(config: StartupGateConfig, clock: Arc<MockClock>) -> (StartupGateAclStorage, Arc<Mutex<Result<(), Error>>>) {
+		let probe_result = Arc::new(Mutex::new(Err(Error::Internal("client is syncing".into()))));
+		let probe = probe_result.clone();
+		let acl_storage = StartupGateAclStorage::with_clock(Arc::new(DummyAclStorage::default()), config,
+			Arc::new(move || probe.lock().clone()), clock);
+		(acl_storage, probe_result)
+	}
+
+	#[test]
+	fn checks_are_rejected_until_ready() {
+		let (acl_storage, probe_result) = acl_storage(Default::default(), Arc::new(MockClock::default()));
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Err(Error::Acl(AclError::ServiceStarting)));
+		assert_eq!(acl_storage.list_requestors(&DocumentAddress::from(1)), Err(Error::Acl(AclError::ServiceStarting)));
+
+		// node is not ready yet
+		assert_eq!(acl_storage.await_ready(), Err(Error::Internal("client is syncing".into())));
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Err(Error::Acl(AclError::ServiceStarting)));
+
+		*probe_result.lock() = Ok(());
+		assert_eq!(acl_storage.await_ready(), Ok(()));
+		assert!(acl_storage.is_ready());
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+
+		// readiness is not lost
+		*probe_result.lock() = Err(Error::Internal("client is syncing".into()));
+		assert_eq!(acl_storage.await_ready(), Ok(()));
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+	}
+
+	#[test]
+	fn access_is_denied_during_startup() {
+		let (acl_storage, probe_result) = acl_storage(StartupGateConfig {
+			window: None,
+			behavior: StartupBehavior::Deny,
+		}, Arc::new(MockClock::default()));
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(false));
+		assert_eq!(acl_storage.check_scoped(&requester, &DocumentAddress::from(1), "decrypt"), Ok(false));
+
+		*probe_result.lock() = Ok(());
+		acl_storage.await_ready().unwrap();
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+	}
+
+	#[test]
+	fn checks_are_served_when_window_elapses() {
+		let clock = Arc::new(MockClock::default());
+		let (acl_storage, _) = acl_storage(StartupGateConfig {
+			window: Some(Duration::from_secs(60)),
+			behavior: StartupBehavior::Reject,
+		}, clock.clone());
+		let requester = Requester::Public(Random.generate().unwrap().public().clone());
+
+		clock.advance(Duration::from_secs(59));
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Err(Error::Acl(AclError::ServiceStarting)));
+		clock.advance(Duration::from_secs(1));
+		assert_eq!(acl_storage.check(&requester, &DocumentAddress::from(1)), Ok(true));
+		assert!(acl_storage.is_ready());
+	}
+}
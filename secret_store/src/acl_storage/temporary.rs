@@ -0,0 +1,350 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use parking_lot::RwLock;
+use ethkey::public_to_address;
+use types::all::{Error, DocumentAddress, Address, Public, Requester};
+use super::{AclStorage, AclContext, AclReport, DecisionBlock, CacheableDecision, EffectivePolicy, PolicyEntry, Clock, SystemClock, limit_listed_requestors};
+
+#[derive(Debug, Clone, Default)]
+/// Temporary grants ACL storage configuration.
+pub struct TemporaryGrantAclStorageConfig {
+	/// Time, by which every grant window is extended, so that nodes with slightly skewed clocks make the same
+	/// decision near the grant expiration. Note that this extends access of every requester by the tolerance.
+	pub clock_skew_tolerance: Duration,
+}
+
+/// ACL storage, which allows operators to grant time-boxed access to documents (i.e. during maintenance).
+/// Unexpired grants take precedence over the underlying storage decision. Grants are not bound to a scope,
+/// so they allow access to the document for any purpose.
+pub struct TemporaryGrantAclStorage {
+	/// Underlying ACL storage.
+	inner: Arc<AclStorage>,
+	/// Time source.
+	clock: Arc<Clock>,
+	/// Time, by which every grant window is extended.
+	clock_skew_tolerance: Duration,
+	/// Temporary grants => grant expiration time.
+	grants: RwLock<HashMap<(Address, DocumentAddress), Instant>>,
+}
+
+impl TemporaryGrantAclStorage {
+	/// Create new temporary grants ACL storage on top of `inner`.
+	pub fn new(inner: Arc<AclStorage>) -> Self {
+		TemporaryGrantAclStorage::with_clock(inner, Arc::new(SystemClock))
+	}
+
+	/// Create new temporary grants ACL storage, which uses given clock to expire grants.
+	pub fn with_clock(inner: Arc<AclStorage>, clock: Arc<Clock>) -> Self {
+		TemporaryGrantAclStorage::with_config(inner, Default::default(), clock)
+	}
+
+	/// Create new temporary grants ACL storage with given configuration.
+	pub fn with_config(inner: Arc<AclStorage>, config: TemporaryGrantAclStorageConfig, clock: Arc<Clock>) -> Self {
+		TemporaryGrantAclStorage {
+			inner: inner,
+			clock: clock,
+			clock_skew_tolerance: config.clock_skew_tolerance,
+			grants: RwLock::new(HashMap::new()),
+		}
+	}
+
+	/// Grant `requester` access to the `document` for given `duration`.
+	/// Existing grant for the same requester and document is replaced.
+	pub fn grant(&self, requester: Address, document: DocumentAddress, duration: Duration) {
+		warn!(target: "secretstore", "Temporarily granting {:?} access to document {:?} for {}s", requester, document, duration.as_secs());
+		self.grants.write().insert((requester, document), self.clock.now() + duration);
+	}
+
+	/// Revoke temporary grant. Returns true if grant has existed.
+	pub fn revoke(&self, requester: &Address, document: &DocumentAddress) -> bool {
+		self.grants.write().remove(&(requester.clone(), document.clone())).is_some()
+	}
+
+	/// Remove all expired grants. Returns number of removed grants.
+	pub fn prune_expired(&self) -> usize {
+		let now = self.clock.now();
+		let mut grants = self.grants.write();
+		let expired: Vec<_> = grants.iter()
+			.filter(|&(_, expires)| !self.is_active(expires, now))
+			.map(|(key, _)| key.clone())
+			.collect();
+		for key in &expired {
+			trace!(target: "secretstore", "Temporary grant of {:?} access to document {:?} has expired", key.0, key.1);
+			grants.remove(key);
+		}
+		expired.len()
+	}
+
+	/// Get number of active (not yet pruned) grants.
+	pub fn grants_count(&self) -> usize {
+		self.grants.read().len()
+	}
+
+	/// Is grant, which expires at `expires`, still active.
+	fn is_active(&self, expires: &Instant, now: Instant) -> bool {
+		*expires + self.clock_skew_tolerance > now
+	}
+
+	/// Get time, when active grant of `requester` access to the `document` expires.
+	fn active_grant(&self, requester: &Address, document: &DocumentAddress) -> Option<Instant> {
+		let key = (requester.clone(), document.clone());
+		let expires = self.grants.read().get(&key).cloned();
+		if let Some(expires) = expires {
+			let now = self.clock.now();
+			if self.is_active(&expires, now) {
+				info!(target: "secretstore", "Access of {:?} to document {:?} is granted by temporary grant, which expires in {}s",
+					requester, document, (expires + self.clock_skew_tolerance - now).as_secs());
+				return Some(expires + self.clock_skew_tolerance);
+			}
+
+			self.prune_expired();
+		}
+
+		None
+	}
+
+	/// Return `granted` if access is granted temporarily and ask the underlying storage with `inner_check` otherwise.
+	fn check_granted<T, F>(&self, requester: &Requester, document: &DocumentAddress, granted: T, inner_check: F) -> Result<T, Error>
+		where F: FnOnce(&Requester) -> Result<T, Error> {
+		let requester = requester.resolve(document)?;
+		if self.active_grant(&requester.address, document).is_some() {
+			return Ok(granted);
+		}
+
+		inner_check(&requester.into())
+	}
+
+	/// Return grant, which expires with the temporary grant, if access is granted temporarily and ask the underlying
+	/// storage with `inner_check` otherwise.
+	fn check_granted_with_expiry<F>(&self, requester: &Requester, document: &DocumentAddress, inner_check: F) -> Result<CacheableDecision, Error>
+		where F: FnOnce(&Requester) -> Result<CacheableDecision, Error> {
+		let requester = requester.resolve(document)?;
+		match self.active_grant(&requester.address, document) {
+			Some(not_after) => Ok(CacheableDecision {
+				granted: true,
+				not_after: Some(not_after),
+				block: None,
+			}),
+			None => inner_check(&requester.into()),
+		}
+	}
+}
+
+impl AclStorage for TemporaryGrantAclStorage {
+	fn check(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		self.check_granted(requester, document, true, |requester| self.inner.check(requester, document))
+	}
+
+	fn check_with_context(&self, requester: &Requester, document: &DocumentAddress, context: &AclContext) -> Result<bool, Error> {
+		self.check_granted(requester, document, true, |requester| self.inner.check_with_context(requester, document, context))
+	}
+
+	fn check_with_block(&self, requester: &Requester, document: &DocumentAddress) -> Result<(bool, Option<DecisionBlock>), Error> {
+		self.check_granted(requester, document, (true, None), |requester| self.inner.check_with_block(requester, document))
+	}
+
+	fn check_with_expiry(&self, requester: &Requester, document: &DocumentAddress) -> Result<CacheableDecision, Error> {
+		self.check_granted_with_expiry(requester, document, |requester| self.inner.check_with_expiry(requester, document))
+	}
+
+	fn check_scoped_with_expiry(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<CacheableDecision, Error> {
+		self.check_granted_with_expiry(requester, document, |requester| self.inner.check_scoped_with_expiry(requester, document, scope))
+	}
+
+	fn check_scoped(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<bool, Error> {
+		self.check_granted(requester, document, true, |requester| self.inner.check_scoped(requester, document, scope))
+	}
+
+	fn check_dry_run(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		self.check_granted(requester, document, true, |requester| self.inner.check_dry_run(requester, document))
+	}
+
+	fn check_scoped_dry_run(&self, requester: &Requester, document: &DocumentAddress, scope: &str) -> Result<bool, Error> {
+		self.check_granted(requester, document, true, |requester| self.inner.check_scoped_dry_run(requester, document, scope))
+	}
+
+	/// Temporarily granted requesters access the document without consuming it.
+	fn check_and_consume(&self, requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		self.check_granted(requester, document, true, |requester| self.inner.check_and_consume(requester, document))
+	}
+
+	fn list_requestors(&self, document: &DocumentAddress) -> Result<Vec<Address>, Error> {
+		let mut requestors = self.inner.list_requestors(document)?;
+		let now = self.clock.now();
+		for (&(ref requestor, ref granted_document), expires) in self.grants.read().iter() {
+			if granted_document == document && self.is_active(expires, now) && !requestors.contains(requestor) {
+				requestors.push(requestor.clone());
+			}
+		}
+
+		limit_listed_requestors(requestors)
+	}
+
+	fn effective_policy(&self, public: &Public) -> Result<EffectivePolicy, Error> {
+		let mut policy = self.inner.effective_policy(public)?;
+		let address = public_to_address(public);
+		let now = self.clock.now();
+		for (&(ref requestor, ref document), expires) in self.grants.read().iter() {
+			if *requestor == address && self.is_active(expires, now) {
+				policy.allow(PolicyEntry {
+					document: document.clone().into(),
+					expires_in_secs: Some((*expires + self.clock_skew_tolerance - now).as_secs()),
+					reason: "temporary grant".into(),
+				});
+			}
+		}
+		Ok(policy)
+	}
+
+	fn fill_report(&self, report: &mut AclReport) {
+		let now = self.clock.now();
+		report.temporary_grants += self.grants.read().values().filter(|expires| self.is_active(expires, now)).count();
+		self.inner.fill_report(report)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::time::Duration;
+	use ethkey::{Random, Generator, KeyPair};
+	use types::all::{DocumentAddress, Requester};
+	use acl_storage::{AclStorage, OnChainAclStorage, PolicyEntry, CacheableDecision, Clock};
+	use acl_storage::tests::{DummyAclStorage, AllowlistAclStorage, DummyAclClient};
+	use acl_storage::clock::tests::MockClock;
+	use super::{TemporaryGrantAclStorage, TemporaryGrantAclStorageConfig};
+
+	#[test]
+	fn temporary_grant_takes_precedence_over_denying_storage() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let acl_storage = TemporaryGrantAclStorage::new(inner.clone());
+		let key_pair: KeyPair = Random.generate().unwrap();
+		let requester = Requester::Public(key_pair.public().clone());
+		let document = DocumentAddress::from(1);
+		inner.prohibit(key_pair.public().clone(), document.clone());
+		assert_eq!(acl_storage.check(&requester, &document), Ok(false));
+
+		acl_storage.grant(key_pair.address(), document.clone(), Duration::from_secs(60));
+		assert_eq!(acl_storage.check(&requester, &document), Ok(true));
+		assert_eq!(inner.checks_count(), 1);
+
+		assert!(acl_storage.revoke(&key_pair.address(), &document));
+		assert_eq!(acl_storage.check(&requester, &document), Ok(false));
+	}
+
+	#[test]
+	fn expired_grant_is_ignored_and_pruned() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let clock = Arc::new(MockClock::default());
+		let acl_storage = TemporaryGrantAclStorage::with_clock(inner.clone(), clock.clone());
+		let key_pair: KeyPair = Random.generate().unwrap();
+		let requester = Requester::Public(key_pair.public().clone());
+		let document = DocumentAddress::from(1);
+		inner.prohibit(key_pair.public().clone(), document.clone());
+
+		acl_storage.grant(key_pair.address(), document.clone(), Duration::from_secs(10));
+		acl_storage.grant(key_pair.address(), DocumentAddress::from(2), Duration::from_secs(60));
+		assert_eq!(acl_storage.grants_count(), 2);
+
+		clock.advance(Duration::from_secs(9));
+		assert_eq!(acl_storage.check(&requester, &document), Ok(true));
+
+		clock.advance(Duration::from_secs(1));
+		assert_eq!(acl_storage.check(&requester, &document), Ok(false));
+		assert_eq!(acl_storage.grants_count(), 1);
+
+		clock.advance(Duration::from_secs(50));
+		assert_eq!(acl_storage.prune_expired(), 1);
+		assert_eq!(acl_storage.grants_count(), 0);
+	}
+
+	#[test]
+	fn grant_window_is_extended_by_clock_skew_tolerance() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let clock = Arc::new(MockClock::default());
+		let acl_storage = TemporaryGrantAclStorage::with_config(inner.clone(), TemporaryGrantAclStorageConfig {
+			clock_skew_tolerance: Duration::from_secs(2),
+		}, clock.clone());
+		let key_pair: KeyPair = Random.generate().unwrap();
+		let requester = Requester::Public(key_pair.public().clone());
+		let document = DocumentAddress::from(1);
+		inner.prohibit(key_pair.public().clone(), document.clone());
+
+		acl_storage.grant(key_pair.address(), document.clone(), Duration::from_secs(10));
+		clock.advance(Duration::from_secs(11));
+		assert_eq!(acl_storage.check(&requester, &document), Ok(true));
+		assert_eq!(acl_storage.prune_expired(), 0);
+
+		clock.advance(Duration::from_secs(1));
+		assert_eq!(acl_storage.check(&requester, &document), Ok(false));
+		assert_eq!(acl_storage.grants_count(), 0);
+	}
+
+	#[test]
+	fn granted_decision_expires_with_grant_window() {
+		let inner = Arc::new(DummyAclStorage::default());
+		let clock = Arc::new(MockClock::default());
+		let acl_storage = TemporaryGrantAclStorage::with_config(inner.clone(), TemporaryGrantAclStorageConfig {
+			clock_skew_tolerance: Duration::from_secs(2),
+		}, clock.clone());
+		let key_pair: KeyPair = Random.generate().unwrap();
+		let requester = Requester::Public(key_pair.public().clone());
+		let document = DocumentAddress::from(1);
+		inner.prohibit(key_pair.public().clone(), document.clone());
+		assert_eq!(acl_storage.check_with_expiry(&requester, &document), Ok(CacheableDecision {
+			granted: false,
+			not_after: None,
+			block: None,
+		}));
+
+		acl_storage.grant(key_pair.address(), document.clone(), Duration::from_secs(10));
+		assert_eq!(acl_storage.check_with_expiry(&requester, &document), Ok(CacheableDecision {
+			granted: true,
+			not_after: Some(clock.now() + Duration::from_secs(12)),
+			block: None,
+		}));
+	}
+
+	#[test]
+	fn temporary_grants_are_reported_in_policy() {
+		let clock = Arc::new(MockClock::default());
+		let acl_storage = TemporaryGrantAclStorage::with_clock(Arc::new(AllowlistAclStorage::default()), clock.clone());
+		let key_pair: KeyPair = Random.generate().unwrap();
+		acl_storage.grant(key_pair.address(), DocumentAddress::from(1), Duration::from_secs(10));
+		acl_storage.grant(Random.generate().unwrap().address(), DocumentAddress::from(2), Duration::from_secs(10));
+		clock.advance(Duration::from_secs(4));
+
+		assert_eq!(acl_storage.effective_policy(key_pair.public()).unwrap().documents, vec![PolicyEntry {
+			document: DocumentAddress::from(1).into(),
+			expires_in_secs: Some(6),
+			reason: "temporary grant".into(),
+		}]);
+
+		clock.advance(Duration::from_secs(6));
+		assert!(acl_storage.effective_policy(key_pair.public()).unwrap().documents.is_empty());
+	}
+
+	#[test]
+	fn policy_is_not_reported_without_enumeration_support() {
+		let client = Arc::new(DummyAclClient::new(None));
+		let acl_storage = TemporaryGrantAclStorage::new(Arc::new(OnChainAclStorage::new(client)));
+		assert!(acl_storage.effective_policy(Random.generate().unwrap().public()).is_err());
+	}
+}
@@ -0,0 +1,238 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+use byteorder::{BigEndian, WriteBytesExt};
+use ethkey::{self, Secret, Signature};
+use util::{H256, Hashable};
+use types::all::{Error, DocumentAddress, Address, Public, Requester};
+use super::{AclStorage, AclReport, Clock, SystemClock};
+
+/// Domain separator of access ticket hashes.
+const ACCESS_TICKET_DOMAIN: &'static [u8] = b"secretstore_ticket";
+
+#[derive(Debug, Clone, PartialEq)]
+/// Access ticket, issued out-of-band by the authorization service. Ticket allows given requester
+/// to access given document until it expires. Tickets are signed by the issuer key.
+pub struct AccessTicket {
+	/// Requester address.
+	pub requester: Address,
+	/// Document, requester is allowed to access.
+	pub document: DocumentAddress,
+	/// Unix timestamp (in seconds), when ticket expires.
+	pub expires: u64,
+}
+
+/// ACL storage, which only grants access to requesters, presenting valid and unexpired access ticket
+/// to `check_signed`. Plain checks are denied. When backend storage is set, it must also grant the access.
+pub struct TicketAclStorage {
+	/// Public key of the tickets issuer.
+	issuer: Public,
+	/// Backend storage, which is consulted after ticket is verified.
+	backend: Option<Arc<AclStorage>>,
+	/// Time source.
+	clock: Arc<Clock>,
+}
+
+impl AccessTicket {
+	/// Get hash of the ticket, which is signed by the issuer:
+	/// `keccak256("secretstore_ticket" ++ requester ++ document ++ u64_be(expires))`.
+	pub fn hash(&self) -> H256 {
+		let mut data = Vec::with_capacity(ACCESS_TICKET_DOMAIN.len() + 20 + 32 + 8);
+		data.extend_from_slice(ACCESS_TICKET_DOMAIN);
+		data.extend_from_slice(&*self.requester);
+		data.extend_from_slice(&*self.document);
+		data.write_u64::<BigEndian>(self.expires).expect("writing to vec never fails; qed");
+		data.sha3()
+	}
+}
+
+/// Sign access ticket with issuer secret key.
+pub fn sign_ticket(secret: &Secret, ticket: &AccessTicket) -> Result<Signature, Error> {
+	Ok(ethkey::sign(secret, &ticket.hash())?)
+}
+
+impl TicketAclStorage {
+	/// Create new tickets ACL storage, accepting tickets of given issuer.
+	pub fn new(issuer: Public, backend: Option<Arc<AclStorage>>) -> Self {
+		TicketAclStorage::with_clock(issuer, backend, Arc::new(SystemClock))
+	}
+
+	/// Create new tickets ACL storage, which uses given clock to expire tickets.
+	pub fn with_clock(issuer: Public, backend: Option<Arc<AclStorage>>, clock: Arc<Clock>) -> Self {
+		TicketAclStorage {
+			issuer: issuer,
+			backend: backend,
+			clock: clock,
+		}
+	}
+
+	/// Check if `requester` can access document with hash `document`, presenting given ticket.
+	/// Check fails if current time is not known, so that expired ticket is never accepted.
+	pub fn check_signed(&self, requester: &Requester, document: &DocumentAddress, ticket: &AccessTicket, signature: &Signature) -> Result<bool, Error> {
+		let requester = requester.resolve(document)?;
+		if ticket.requester != requester.address || ticket.document != *document {
+			trace!(target: "secretstore", "Access to document {:?} is denied: ticket is issued for other request", document);
+			return Ok(false);
+		}
+		if ticket.expires <= self.clock.unix_time()? {
+			trace!(target: "secretstore", "Access to document {:?} is denied: ticket has expired at {}", document, ticket.expires);
+			return Ok(false);
+		}
+		if !ethkey::verify_public(&self.issuer, signature, &ticket.hash())? {
+			warn!(target: "secretstore", "Access to document {:?} is denied: ticket is not signed by the issuer", document);
+			return Ok(false);
+		}
+
+		match self.backend {
+			Some(ref backend) => backend.check(&requester.into(), document),
+			None => Ok(true),
+		}
+	}
+}
+
+impl AclStorage for TicketAclStorage {
+	fn check(&self, _requester: &Requester, document: &DocumentAddress) -> Result<bool, Error> {
+		trace!(target: "secretstore", "Access to document {:?} is denied: ticket is required", document);
+		Ok(false)
+	}
+
+	fn check_scoped(&self, requester: &Requester, document: &DocumentAddress, _scope: &str) -> Result<bool, Error> {
+		self.check(requester, document)
+	}
+
+	fn fill_report(&self, report: &mut AclReport) {
+		if let Some(ref backend) = self.backend {
+			backend.fill_report(report);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use ethkey::{Random, Generator, KeyPair};
+	use types::all::{Error, DocumentAddress, Requester};
+	use acl_storage::AclStorage;
+	use acl_storage::tests::DummyAclStorage;
+	use acl_storage::clock::tests::{MockClock, MOCK_UNIX_START};
+	use super::{AccessTicket, TicketAclStorage, sign_ticket};
+
+	const NOW: u64 = MOCK_UNIX_START;
+
+	fn ticket(requester: &KeyPair, expires: u64) -> AccessTicket {
+		AccessTicket {
+			requester: requester.address(),
+			document: DocumentAddress::from(1),
+			expires: expires,
+		}
+	}
+
+	fn acl_storage(issuer: &KeyPair, backend: Option<Arc<AclStorage>>) -> TicketAclStorage {
+		TicketAclStorage::with_clock(issuer.public().clone(), backend, Arc::new(MockClock::default()))
+	}
+
+	#[test]
+	fn valid_ticket_grants_access() {
+		let issuer = Random.generate().unwrap();
+		let requester = Random.generate().unwrap();
+		let acl_storage = acl_storage(&issuer, None);
+		let ticket = ticket(&requester, NOW + 60);
+		let signature = sign_ticket(issuer.secret(), &ticket).unwrap();
+
+		let public = Requester::Public(requester.public().clone());
+		assert_eq!(acl_storage.check_signed(&public, &DocumentAddress::from(1), &ticket, &signature), Ok(true));
+		// ticket is bound to the document
+		assert_eq!(acl_storage.check_signed(&public, &DocumentAddress::from(2), &ticket, &signature), Ok(false));
+		// ticket is required
+		assert_eq!(acl_storage.check(&public, &DocumentAddress::from(1)), Ok(false));
+		assert_eq!(acl_storage.check_scoped(&public, &DocumentAddress::from(1), "decrypt"), Ok(false));
+	}
+
+	#[test]
+	fn expired_ticket_is_rejected() {
+		let issuer = Random.generate().unwrap();
+		let requester = Random.generate().unwrap();
+		let ticket = ticket(&requester, NOW);
+		let signature = sign_ticket(issuer.secret(), &ticket).unwrap();
+
+		let requester = Requester::Public(requester.public().clone());
+		assert_eq!(acl_storage(&issuer, None).check_signed(&requester, &DocumentAddress::from(1), &ticket, &signature), Ok(false));
+	}
+
+	#[test]
+	fn ticket_is_not_accepted_when_time_is_unknown() {
+		let issuer = Random.generate().unwrap();
+		let requester = Random.generate().unwrap();
+		let clock = Arc::new(MockClock::default());
+		let acl_storage = TicketAclStorage::with_clock(issuer.public().clone(), None, clock.clone());
+		let ticket = ticket(&requester, NOW + 60);
+		let signature = sign_ticket(issuer.secret(), &ticket).unwrap();
+
+		let requester = Requester::Public(requester.public().clone());
+		clock.set_unix_start(None);
+		assert_eq!(acl_storage.check_signed(&requester, &DocumentAddress::from(1), &ticket, &signature),
+			Err(Error::Internal("unix time is not available".into())));
+	}
+
+	#[test]
+	fn ticket_of_other_issuer_is_rejected() {
+		let issuer = Random.generate().unwrap();
+		let other_issuer = Random.generate().unwrap();
+		let requester = Random.generate().unwrap();
+		let ticket = ticket(&requester, NOW + 60);
+		let signature = sign_ticket(other_issuer.secret(), &ticket).unwrap();
+
+		let requester = Requester::Public(requester.public().clone());
+		assert_eq!(acl_storage(&issuer, None).check_signed(&requester, &DocumentAddress::from(1), &ticket, &signature), Ok(false));
+	}
+
+	#[test]
+	fn tampered_ticket_is_rejected() {
+		let issuer = Random.generate().unwrap();
+		let requester = Random.generate().unwrap();
+		let acl_storage = acl_storage(&issuer, None);
+		let ticket = ticket(&requester, NOW + 60);
+		let signature = sign_ticket(issuer.secret(), &ticket).unwrap();
+
+		let mut tampered = ticket.clone();
+		tampered.expires += 3600;
+		let public = Requester::Public(requester.public().clone());
+		assert_eq!(acl_storage.check_signed(&public, &DocumentAddress::from(1), &tampered, &signature), Ok(false));
+
+		// ticket, issued to other requester
+		let other_requester = Requester::Public(Random.generate().unwrap().public().clone());
+		assert_eq!(acl_storage.check_signed(&other_requester, &DocumentAddress::from(1), &ticket, &signature), Ok(false));
+		let mut tampered = ticket.clone();
+		tampered.requester = other_requester.address(&DocumentAddress::from(1)).unwrap();
+		assert_eq!(acl_storage.check_signed(&other_requester, &DocumentAddress::from(1), &tampered, &signature), Ok(false));
+	}
+
+	#[test]
+	fn backend_is_consulted_after_ticket_is_verified() {
+		let issuer = Random.generate().unwrap();
+		let requester = Random.generate().unwrap();
+		let backend = Arc::new(DummyAclStorage::default());
+		let acl_storage = acl_storage(&issuer, Some(backend.clone()));
+		let ticket = ticket(&requester, NOW + 60);
+		let signature = sign_ticket(issuer.secret(), &ticket).unwrap();
+		let public = Requester::Public(requester.public().clone());
+
+		assert_eq!(acl_storage.check_signed(&public, &DocumentAddress::from(1), &ticket, &signature), Ok(true));
+		backend.prohibit(requester.public().clone(), DocumentAddress::from(1));
+		assert_eq!(acl_storage.check_signed(&public, &DocumentAddress::from(1), &ticket, &signature), Ok(false));
+	}
+}
@@ -23,11 +23,14 @@ use hyper::server::{Server as HttpServer, Request as HttpRequest, Response as Ht
 	Listening as HttpListening};
 use serde_json;
 use url::percent_encoding::percent_decode;
+use ethkey;
 
-use util::ToPretty;
+use util::{ToPretty, H256, Hashable};
 use traits::KeyServer;
+use acl_storage::AclStorage;
 use serialization::SerializableDocumentEncryptedKeyShadow;
-use types::all::{Error, ServiceConfiguration, RequestSignature, DocumentAddress, DocumentEncryptedKey, DocumentEncryptedKeyShadow};
+use types::all::{Error, AclError, ServiceConfiguration, RequestSignature, DocumentAddress, DocumentEncryptedKey, DocumentEncryptedKeyShadow,
+	Address, Public};
 
 /// Key server http-requests listener
 pub struct KeyServerHttpListener<T: KeyServer + 'static> {
@@ -40,12 +43,14 @@ pub struct KeyServerHttpListener<T: KeyServer + 'static> {
 enum Request {
 	/// Invalid request
 	Invalid,
-	/// Generate encryption key.
-	GenerateDocumentKey(DocumentAddress, RequestSignature, usize),
+	/// Generate encryption key, allowing listed requestors to access the document regardless of ACL storage decision.
+	GenerateDocumentKey(DocumentAddress, RequestSignature, usize, Vec<Address>),
 	/// Request encryption key of given document for given requestor.
 	GetDocumentKey(DocumentAddress, RequestSignature),
 	/// Request shadow of encryption key of given document for given requestor.
 	GetDocumentKeyShadow(DocumentAddress, RequestSignature),
+	/// Request ACL storage report, signed by one of the cluster nodes.
+	GetAclReport(RequestSignature),
 }
 
 /// Cloneable http handler
@@ -56,13 +61,25 @@ struct KeyServerHttpHandler<T: KeyServer + 'static> {
 /// Shared http handler
 struct KeyServerSharedHttpHandler<T: KeyServer + 'static> {
 	key_server: T,
+	/// ACL storage, which state is reported. None if report is not served.
+	acl_storage: Option<Arc<AclStorage>>,
+	/// Public keys of cluster nodes, allowed to request the ACL storage report.
+	report_requestors: Vec<Public>,
 }
 
 impl<T> KeyServerHttpListener<T> where T: KeyServer + 'static {
 	/// Start KeyServer http listener
 	pub fn start(config: ServiceConfiguration, key_server: T) -> Result<Self, Error> {
+		KeyServerHttpListener::start_with_acl_report(config, key_server, None)
+	}
+
+	/// Start KeyServer http listener, which also serves state report of given ACL storage.
+	/// Report is only served to requests, signed by one of the cluster nodes (see `acl_report_message`).
+	pub fn start_with_acl_report(config: ServiceConfiguration, key_server: T, acl_storage: Option<Arc<AclStorage>>) -> Result<Self, Error> {
 		let shared_handler = Arc::new(KeyServerSharedHttpHandler {
 			key_server: key_server,
+			acl_storage: acl_storage,
+			report_requestors: config.cluster_config.nodes.keys().cloned().collect(),
 		});
 		let handler = KeyServerHttpHandler {
 			handler: shared_handler.clone(),
@@ -84,6 +101,11 @@ impl<T> KeyServer for KeyServerHttpListener<T> where T: KeyServer + 'static {
 		self.handler.key_server.generate_document_key(signature, document, threshold)
 	}
 
+	fn generate_document_key_with_allowed_requestors(&self, signature: &RequestSignature, document: &DocumentAddress, threshold: usize,
+		allowed_requestors: Vec<Address>) -> Result<DocumentEncryptedKey, Error> {
+		self.handler.key_server.generate_document_key_with_allowed_requestors(signature, document, threshold, allowed_requestors)
+	}
+
 	fn document_key(&self, signature: &RequestSignature, document: &DocumentAddress) -> Result<DocumentEncryptedKey, Error> {
 		self.handler.key_server.document_key(signature, document)
 	}
@@ -105,8 +127,8 @@ impl<T> HttpHandler for KeyServerHttpHandler<T> where T: KeyServer + 'static {
 		let req_uri = req.uri.clone();
 		match &req_uri {
 			&RequestUri::AbsolutePath(ref path) => match parse_request(&req_method, &path) {
-				Request::GenerateDocumentKey(document, signature, threshold) => {
-					return_document_key(req, res, self.handler.key_server.generate_document_key(&signature, &document, threshold)
+				Request::GenerateDocumentKey(document, signature, threshold, allowed_requestors) => {
+					return_document_key(req, res, self.handler.key_server.generate_document_key_with_allowed_requestors(&signature, &document, threshold, allowed_requestors)
 						.map_err(|err| {
 							warn!(target: "secretstore", "GenerateDocumentKey request {} has failed with: {}", req_uri, err);
 							err
@@ -147,6 +169,34 @@ impl<T> HttpHandler for KeyServerHttpHandler<T> where T: KeyServer + 'static {
 						Err(err) => return_error(res, err),
 					}
 				},
+				Request::GetAclReport(signature) => {
+					let acl_storage = match self.handler.acl_storage {
+						Some(ref acl_storage) => acl_storage,
+						None => {
+							warn!(target: "secretstore", "Ignoring {}-request {}: ACL report is not served", req_method, req_uri);
+							*res.status_mut() = HttpStatusCode::NotFound;
+							return;
+						},
+					};
+					match authorize_acl_report(&self.handler.report_requestors, &signature) {
+						Ok(()) => match serde_json::to_vec(&acl_storage.report()) {
+							Ok(report) => {
+								res.headers_mut().set(header::ContentType::json());
+								if let Err(err) = res.send(&report) {
+									// nothing to do, but to log an error
+									warn!(target: "secretstore", "response to request {} has failed with: {}", req.uri, err);
+								}
+							},
+							Err(err) => {
+								warn!(target: "secretstore", "response to request {} has failed with: {}", req.uri, err);
+							}
+						},
+						Err(err) => {
+							warn!(target: "secretstore", "GetAclReport request {} has failed with: {}", req_uri, err);
+							return_error(res, err);
+						},
+					}
+				},
 				Request::Invalid => {
 					warn!(target: "secretstore", "Ignoring invalid {}-request {}", req_method, req_uri);
 					*res.status_mut() = HttpStatusCode::BadRequest;
@@ -181,7 +231,27 @@ fn return_error(mut res: HttpResponse, err: Error) {
 		Error::DocumentNotFound => *res.status_mut() = HttpStatusCode::NotFound,
 		Error::Database(_) => *res.status_mut() = HttpStatusCode::InternalServerError,
 		Error::Internal(_) => *res.status_mut() = HttpStatusCode::InternalServerError,
+		Error::Acl(AclError::TooManyConcurrent) => *res.status_mut() = HttpStatusCode::TooManyRequests,
+		Error::Acl(AclError::Overloaded) => *res.status_mut() = HttpStatusCode::ServiceUnavailable,
+		Error::Acl(AclError::ServiceStarting) => *res.status_mut() = HttpStatusCode::ServiceUnavailable,
+		Error::Acl(_) => *res.status_mut() = HttpStatusCode::InternalServerError,
+	}
+}
+
+/// Message, which is signed by the cluster node to request ACL storage report.
+pub fn acl_report_message() -> H256 {
+	"acl_report".sha3()
+}
+
+/// Check that ACL storage report is requested by one of `requestors`.
+fn authorize_acl_report(requestors: &[Public], signature: &RequestSignature) -> Result<(), Error> {
+	let public = ethkey::recover(signature, &acl_report_message())
+		.map_err(|_| Error::BadSignature)?;
+	if !requestors.contains(&public) {
+		return Err(Error::AccessDenied);
 	}
+
+	Ok(())
 }
 
 fn parse_request(method: &HttpMethod, uri_path: &str) -> Request {
@@ -194,6 +264,12 @@ fn parse_request(method: &HttpMethod, uri_path: &str) -> Request {
 	if path.len() == 0 {
 		return Request::Invalid;
 	}
+	if &path[0] == "acl_report" {
+		return match (path.len(), method, path.get(1).map(|signature| signature.parse())) {
+			(2, &HttpMethod::Get, Some(Ok(signature))) => Request::GetAclReport(signature),
+			_ => Request::Invalid,
+		};
+	}
 	let (args_prefix, args_offset) = if &path[0] == "shadow" {
 		("shadow", 1)
 	} else {
@@ -208,18 +284,28 @@ fn parse_request(method: &HttpMethod, uri_path: &str) -> Request {
 	let document = path[args_offset].parse();
 	let signature = path[args_offset + 1].parse();
 	let threshold = (if args_len > args_offset + 2 { &path[args_offset + 2] } else { "" }).parse();
-	match (args_prefix, args_len, method, document, signature, threshold) {
-		("",		3, &HttpMethod::Post, Ok(document), Ok(signature), Ok(threshold)) => Request::GenerateDocumentKey(document, signature, threshold),
-		("",		2, &HttpMethod::Get, Ok(document), Ok(signature), _) => Request::GetDocumentKey(document, signature),
-		("shadow",	3, &HttpMethod::Get, Ok(document), Ok(signature), _) => Request::GetDocumentKeyShadow(document, signature),
+	let allowed_requestors = if args_len > args_offset + 3 { parse_allowed_requestors(&path[args_offset + 3]) } else { Ok(Vec::new()) };
+	match (args_prefix, args_len, method, document, signature, threshold, allowed_requestors) {
+		("",		3, &HttpMethod::Post, Ok(document), Ok(signature), Ok(threshold), _) => Request::GenerateDocumentKey(document, signature, threshold, Vec::new()),
+		("",		4, &HttpMethod::Post, Ok(document), Ok(signature), Ok(threshold), Ok(allowed_requestors)) =>
+			Request::GenerateDocumentKey(document, signature, threshold, allowed_requestors),
+		("",		2, &HttpMethod::Get, Ok(document), Ok(signature), _, _) => Request::GetDocumentKey(document, signature),
+		("shadow",	3, &HttpMethod::Get, Ok(document), Ok(signature), _, _) => Request::GetDocumentKeyShadow(document, signature),
 		_ => Request::Invalid,
 	}
 }
 
+/// Parse comma-separated list of allowed requestors addresses.
+fn parse_allowed_requestors(list: &str) -> Result<Vec<Address>, ()> {
+	list.split(',').map(|address| address.parse().map_err(|_| ())).collect()
+}
+
 #[cfg(test)]
 mod tests {
 	use hyper::method::Method as HttpMethod;
-	use super::{parse_request, Request};
+	use ethkey::{self, Random, Generator};
+	use types::all::{Error, Address};
+	use super::{parse_request, authorize_acl_report, acl_report_message, Request};
 
 	#[test]
 	fn parse_request_successful() {
@@ -231,6 +317,41 @@ mod tests {
 				"a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01".parse().unwrap()));
 	}
 
+	#[test]
+	fn parse_generation_request_with_allowed_requestors() {
+		assert_eq!(parse_request(&HttpMethod::Post, "/0000000000000000000000000000000000000000000000000000000000000001/a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01/2"),
+			Request::GenerateDocumentKey("0000000000000000000000000000000000000000000000000000000000000001".into(),
+				"a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01".parse().unwrap(),
+				2, Vec::new()));
+		assert_eq!(parse_request(&HttpMethod::Post, "/0000000000000000000000000000000000000000000000000000000000000001/a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01/2/0000000000000000000000000000000000000001,0000000000000000000000000000000000000002"),
+			Request::GenerateDocumentKey("0000000000000000000000000000000000000000000000000000000000000001".into(),
+				"a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01".parse().unwrap(),
+				2, vec![Address::from(1), Address::from(2)]));
+		assert_eq!(parse_request(&HttpMethod::Post, "/0000000000000000000000000000000000000000000000000000000000000001/a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01/2/not-an-address"),
+			Request::Invalid);
+	}
+
+	#[test]
+	fn parse_acl_report_request() {
+		assert_eq!(parse_request(&HttpMethod::Get, "/acl_report/a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01"),
+			Request::GetAclReport("a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01".parse().unwrap()));
+		assert_eq!(parse_request(&HttpMethod::Post, "/acl_report/a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01"),
+			Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Get, "/acl_report"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Get, "/acl_report/not-a-signature"), Request::Invalid);
+	}
+
+	#[test]
+	fn acl_report_is_only_served_to_cluster_nodes() {
+		let node = Random.generate().unwrap();
+		let other = Random.generate().unwrap();
+		let requestors = vec![node.public().clone()];
+
+		assert_eq!(authorize_acl_report(&requestors, &ethkey::sign(node.secret(), &acl_report_message()).unwrap()), Ok(()));
+		assert_eq!(authorize_acl_report(&requestors, &ethkey::sign(other.secret(), &acl_report_message()).unwrap()), Err(Error::AccessDenied));
+		assert_eq!(authorize_acl_report(&requestors, &Default::default()), Err(Error::BadSignature));
+	}
+
 	#[test]
 	fn parse_request_failed() {
 		assert_eq!(parse_request(&HttpMethod::Get, "/0000000000000000000000000000000000000000000000000000000000000001"), Request::Invalid);
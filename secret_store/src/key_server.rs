@@ -26,7 +26,7 @@ use super::acl_storage::AclStorage;
 use super::key_storage::KeyStorage;
 use key_server_cluster::ClusterCore;
 use traits::KeyServer;
-use types::all::{Error, RequestSignature, DocumentAddress, DocumentEncryptedKey, DocumentEncryptedKeyShadow, ClusterConfiguration};
+use types::all::{Error, RequestSignature, DocumentAddress, DocumentEncryptedKey, DocumentEncryptedKeyShadow, ClusterConfiguration, Address};
 use key_server_cluster::{ClusterClient, ClusterConfiguration as NetClusterConfiguration};
 
 /// Secret store key server implementation
@@ -58,12 +58,17 @@ impl KeyServerImpl {
 
 impl KeyServer for KeyServerImpl {
 	fn generate_document_key(&self, signature: &RequestSignature, document: &DocumentAddress, threshold: usize) -> Result<DocumentEncryptedKey, Error> {
+		self.generate_document_key_with_allowed_requestors(signature, document, threshold, Vec::new())
+	}
+
+	fn generate_document_key_with_allowed_requestors(&self, signature: &RequestSignature, document: &DocumentAddress, threshold: usize,
+		allowed_requestors: Vec<Address>) -> Result<DocumentEncryptedKey, Error> {
 		// recover requestor' public key from signature
 		let public = ethkey::recover(signature, document)
 			.map_err(|_| Error::BadSignature)?;
 
 		// generate document key
-		let encryption_session = self.data.lock().cluster.new_encryption_session(document.clone(), threshold)?;
+		let encryption_session = self.data.lock().cluster.new_encryption_session(document.clone(), threshold, allowed_requestors)?;
 		let document_key = encryption_session.wait()?;
 
 		// encrypt document key with requestor public key
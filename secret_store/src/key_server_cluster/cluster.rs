@@ -27,6 +27,7 @@ use tokio_io::IoFuture;
 use tokio_core::reactor::{Handle, Remote, Timeout, Interval};
 use tokio_core::net::{TcpListener, TcpStream};
 use ethkey::{Secret, KeyPair, Signature, Random, Generator};
+use util::Address;
 use key_server_cluster::{Error, NodeId, SessionId, EncryptionConfiguration, AclStorage, KeyStorage};
 use key_server_cluster::message::{self, Message, ClusterMessage, EncryptionMessage, DecryptionMessage};
 use key_server_cluster::decryption_session::{SessionImpl as DecryptionSessionImpl, DecryptionSessionId,
@@ -42,8 +43,9 @@ pub type BoxedEmptyFuture = BoxFuture<(), ()>;
 pub trait ClusterClient: Send + Sync {
 	/// Get cluster state.
 	fn cluster_state(&self) -> ClusterState;
-	/// Start new encryption session.
-	fn new_encryption_session(&self, session_id: SessionId, threshold: usize) -> Result<Arc<EncryptionSession>, Error>;
+	/// Start new encryption session. `allowed_requestors` are stored with every key share and are allowed
+	/// to access the document regardless of ACL storage decision.
+	fn new_encryption_session(&self, session_id: SessionId, threshold: usize, allowed_requestors: Vec<Address>) -> Result<Arc<EncryptionSession>, Error>;
 	/// Start new decryption session.
 	fn new_decryption_session(&self, session_id: SessionId, requestor_signature: Signature, is_shadow_decryption: bool) -> Result<Arc<DecryptionSession>, Error>;
 }
@@ -860,13 +862,13 @@ impl ClusterClient for ClusterClientImpl {
 		self.data.connections.cluster_state()
 	}
 
-	fn new_encryption_session(&self, session_id: SessionId, threshold: usize) -> Result<Arc<EncryptionSession>, Error> {
+	fn new_encryption_session(&self, session_id: SessionId, threshold: usize, allowed_requestors: Vec<Address>) -> Result<Arc<EncryptionSession>, Error> {
 		let mut connected_nodes = self.data.connections.connected_nodes();
 		connected_nodes.insert(self.data.self_key_pair.public().clone());
 
 		let cluster = Arc::new(ClusterView::new(self.data.clone(), connected_nodes.clone()));
 		let session = self.data.sessions.new_encryption_session(self.data.self_key_pair.public().clone(), session_id, cluster)?;
-		session.initialize(threshold, connected_nodes)?;
+		session.initialize_with_allowed_requestors(threshold, connected_nodes, allowed_requestors)?;
 		Ok(session)
 	}
 
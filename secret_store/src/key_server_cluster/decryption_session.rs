@@ -20,6 +20,7 @@ use std::sync::Arc;
 use parking_lot::{Mutex, Condvar};
 use ethcrypto::ecies::encrypt_single_message;
 use ethkey::{self, Secret, Public, Signature};
+use types::all::Requester;
 use key_server_cluster::{Error, AclStorage, DocumentKeyShare, NodeId, SessionId, DocumentEncryptedKeyShadow};
 use key_server_cluster::cluster::Cluster;
 use key_server_cluster::math;
@@ -211,7 +212,7 @@ impl SessionImpl {
 		data.requested_nodes.extend(self.encrypted_data.id_numbers.keys().cloned());
 
 		// ..and finally check access on our's own
-		let is_requestor_allowed_to_read = self.acl_storage.check(&requestor_public, &self.id).unwrap_or(false);
+		let is_requestor_allowed_to_read = self.acl_storage.check(&Requester::Public(requestor_public.clone()), &self.id).unwrap_or(false);
 		process_initialization_response(&self.encrypted_data, &mut *data, self.node(), is_requestor_allowed_to_read)?;
 
 		// check if we have enough nodes to decrypt data
@@ -260,7 +261,7 @@ impl SessionImpl {
 		let requestor_public = ethkey::recover(&message.requestor_signature, &self.id)?;
 
 		// check access
-		let is_requestor_allowed_to_read = self.acl_storage.check(&requestor_public, &self.id).unwrap_or(false);
+		let is_requestor_allowed_to_read = self.acl_storage.check(&Requester::Public(requestor_public.clone()), &self.id).unwrap_or(false);
 		data.state = if is_requestor_allowed_to_read { SessionState::WaitingForPartialDecryptionRequest }
 			else { SessionState::Failed };
 		data.requestor = Some(requestor_public);
@@ -593,6 +594,7 @@ mod tests {
 			secret_share: secret_shares[i].clone(),
 			common_point: common_point.clone(),
 			encrypted_point: encrypted_point.clone(),
+			allowed_requestors: Vec::new(),
 		}).collect();
 		let acl_storages: Vec<_> = (0..5).map(|_| Arc::new(DummyAclStorage::default())).collect();
 		let clusters: Vec<_> = (0..5).map(|i| Arc::new(DummyCluster::new(id_numbers.iter().nth(i).clone().unwrap().0))).collect();
@@ -644,6 +646,7 @@ mod tests {
 				secret_share: Random.generate().unwrap().secret().clone(),
 				common_point: Random.generate().unwrap().public().clone(),
 				encrypted_point: Random.generate().unwrap().public().clone(),
+				allowed_requestors: Vec::new(),
 			},
 			acl_storage: Arc::new(DummyAclStorage::default()),
 			cluster: Arc::new(DummyCluster::new(self_node_id.clone())),
@@ -669,6 +672,7 @@ mod tests {
 				secret_share: Random.generate().unwrap().secret().clone(),
 				common_point: Random.generate().unwrap().public().clone(),
 				encrypted_point: Random.generate().unwrap().public().clone(),
+				allowed_requestors: Vec::new(),
 			},
 			acl_storage: Arc::new(DummyAclStorage::default()),
 			cluster: Arc::new(DummyCluster::new(self_node_id.clone())),
@@ -694,6 +698,7 @@ mod tests {
 				secret_share: Random.generate().unwrap().secret().clone(),
 				common_point: Random.generate().unwrap().public().clone(),
 				encrypted_point: Random.generate().unwrap().public().clone(),
+				allowed_requestors: Vec::new(),
 			},
 			acl_storage: Arc::new(DummyAclStorage::default()),
 			cluster: Arc::new(DummyCluster::new(self_node_id.clone())),
@@ -19,7 +19,8 @@ use std::fmt::{Debug, Formatter, Error as FmtError};
 use std::sync::Arc;
 use parking_lot::{Condvar, Mutex};
 use ethkey::{Public, Secret};
-use key_server_cluster::{Error, NodeId, SessionId, KeyStorage, DocumentKeyShare};
+use util::Address;
+use key_server_cluster::{Error, NodeId, SessionId, KeyStorage, DocumentKeyShare, MAX_ALLOWED_REQUESTORS};
 use key_server_cluster::math;
 use key_server_cluster::cluster::Cluster;
 use key_server_cluster::message::{Message, EncryptionMessage, InitializeSession, ConfirmInitialization, CompleteInitialization,
@@ -89,6 +90,8 @@ struct SessionData {
 	derived_point: Option<Public>,
 	/// Nodes-specific data.
 	nodes: BTreeMap<NodeId, NodeData>,
+	/// Requestors, which are allowed to access the document regardless of ACL storage decision.
+	allowed_requestors: Vec<Address>,
 
 	// === Values, filled during KD phase ===
 	/// Value of polynom1[0], generated by this node.
@@ -197,6 +200,7 @@ impl SessionImpl {
 				threshold: None,
 				derived_point: None,
 				nodes: BTreeMap::new(),
+				allowed_requestors: Vec::new(),
 				secret_coeff: None,
 				secret_share: None,
 				joint_public: None,
@@ -234,8 +238,15 @@ impl SessionImpl {
 
 	/// Start new session initialization. This must be called on master node.
 	pub fn initialize(&self, threshold: usize, nodes: BTreeSet<NodeId>) -> Result<(), Error> {
+		self.initialize_with_allowed_requestors(threshold, nodes, Vec::new())
+	}
+
+	/// Start new session initialization, storing requestors, which are allowed to access the document
+	/// regardless of ACL storage decision, with every key share. This must be called on master node.
+	pub fn initialize_with_allowed_requestors(&self, threshold: usize, nodes: BTreeSet<NodeId>, allowed_requestors: Vec<Address>) -> Result<(), Error> {
 		check_cluster_nodes(self.node(), &nodes)?;
 		check_threshold(threshold, &nodes)?;
+		check_allowed_requestors(&allowed_requestors)?;
 
 		let mut data = self.data.lock();
 
@@ -247,6 +258,7 @@ impl SessionImpl {
 		// update state
 		data.master = Some(self.node().clone());
 		data.threshold = Some(threshold);
+		data.allowed_requestors = allowed_requestors;
 		for node_id in &nodes {
 			// generate node identification parameter
 			let node_id_number = math::generate_random_scalar()?;
@@ -336,6 +348,7 @@ impl SessionImpl {
 					nodes: data.nodes.iter().map(|(id, data)| (id.clone().into(), data.id_number.clone().into())).collect(),
 					threshold: data.threshold.expect("threshold is filled in initialization phase; KD phase follows initialization phase; qed"),
 					derived_point: derived_point.into(),
+					allowed_requestors: data.allowed_requestors.iter().cloned().map(Into::into).collect(),
 				})))?;
 			},
 		}
@@ -354,6 +367,8 @@ impl SessionImpl {
 		let nodes_ids = message.nodes.keys().cloned().map(Into::into).collect();
 		check_cluster_nodes(self.node(), &nodes_ids)?;
 		check_threshold(message.threshold, &nodes_ids)?;
+		let allowed_requestors: Vec<Address> = message.allowed_requestors.iter().cloned().map(Into::into).collect();
+		check_allowed_requestors(&allowed_requestors)?;
 
 		let mut data = self.data.lock();
 
@@ -369,6 +384,7 @@ impl SessionImpl {
 		data.threshold = Some(message.threshold);
 		data.derived_point = Some(message.derived_point.clone().into());
 		data.nodes = message.nodes.iter().map(|(id, number)| (id.clone().into(), NodeData::with_id_number(number.clone().into()))).collect();
+		data.allowed_requestors = allowed_requestors;
 
 		// now it is time for keys dissemination (KD) phase
 		drop(data);
@@ -614,6 +630,7 @@ impl SessionImpl {
 			secret_share: data.secret_share.as_ref().expect("secret_share is filled in KG phase; we are at the end of KG phase; qed").clone(),
 			common_point: encrypted_secret_point.common_point,
 			encrypted_point: encrypted_secret_point.encrypted_point,
+			allowed_requestors: data.allowed_requestors.clone(),
 		};
 		self.key_storage.insert(self.id.clone(), encrypted_data.clone())
 			.map_err(|e| Error::KeyStorage(e.into()))?;
@@ -667,6 +684,7 @@ impl SessionImpl {
 				secret_share: data.secret_share.as_ref().expect("secret_share is filled in KG phase; we are at the end of KG phase; qed").clone(),
 				common_point: message.common_point.clone().into(),
 				encrypted_point: message.encrypted_point.clone().into(),
+				allowed_requestors: data.allowed_requestors.clone(),
 			};
 			self.key_storage.insert(self.id.clone(), encrypted_data.clone())
 				.map_err(|e| Error::KeyStorage(e.into()))?;
@@ -882,6 +900,15 @@ pub fn check_threshold(threshold: usize, nodes: &BTreeSet<NodeId>) -> Result<(),
 	Ok(())
 }
 
+/// Check that the list of allowed requestors fits into the key share.
+fn check_allowed_requestors(allowed_requestors: &[Address]) -> Result<(), Error> {
+	if allowed_requestors.len() > MAX_ALLOWED_REQUESTORS {
+		return Err(Error::InvalidMessage);
+	}
+
+	Ok(())
+}
+
 #[cfg(test)]
 mod tests {
 	use std::time;
@@ -889,7 +916,8 @@ mod tests {
 	use std::collections::{BTreeSet, BTreeMap, VecDeque};
 	use tokio_core::reactor::Core;
 	use ethkey::{Random, Generator};
-	use key_server_cluster::{NodeId, SessionId, Error, DummyKeyStorage};
+	use util::Address;
+	use key_server_cluster::{NodeId, SessionId, Error, DummyKeyStorage, MAX_ALLOWED_REQUESTORS};
 	use key_server_cluster::message::{self, Message, EncryptionMessage};
 	use key_server_cluster::cluster::tests::{DummyCluster, make_clusters, run_clusters, loop_until, all_connections_established};
 	use key_server_cluster::encryption_session::{Session, SessionImpl, SessionState, SessionParams};
@@ -1116,6 +1144,7 @@ mod tests {
 			nodes: nodes.into_iter().map(|(k, v)| (k.into(), v.into())).collect(),
 			threshold: 0,
 			derived_point: math::generate_random_point().unwrap().into(),
+			allowed_requestors: Vec::new(),
 		}).unwrap_err(), Error::InvalidNodesCount);
 	}
 
@@ -1130,6 +1159,7 @@ mod tests {
 			nodes: nodes.into_iter().map(|(k, v)| (k.into(), v.into())).collect(),
 			threshold: 0,
 			derived_point: math::generate_random_point().unwrap().into(),
+			allowed_requestors: Vec::new(),
 		}).unwrap_err(), Error::InvalidNodesConfiguration);
 	}
 
@@ -1144,6 +1174,7 @@ mod tests {
 			nodes: nodes.into_iter().map(|(k, v)| (k.into(), v.into())).collect(),
 			threshold: 2,
 			derived_point: math::generate_random_point().unwrap().into(),
+			allowed_requestors: Vec::new(),
 		}).unwrap_err(), Error::InvalidThreshold);
 	}
 
@@ -1158,6 +1189,7 @@ mod tests {
 			nodes: nodes.into_iter().map(|(k, v)| (k.into(), v.into())).collect(),
 			threshold: 0,
 			derived_point: math::generate_random_point().unwrap().into(),
+			allowed_requestors: Vec::new(),
 		}).unwrap_err(), Error::InvalidStateForRequest);
 	}
 
@@ -1177,6 +1209,7 @@ mod tests {
 			nodes: nodes.into_iter().map(|(k, v)| (k.into(), v.into())).collect(),
 			threshold: 0,
 			derived_point: math::generate_random_point().unwrap().into(),
+			allowed_requestors: Vec::new(),
 		}).unwrap_err(), Error::InvalidMessage);
 	}
 
@@ -1368,6 +1401,31 @@ mod tests {
 		}).unwrap_err(), Error::InvalidMessage);
 	}
 
+	#[test]
+	fn allowed_requestors_are_stored_with_every_key_share() {
+		let mut l = MessageLoop::new(3);
+		let allowed_requestors = vec![Address::from(1), Address::from(2)];
+		l.master().initialize_with_allowed_requestors(1, l.nodes.keys().cloned().collect(), allowed_requestors.clone()).unwrap();
+		l.take_and_process_all_messages().unwrap();
+		for node in l.nodes.values() {
+			node.session.start_key_generation_phase().unwrap();
+		}
+		l.take_and_process_all_messages().unwrap();
+
+		for node in l.nodes.values() {
+			assert_eq!(node.session.state(), SessionState::Finished);
+			assert_eq!(node.session.key_storage.get(&l.session_id).unwrap().allowed_requestors, allowed_requestors);
+		}
+	}
+
+	#[test]
+	fn fails_to_initialize_with_too_many_allowed_requestors() {
+		let l = MessageLoop::new(2);
+		let allowed_requestors = (0..MAX_ALLOWED_REQUESTORS as u64 + 1).map(Address::from).collect();
+		assert_eq!(l.master().initialize_with_allowed_requestors(0, l.nodes.keys().cloned().collect(), allowed_requestors).unwrap_err(),
+			Error::InvalidMessage);
+	}
+
 	#[test]
 	fn complete_enc_dec_session() {
 		let test_cases = [(0, 5), (2, 5), (3, 5)];
@@ -1436,7 +1494,7 @@ mod tests {
 
 			// run session to completion
 			let session_id = SessionId::default();
-			let session = clusters[0].client().new_encryption_session(session_id, threshold).unwrap();
+			let session = clusters[0].client().new_encryption_session(session_id, threshold, Vec::new()).unwrap();
 			loop_until(&mut core, time::Duration::from_millis(1000), || session.joint_public_key().is_some());
 		}
 	}
@@ -18,7 +18,7 @@ use std::fmt;
 use std::collections::{BTreeSet, BTreeMap};
 use ethkey::Secret;
 use key_server_cluster::SessionId;
-use super::{SerializableH256, SerializablePublic, SerializableSecret, SerializableSignature};
+use super::{SerializableH256, SerializablePublic, SerializableSecret, SerializableSignature, SerializableAddress};
 
 pub type MessageSessionId = SerializableH256;
 pub type MessageNodeId = SerializablePublic;
@@ -145,6 +145,9 @@ pub struct CompleteInitialization {
 	pub threshold: usize,
 	/// Derived generation point.
 	pub derived_point: SerializablePublic,
+	/// Requestors, which are allowed to access the document regardless of ACL storage decision.
+	#[serde(default)]
+	pub allowed_requestors: Vec<SerializableAddress>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
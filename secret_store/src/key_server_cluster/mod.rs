@@ -22,8 +22,8 @@ use super::types::all::DocumentAddress;
 
 pub use super::types::all::{NodeId, EncryptionConfiguration, DocumentEncryptedKeyShadow};
 pub use super::acl_storage::AclStorage;
-pub use super::key_storage::{KeyStorage, DocumentKeyShare};
-pub use super::serialization::{SerializableSignature, SerializableH256, SerializableSecret, SerializablePublic};
+pub use super::key_storage::{KeyStorage, DocumentKeyShare, MAX_ALLOWED_REQUESTORS};
+pub use super::serialization::{SerializableSignature, SerializableH256, SerializableSecret, SerializablePublic, SerializableAddress};
 pub use self::cluster::{ClusterCore, ClusterConfiguration, ClusterClient};
 pub use self::encryption_session::Session as EncryptionSession;
 pub use self::decryption_session::Session as DecryptionSession;
@@ -18,9 +18,12 @@ use std::path::PathBuf;
 use std::collections::BTreeMap;
 use serde_json;
 use ethkey::{Secret, Public};
-use util::Database;
+use util::{Database, Address};
 use types::all::{Error, ServiceConfiguration, DocumentAddress, NodeId};
-use serialization::{SerializablePublic, SerializableSecret};
+use serialization::{SerializablePublic, SerializableSecret, SerializableAddress};
+
+/// Max number of requestors, which could be allowed to access the document in addition to the ACL storage.
+pub const MAX_ALLOWED_REQUESTORS: usize = 16;
 
 #[derive(Debug, Clone, PartialEq)]
 /// Encrypted key share, stored by key storage on the single key server.
@@ -35,6 +38,8 @@ pub struct DocumentKeyShare {
 	pub common_point: Public,
 	/// Encrypted point.
 	pub encrypted_point: Public,
+	/// Requestors, which are allowed to access the document regardless of ACL storage decision.
+	pub allowed_requestors: Vec<Address>,
 }
 
 /// Document encryption keys storage
@@ -65,6 +70,9 @@ struct SerializableDocumentKeyShare {
 	pub common_point: SerializablePublic,
 	/// Encrypted point.
 	pub encrypted_point: SerializablePublic,
+	/// Requestors, which are allowed to access the document regardless of ACL storage decision.
+	#[serde(default)]
+	pub allowed_requestors: Vec<SerializableAddress>,
 }
 
 impl PersistentKeyStorage {
@@ -105,6 +113,22 @@ impl KeyStorage for PersistentKeyStorage {
 	}
 }
 
+/// Export document key share (including the requestors, allowed to access the document) for backup.
+pub fn export_key_share(key_storage: &KeyStorage, document: &DocumentAddress) -> Result<Vec<u8>, Error> {
+	let key: SerializableDocumentKeyShare = key_storage.get(document)?.into();
+	serde_json::to_vec(&key).map_err(|e| Error::Internal(e.to_string()))
+}
+
+/// Import document key share, previously exported with `export_key_share`.
+pub fn import_key_share(key_storage: &KeyStorage, document: DocumentAddress, data: &[u8]) -> Result<(), Error> {
+	let key = serde_json::from_slice::<SerializableDocumentKeyShare>(data).map_err(|e| Error::Internal(e.to_string()))?;
+	let key: DocumentKeyShare = key.into();
+	if key.allowed_requestors.len() > MAX_ALLOWED_REQUESTORS {
+		return Err(Error::Internal(format!("too many allowed requestors: {}", key.allowed_requestors.len())));
+	}
+	key_storage.insert(document, key)
+}
+
 impl From<DocumentKeyShare> for SerializableDocumentKeyShare {
 	fn from(key: DocumentKeyShare) -> Self {
 		SerializableDocumentKeyShare {
@@ -113,6 +137,7 @@ impl From<DocumentKeyShare> for SerializableDocumentKeyShare {
 			secret_share: key.secret_share.into(),
 			common_point: key.common_point.into(),
 			encrypted_point: key.encrypted_point.into(),
+			allowed_requestors: key.allowed_requestors.into_iter().map(Into::into).collect(),
 		}
 	}
 }
@@ -125,6 +150,7 @@ impl From<SerializableDocumentKeyShare> for DocumentKeyShare {
 			secret_share: key.secret_share.into(),
 			common_point: key.common_point.into(),
 			encrypted_point: key.encrypted_point.into(),
+			allowed_requestors: key.allowed_requestors.into_iter().map(Into::into).collect(),
 		}
 	}
 }
@@ -137,7 +163,7 @@ pub mod tests {
 	use ethkey::{Random, Generator};
 	use super::super::types::all::{Error, NodeAddress, ServiceConfiguration, ClusterConfiguration,
 		DocumentAddress, EncryptionConfiguration};
-	use super::{KeyStorage, PersistentKeyStorage, DocumentKeyShare};
+	use super::{KeyStorage, PersistentKeyStorage, DocumentKeyShare, MAX_ALLOWED_REQUESTORS, export_key_share, import_key_share};
 
 	#[derive(Default)]
 	/// In-memory document encryption keys storage
@@ -193,6 +219,7 @@ pub mod tests {
 			secret_share: Random.generate().unwrap().secret().clone(),
 			common_point: Random.generate().unwrap().public().clone(),
 			encrypted_point: Random.generate().unwrap().public().clone(),
+			allowed_requestors: vec![Random.generate().unwrap().address()],
 		};
 		let key2 = DocumentAddress::from(2);
 		let value2 = DocumentKeyShare {
@@ -203,6 +230,7 @@ pub mod tests {
 			secret_share: Random.generate().unwrap().secret().clone(),
 			common_point: Random.generate().unwrap().public().clone(),
 			encrypted_point: Random.generate().unwrap().public().clone(),
+			allowed_requestors: Vec::new(),
 		};
 		let key3 = DocumentAddress::from(3);
 
@@ -219,4 +247,34 @@ pub mod tests {
 		assert_eq!(key_storage.get(&key2), Ok(value2));
 		assert_eq!(key_storage.get(&key3), Err(Error::DocumentNotFound));
 	}
+
+	#[test]
+	fn exported_key_share_preserves_allowed_requestors() {
+		let document = DocumentAddress::from(1);
+		let key = DocumentKeyShare {
+			threshold: 1,
+			id_numbers: vec![
+				(Random.generate().unwrap().public().clone(), Random.generate().unwrap().secret().clone())
+			].into_iter().collect(),
+			secret_share: Random.generate().unwrap().secret().clone(),
+			common_point: Random.generate().unwrap().public().clone(),
+			encrypted_point: Random.generate().unwrap().public().clone(),
+			allowed_requestors: vec![Random.generate().unwrap().address(), Random.generate().unwrap().address()],
+		};
+		let key_storage = DummyKeyStorage::default();
+		key_storage.insert(document.clone(), key.clone()).unwrap();
+		let exported = export_key_share(&key_storage, &document).unwrap();
+
+		let restored_storage = DummyKeyStorage::default();
+		import_key_share(&restored_storage, document.clone(), &exported).unwrap();
+		assert_eq!(restored_storage.get(&document), Ok(key.clone()));
+		assert_eq!(export_key_share(&key_storage, &DocumentAddress::from(2)), Err(Error::DocumentNotFound));
+
+		// oversized list is rejected on import
+		let mut oversized = key;
+		oversized.allowed_requestors = (0..MAX_ALLOWED_REQUESTORS + 1).map(|_| Random.generate().unwrap().address()).collect();
+		key_storage.insert(document.clone(), oversized).unwrap();
+		let exported = export_key_share(&key_storage, &document).unwrap();
+		assert!(import_key_share(&DummyKeyStorage::default(), document, &exported).is_err());
+	}
 }
@@ -59,17 +59,34 @@ mod serialization;
 use std::sync::Arc;
 use ethcore::client::Client;
 
-pub use types::all::{DocumentAddress, DocumentKey, DocumentEncryptedKey, RequestSignature, Public,
-	Error, NodeAddress, ServiceConfiguration, ClusterConfiguration, EncryptionConfiguration};
+pub use types::all::{DocumentAddress, DocumentKey, DocumentEncryptedKey, RequestSignature, Public, Requester,
+	Error, AclError, NodeAddress, ServiceConfiguration, ClusterConfiguration, EncryptionConfiguration};
 pub use traits::{KeyServer};
+pub use key_storage::{export_key_share, import_key_share};
+pub use acl_storage::{AclStorage, AclClient, AclContext, DecisionBlock, CacheableDecision, OnChainAclStorage, OnChainAclStorageConfig, DocumentAddressScheme, DecisionTransform, CandidateAddresses, namespaced_document,
+	CachedAclStorage, CachedAclStorageConfig, CacheMode, GrantOnlyCacheAclStorage, PrewarmStats, AuditedAclStorage, AclAuditEvent, DecisionReceiver, OverrideAclStorage, AdminResolver,
+	CompositeAclStorage, CombinePolicy, CompositeDecision, OnBackendError, TemporaryGrantAclStorage, TemporaryGrantAclStorageConfig, Clock, SystemClock,
+	NetworkGatedAclStorage, IpNetwork, AclDecision, sign_decision, verify_decision,
+	SingleUseAclStorage, AclReport, CacheReport, FailClosedAclStorage,
+	PermissionChange, PermissionChangeInvalidator, AttributeAclStorage, AttributeAclStorageConfig, AttributeExpression, AttributeResolver,
+	SessionLimitAclStorage, SessionGuard, MerkleAuditLog, MerkleProof, audit_leaf, AclService, AclRequest,
+	CacheAuditor, CacheAuditorConfig, CacheMismatch, MismatchHandler, EffectivePolicy, PolicyEntry,
+	TicketAclStorage, AccessTicket, sign_ticket, AclGovernor, AclWork, GovernedAclStorage,
+	AuditReplayer, ReplayRecord, ReplayReport, ReplayMismatch,
+	CommitAclStorage, GrantToken, AccessOutcome, AccessRecord, AccessHandler, StartupGateAclStorage, StartupGateConfig,
+	StartupBehavior, ReadinessProbe, AclEvent, AclEventSink, AclEventStream, EventStreamAclStorage, LogEventSink, FileEventSink,
+	ChannelEventSink, AclEventReceiver};
 
 /// Start new key server instance
 pub fn start(client: Arc<Client>, config: ServiceConfiguration) -> Result<Box<KeyServer>, Error> {
 	use std::sync::Arc;
 
-	let acl_storage = Arc::new(acl_storage::OnChainAclStorage::new(client));
+	// every check of the node is admitted by the same governor, so that overloaded node rejects checks
+	// instead of queueing them
+	let governor = Arc::new(acl_storage::AclGovernor::default());
+	let acl_storage: Arc<acl_storage::AclStorage> = Arc::new(acl_storage::GovernedAclStorage::new(Arc::new(acl_storage::OnChainAclStorage::new(client)), governor));
 	let key_storage = Arc::new(key_storage::PersistentKeyStorage::new(&config)?);
-	let key_server = key_server::KeyServerImpl::new(&config.cluster_config, acl_storage, key_storage)?;
-	let listener = http_listener::KeyServerHttpListener::start(config, key_server)?;
+	let key_server = key_server::KeyServerImpl::new(&config.cluster_config, acl_storage.clone(), key_storage)?;
+	let listener = http_listener::KeyServerHttpListener::start_with_acl_report(config, key_server, Some(acl_storage))?;
 	Ok(Box::new(listener))
 }
@@ -21,7 +21,8 @@ use rustc_serialize::hex::{ToHex, FromHex};
 use serde::{Serialize, Deserialize, Serializer, Deserializer};
 use serde::de::{Visitor, Error as SerdeError};
 use ethkey::{Public, Secret, Signature};
-use util::{H256, Bytes};
+use util::{H256, Address, Bytes};
+use types::all::Requester;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 /// Serializable shadow decryption result.
@@ -34,6 +35,40 @@ pub struct SerializableDocumentEncryptedKeyShadow {
 	pub decrypt_shadows: Vec<SerializableBytes>,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+/// Serializable requester.
+pub enum SerializableRequester {
+	/// Requester public key.
+	#[serde(rename = "public")]
+	Public(SerializablePublic),
+	/// Requester address.
+	#[serde(rename = "address")]
+	Address(SerializableAddress),
+	/// Signature of the document address, made by the requester.
+	#[serde(rename = "signature")]
+	Signature(SerializableSignature),
+}
+
+impl From<Requester> for SerializableRequester {
+	fn from(requester: Requester) -> Self {
+		match requester {
+			Requester::Public(public) => SerializableRequester::Public(public.into()),
+			Requester::Address(address) => SerializableRequester::Address(address.into()),
+			Requester::Signature(signature) => SerializableRequester::Signature(signature.into()),
+		}
+	}
+}
+
+impl From<SerializableRequester> for Requester {
+	fn from(requester: SerializableRequester) -> Self {
+		match requester {
+			SerializableRequester::Public(public) => Requester::Public(public.into()),
+			SerializableRequester::Address(address) => Requester::Address(address.into()),
+			SerializableRequester::Signature(signature) => Requester::Signature(signature.into()),
+		}
+	}
+}
+
 #[derive(Clone, Debug)]
 /// Serializable Bytes.
 pub struct SerializableBytes(Bytes);
@@ -128,7 +163,7 @@ impl Deserialize for SerializableSignature {
 	}
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 /// Serializable H256.
 pub struct SerializableH256(H256);
 
@@ -182,6 +217,60 @@ impl Deserialize for SerializableH256 {
 	}
 }
 
+#[derive(Clone, Debug)]
+/// Serializable Address.
+pub struct SerializableAddress(Address);
+
+impl<T> From<T> for SerializableAddress where Address: From<T> {
+	fn from(s: T) -> SerializableAddress {
+		SerializableAddress(s.into())
+	}
+}
+
+impl Into<Address> for SerializableAddress {
+	fn into(self) -> Address {
+		self.0
+	}
+}
+
+impl Deref for SerializableAddress {
+	type Target = Address;
+
+	fn deref(&self) -> &Address {
+		&self.0
+	}
+}
+
+impl Serialize for SerializableAddress {
+	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+		serializer.serialize_str(&(*self.0).to_hex())
+	}
+}
+
+impl Deserialize for SerializableAddress {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer {
+		struct HashVisitor;
+
+		impl Visitor for HashVisitor {
+			type Value = SerializableAddress;
+
+			fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+				write!(formatter, "a hex-encoded Address")
+			}
+
+			fn visit_str<E>(self, value: &str) -> Result<Self::Value, E> where E: SerdeError {
+				value.parse().map(|s| SerializableAddress(s)).map_err(SerdeError::custom)
+			}
+
+			fn visit_string<E>(self, value: String) -> Result<Self::Value, E> where E: SerdeError {
+				self.visit_str(value.as_ref())
+			}
+		}
+
+		deserializer.deserialize(HashVisitor)
+	}
+}
+
 #[derive(Clone, Debug)]
 /// Serializable EC scalar/secret key.
 pub struct SerializableSecret(Secret);
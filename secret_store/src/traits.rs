@@ -14,13 +14,17 @@
 // You should have received a copy of the GNU General Public License
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
-use types::all::{Error, RequestSignature, DocumentAddress, DocumentEncryptedKey, DocumentEncryptedKeyShadow};
+use types::all::{Error, RequestSignature, DocumentAddress, DocumentEncryptedKey, DocumentEncryptedKeyShadow, Address};
 
 #[ipc(client_ident="RemoteKeyServer")]
 /// Secret store key server
 pub trait KeyServer: Send + Sync {
 	/// Generate encryption key for given document.
 	fn generate_document_key(&self, signature: &RequestSignature, document: &DocumentAddress, threshold: usize) -> Result<DocumentEncryptedKey, Error>;
+	/// Generate encryption key for given document. `allowed_requestors` are stored with every document key share
+	/// and are allowed to access the document regardless of ACL storage decision (see `OverrideAclStorage`).
+	fn generate_document_key_with_allowed_requestors(&self, signature: &RequestSignature, document: &DocumentAddress, threshold: usize,
+		allowed_requestors: Vec<Address>) -> Result<DocumentEncryptedKey, Error>;
 	/// Request encryption key of given document for given requestor
 	fn document_key(&self, signature: &RequestSignature, document: &DocumentAddress) -> Result<DocumentEncryptedKey, Error>;
 	/// Request encryption key of given document for given requestor.
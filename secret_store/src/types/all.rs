@@ -33,6 +33,8 @@ pub type DocumentEncryptedKey = util::Bytes;
 pub type RequestSignature = ethkey::Signature;
 /// Public key type.
 pub use ethkey::Public;
+/// Requester address type.
+pub type Address = util::Address;
 
 #[derive(Debug, Clone, PartialEq)]
 #[binary]
@@ -48,6 +50,50 @@ pub enum Error {
 	Database(String),
 	/// Internal error
 	Internal(String),
+	/// ACL storage error
+	Acl(AclError),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[binary]
+/// ACL storage error. Contract call errors are distinguished by the failed call phase
+pub enum AclError {
+	/// Contract call has failed
+	Call(String),
+	/// Contract call output could not be decoded
+	Decode(String),
+	/// Too many concurrent sessions are accessing the document
+	TooManyConcurrent,
+	/// Registry points to the contract, which is not trusted
+	UntrustedContract,
+	/// Too many ACL checks are outstanding on the node
+	Overloaded,
+	/// Contract call output is larger than allowed
+	ResponseTooLarge,
+	/// Requester public key is not a valid curve point
+	InvalidRequestor,
+	/// Node is starting and does not serve checks yet
+	ServiceStarting,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Requester of the document, as it is passed to ACL checks.
+pub enum Requester {
+	/// Requester public key.
+	Public(Public),
+	/// Requester address.
+	Address(Address),
+	/// Signature of the document address, made by the requester.
+	Signature(RequestSignature),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Requester with address derived and public key recovered (when possible).
+pub struct ResolvedRequester {
+	/// Requester address.
+	pub address: Address,
+	/// Requester public key, if known.
+	pub public: Option<Public>,
 }
 
 #[derive(Debug, Clone)]
@@ -111,6 +157,72 @@ pub struct DocumentEncryptedKeyShadow {
 	pub decrypt_shadows: Option<Vec<Vec<u8>>>,
 }
 
+impl Requester {
+	/// Create requester from public key in compressed, uncompressed or raw encoding.
+	/// Returns `AclError::InvalidRequestor` if key is not a valid curve point.
+	pub fn from_public_bytes(data: &[u8]) -> Result<Requester, Error> {
+		ethkey::math::public_from_slice(data)
+			.map(Requester::Public)
+			.map_err(|_| AclError::InvalidRequestor.into())
+	}
+
+	/// Get requester with public key in canonical (uncompressed) form, validating it.
+	/// Other requesters are returned as is.
+	pub fn normalized(&self) -> Result<Requester, Error> {
+		match *self {
+			Requester::Public(ref public) => Requester::from_public_bytes(&**public),
+			_ => Ok(self.clone()),
+		}
+	}
+
+	/// Resolve requester of the `document`, recovering public key from signature if required.
+	/// This is the only place, where requester address is derived.
+	pub fn resolve(&self, document: &DocumentAddress) -> Result<ResolvedRequester, Error> {
+		match *self {
+			Requester::Public(ref public) => Ok(ResolvedRequester {
+				address: ethkey::public_to_address(public),
+				public: Some(public.clone()),
+			}),
+			Requester::Address(ref address) => Ok(ResolvedRequester {
+				address: address.clone(),
+				public: None,
+			}),
+			Requester::Signature(ref signature) => ethkey::recover(signature, document)
+				.map_err(|_| Error::BadSignature)
+				.map(|public| ResolvedRequester {
+					address: ethkey::public_to_address(&public),
+					public: Some(public),
+				}),
+		}
+	}
+
+	/// Get requester address.
+	pub fn address(&self, document: &DocumentAddress) -> Result<Address, Error> {
+		self.resolve(document).map(|requester| requester.address)
+	}
+
+	/// Get requester public key, if it is known or could be recovered.
+	pub fn public(&self, document: &DocumentAddress) -> Result<Option<Public>, Error> {
+		self.resolve(document).map(|requester| requester.public)
+	}
+}
+
+impl From<Public> for Requester {
+	fn from(public: Public) -> Self {
+		Requester::Public(public)
+	}
+}
+
+impl From<ResolvedRequester> for Requester {
+	/// Convert back to requester, which is cheap to resolve again.
+	fn from(requester: ResolvedRequester) -> Self {
+		match requester.public {
+			Some(public) => Requester::Public(public),
+			None => Requester::Address(requester.address),
+		}
+	}
+}
+
 impl fmt::Display for Error {
 	fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
 		match *self {
@@ -119,10 +231,32 @@ impl fmt::Display for Error {
 			Error::DocumentNotFound => write!(f, "Document not found"),
 			Error::Database(ref msg) => write!(f, "Database error: {}", msg),
 			Error::Internal(ref msg) => write!(f, "Internal error: {}", msg),
+			Error::Acl(ref err) => write!(f, "ACL error: {}", err),
 		}
 	}
 }
 
+impl fmt::Display for AclError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+		match *self {
+			AclError::Call(ref msg) => write!(f, "contract call has failed: {}", msg),
+			AclError::Decode(ref msg) => write!(f, "failed to decode contract output: {}", msg),
+			AclError::TooManyConcurrent => write!(f, "too many concurrent sessions"),
+			AclError::UntrustedContract => write!(f, "registry points to untrusted contract"),
+			AclError::Overloaded => write!(f, "too many outstanding checks"),
+			AclError::ResponseTooLarge => write!(f, "contract call output is too large"),
+			AclError::InvalidRequestor => write!(f, "requester public key is invalid"),
+			AclError::ServiceStarting => write!(f, "node is starting"),
+		}
+	}
+}
+
+impl From<AclError> for Error {
+	fn from(err: AclError) -> Self {
+		Error::Acl(err)
+	}
+}
+
 impl From<ethkey::Error> for Error {
 	fn from(err: ethkey::Error) -> Self {
 		Error::Internal(err.into())
@@ -143,3 +277,112 @@ impl Into<String> for Error {
 		format!("{}", self)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use serde_json;
+	use ethkey::{self, Random, Generator, KeyPair};
+	use serialization::SerializableRequester;
+	use super::{Error, AclError, DocumentAddress, Requester, ResolvedRequester, RequestSignature};
+
+	#[test]
+	fn public_requester_is_resolved() {
+		let key_pair: KeyPair = Random.generate().unwrap();
+		let requester = Requester::Public(key_pair.public().clone());
+		assert_eq!(requester.resolve(&DocumentAddress::from(1)), Ok(ResolvedRequester {
+			address: key_pair.address(),
+			public: Some(key_pair.public().clone()),
+		}));
+	}
+
+	#[test]
+	fn address_requester_is_resolved() {
+		let key_pair: KeyPair = Random.generate().unwrap();
+		let requester = Requester::Address(key_pair.address());
+		assert_eq!(requester.resolve(&DocumentAddress::from(1)), Ok(ResolvedRequester {
+			address: key_pair.address(),
+			public: None,
+		}));
+	}
+
+	#[test]
+	fn signature_requester_is_resolved() {
+		let key_pair: KeyPair = Random.generate().unwrap();
+		let document = DocumentAddress::from(1);
+		let requester = Requester::Signature(ethkey::sign(key_pair.secret(), &document).unwrap());
+		assert_eq!(requester.resolve(&document), Ok(ResolvedRequester {
+			address: key_pair.address(),
+			public: Some(key_pair.public().clone()),
+		}));
+
+		// signature of other document recovers other requester
+		assert!(requester.address(&DocumentAddress::from(2)).unwrap() != key_pair.address());
+	}
+
+	#[test]
+	fn invalid_signature_is_rejected() {
+		let requester = Requester::Signature(RequestSignature::default());
+		assert_eq!(requester.resolve(&DocumentAddress::from(1)), Err(Error::BadSignature));
+	}
+
+	#[test]
+	fn resolved_requester_is_converted_back() {
+		let key_pair: KeyPair = Random.generate().unwrap();
+		let document = DocumentAddress::from(1);
+		let requester = Requester::Signature(ethkey::sign(key_pair.secret(), &document).unwrap());
+		assert_eq!(Requester::from(requester.resolve(&document).unwrap()), Requester::Public(key_pair.public().clone()));
+
+		let requester = Requester::Address(key_pair.address());
+		assert_eq!(Requester::from(requester.resolve(&document).unwrap()), requester);
+	}
+
+	#[test]
+	fn public_encodings_are_normalized() {
+		let key_pair: KeyPair = Random.generate().unwrap();
+		let public = key_pair.public();
+		let mut uncompressed = vec![4];
+		uncompressed.extend_from_slice(&**public);
+		let mut compressed = vec![2 + (public[63] & 1)];
+		compressed.extend_from_slice(&public[0..32]);
+
+		let expected = Requester::Public(public.clone());
+		assert_eq!(Requester::from_public_bytes(&**public), Ok(expected.clone()));
+		assert_eq!(Requester::from_public_bytes(&uncompressed), Ok(expected.clone()));
+		assert_eq!(Requester::from_public_bytes(&compressed), Ok(expected.clone()));
+		assert_eq!(expected.normalized(), Ok(expected.clone()));
+		assert_eq!(Requester::from_public_bytes(&compressed).unwrap().address(&DocumentAddress::from(1)),
+			Ok(key_pair.address()));
+	}
+
+	#[test]
+	fn invalid_public_is_rejected() {
+		let invalid = Err(Error::Acl(AclError::InvalidRequestor));
+		let key_pair: KeyPair = Random.generate().unwrap();
+		let mut compressed = vec![5];
+		compressed.extend_from_slice(&key_pair.public()[0..32]);
+		assert_eq!(Requester::from_public_bytes(&compressed), invalid);
+		assert_eq!(Requester::from_public_bytes(&compressed[1..]), invalid);
+		assert_eq!(Requester::from_public_bytes(&[0; 65]), invalid);
+		assert_eq!(Requester::Public(Default::default()).normalized(), invalid);
+
+		// other requesters are not affected
+		let requester = Requester::Address(key_pair.address());
+		assert_eq!(requester.normalized(), Ok(requester));
+	}
+
+	#[test]
+	fn requester_serialization_roundtrip() {
+		let key_pair: KeyPair = Random.generate().unwrap();
+		let requesters = vec![
+			Requester::Public(key_pair.public().clone()),
+			Requester::Address(key_pair.address()),
+			Requester::Signature(ethkey::sign(key_pair.secret(), &DocumentAddress::from(1)).unwrap()),
+		];
+
+		for requester in requesters {
+			let serialized = serde_json::to_string(&SerializableRequester::from(requester.clone())).unwrap();
+			let deserialized: SerializableRequester = serde_json::from_str(&serialized).unwrap();
+			assert_eq!(Requester::from(deserialized), requester);
+		}
+	}
+}